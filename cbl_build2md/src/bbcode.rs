@@ -0,0 +1,223 @@
+//! BBCode for posting a build on the DDO forums.
+//!
+//! Names are escaped (see `escape()`), except in `[code]` blocks, which the
+//! forums don't look for tags in.
+
+use crate::sections::{
+    build_type_heading,
+    format_ranks,
+    level_order_grid,
+    tier_heading,
+    Sections,
+};
+use cbl_build_parser::{Ability, CharacterBuild};
+use std::fmt::Write;
+
+pub fn build_to_bbcode(build: &CharacterBuild) -> String {
+    let sections = Sections::new(build);
+    let mut out = String::with_capacity(4_096);
+
+    write_overview(&sections, &mut out);
+    write_level_order(&sections, &mut out);
+    write_stats(&sections, &mut out);
+    write_skills(&sections, &mut out);
+    write_feats(&sections, &mut out);
+    write_spells(&sections, &mut out);
+    write_enhancements(&sections, &mut out);
+
+    out
+}
+
+fn write_overview(sections: &Sections, out: &mut String) {
+    let overview = &sections.overview;
+
+    writeln!(out, "[size=5][b]{}[/b][/size]", escape(&overview.name)).unwrap();
+    writeln!(out, "{}", escape(&overview.split)).unwrap();
+    writeln!(out, "{}", escape(&overview.alignment)).unwrap();
+}
+
+fn write_level_order(sections: &Sections, out: &mut String) {
+    out.push_str("\n[b]Level Order[/b]\n[table]\n");
+    for row in level_order_grid(&sections.level_order) {
+        out.push_str("[tr]");
+        for cell in row {
+            match cell {
+                Some((level, class)) => write!(
+                    out,
+                    "[td]{}) {}[/td]",
+                    level,
+                    escape(&class.to_string()),
+                )
+                .unwrap(),
+                None => out.push_str("[td][/td]"),
+            }
+        }
+        out.push_str("[/tr]\n");
+    }
+    out.push_str("[/table]\n");
+}
+
+fn write_stats(sections: &Sections, out: &mut String) {
+    let stats = &sections.stats;
+
+    let mut header = vec![String::new()];
+    header.extend(
+        stats
+            .columns
+            .iter()
+            .map(|(build_type, _)| build_type_heading(*build_type)),
+    );
+    header.push("Level Up".to_owned());
+
+    let mut rows = vec![header];
+    for i in 0..Ability::ALL.len().max(stats.levelups.len()) {
+        let mut row = vec![Ability::ALL
+            .get(i)
+            .map_or_else(String::new, |a| a.to_string())];
+        row.extend(stats.columns.iter().map(|(_, scores)| {
+            scores.get(i).map_or_else(String::new, u8::to_string)
+        }));
+        row.push(stats.levelups.get(i).map_or_else(String::new, |(l, a)| {
+            format!("{}: {}", l, a.abbreviation())
+        }));
+        rows.push(row);
+    }
+
+    out.push_str("\n[b]Stats[/b]\n");
+    write_code_table(&rows, out);
+}
+
+fn write_skills(sections: &Sections, out: &mut String) {
+    let skills = &sections.skills;
+    if skills.rows.is_empty() {
+        return;
+    }
+
+    let mut header = vec![String::new()];
+    header.extend((1..=skills.classes.len()).map(|l| l.to_string()));
+    header.push(String::new());
+    let mut classes = vec![String::new()];
+    classes.extend(skills.initials().iter().map(char::to_string));
+    classes.push(String::new());
+
+    let mut rows = vec![header, classes];
+    for skill_row in skills.rows.iter() {
+        let mut row = vec![skill_row.skill.to_string()];
+        row.extend(skill_row.ranks.iter().map(|&r| format_ranks(r)));
+        row.push(format_ranks(skill_row.total));
+        rows.push(row);
+    }
+    let mut spent = vec![String::new()];
+    spent.extend(skills.spent.iter().map(u8::to_string));
+    spent.push(String::new());
+    rows.push(spent);
+
+    out.push_str("\n[b]Skills[/b]\n");
+    write_code_table(&rows, out);
+}
+
+fn write_feats(sections: &Sections, out: &mut String) {
+    if sections.feats.is_empty() {
+        return;
+    }
+
+    out.push_str("\n[b]Feats[/b]\n[list]\n");
+    for feat in sections.feats.iter() {
+        let level = match feat.class {
+            Some(class) => format!("{} {}", feat.level, class),
+            None => feat.level.to_string(),
+        };
+        writeln!(out, "[*]{}: {}", escape(&level), escape(&feat.name))
+            .unwrap();
+    }
+    out.push_str("[/list]\n");
+}
+
+fn write_spells(sections: &Sections, out: &mut String) {
+    if sections.spells.is_empty() {
+        return;
+    }
+
+    out.push_str("\n[b]Spells[/b]\n[list]\n");
+    for row in sections.spells.iter() {
+        writeln!(
+            out,
+            "[*]{} {}: {}",
+            escape(&row.class.to_string()),
+            row.spell_level,
+            escape(&row.spells.join(", ")),
+        )
+        .unwrap();
+    }
+    out.push_str("[/list]\n");
+}
+
+/// Each tree goes in a spoiler, since the lists are long. Its title is
+/// quoted, so that a `]` in it doesn't end the tag early.
+fn write_enhancements(sections: &Sections, out: &mut String) {
+    if sections.enhancements.is_empty() {
+        return;
+    }
+
+    writeln!(out, "\n[b]Enhancements ({} AP)[/b]", sections.total_ap())
+        .unwrap();
+    for tree in sections.enhancements.iter() {
+        writeln!(
+            out,
+            "[spoiler=\"{} ({} AP)\"]",
+            tree.name.replace('"', "'"),
+            tree.ap,
+        )
+        .unwrap();
+        for (tier, names) in tree.tiers.iter() {
+            writeln!(
+                out,
+                "{}: {}",
+                tier_heading(*tier),
+                escape(&names.join(", ")),
+            )
+            .unwrap();
+        }
+        out.push_str("[/spoiler]\n");
+    }
+}
+
+/// Writes a table as monospaced text in a `[code]` block. The first column
+/// is aligned to the left, and the rest to the right.
+fn write_code_table(rows: &[Vec<String>], out: &mut String) {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|col| {
+            rows.iter()
+                .filter_map(|row| row.get(col))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    out.push_str("[code]\n");
+    for row in rows.iter() {
+        let mut line = String::new();
+        for (col, cell) in row.iter().enumerate() {
+            let padding = " ".repeat(widths[col] - cell.chars().count());
+            if col == 0 {
+                line.push_str(cell);
+                line.push_str(&padding);
+            } else {
+                line.push_str("  ");
+                line.push_str(&padding);
+                line.push_str(cell);
+            }
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out.push_str("[/code]\n");
+}
+
+/// Keeps the forums from reading a `[` as the start of a tag, by wrapping it
+/// in `[plain]`.
+fn escape(s: &str) -> String {
+    s.replace('[', "[plain][[/plain]")
+}
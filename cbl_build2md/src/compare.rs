@@ -0,0 +1,315 @@
+//! A side-by-side comparison of several builds, worked out once so that each
+//! output format only has to lay out its matrix: one column per build, and
+//! one row per thing that the builds are compared by.
+
+use crate::sections::{tier_heading, Sections};
+use cbl_build_parser::{
+    content::{self, Registry, Save},
+    order::format_split,
+    Ability,
+    CharacterBuild,
+    Class,
+    SecondaryFeatType,
+    HEROIC_LEVEL_CAP,
+};
+use std::cmp::Reverse;
+
+/// The saves, in the order that their rows are listed.
+const SAVES: [Save; 3] = [Save::Fortitude, Save::Reflex, Save::Will];
+
+pub struct ComparisonRow {
+    pub label: String,
+    /// One cell for each build, in the order that the builds were given.
+    /// Empty where a build has nothing to show.
+    pub cells: Vec<String>,
+}
+
+pub struct ComparisonGroup {
+    pub title: &'static str,
+    pub rows:  Vec<ComparisonRow>,
+}
+
+pub struct Comparison {
+    /// The name of each build, which heads its column.
+    pub names:  Vec<String>,
+    pub groups: Vec<ComparisonGroup>,
+}
+
+impl Comparison {
+    pub fn new(builds: &[CharacterBuild]) -> Self {
+        Self::new_with(builds, &content::registry())
+    }
+
+    pub fn new_with(builds: &[CharacterBuild], content: &Registry) -> Self {
+        let sections = builds
+            .iter()
+            .map(|b| Sections::new_with(b, content))
+            .collect::<Vec<_>>();
+
+        Self {
+            names:  sections.iter().map(|s| s.overview.name.clone()).collect(),
+            groups: vec![
+                ComparisonGroup {
+                    title: "Overview",
+                    rows:  overview(builds),
+                },
+                ComparisonGroup {
+                    title: "Ability Scores",
+                    rows:  ability_scores(builds, content),
+                },
+                ComparisonGroup {
+                    title: "Combat",
+                    rows:  combat(builds, content),
+                },
+                ComparisonGroup {
+                    title: "Feats",
+                    rows:  feats(builds, content),
+                },
+                ComparisonGroup {
+                    title: "Enhancements",
+                    rows:  enhancements(builds, &sections, content),
+                },
+            ],
+        }
+    }
+}
+
+fn overview(builds: &[CharacterBuild]) -> Vec<ComparisonRow> {
+    vec![
+        row("Race", builds, |b| b.race.to_string()),
+        row("Alignment", builds, |b| b.alignment.to_string()),
+        row("Classes", builds, |b| format_split(&b.class_split())),
+        row("Levels", builds, |b| b.max_levels.to_string()),
+    ]
+}
+
+/// The final ability scores of each build's preferred build type, with
+/// racial modifiers, ability score increases and tomes.
+fn ability_scores(
+    builds: &[CharacterBuild],
+    content: &Registry,
+) -> Vec<ComparisonRow> {
+    let scores = builds
+        .iter()
+        .map(|b| b.at_level_with(b.max_levels, content).ability_scores)
+        .collect::<Vec<_>>();
+
+    Ability::ALL
+        .iter()
+        .map(|&a| ComparisonRow {
+            label: a.to_string(),
+            cells: scores
+                .iter()
+                .map(|s| {
+                    s.as_ref().map_or_else(String::new, |s| s[a].to_string())
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// The base attack bonus, base saves and hit points that the heroic levels
+/// grant. Hit points are each level's full hit die plus the Constitution
+/// modifier, without feats, enhancements or gear.
+fn combat(
+    builds: &[CharacterBuild],
+    content: &Registry,
+) -> Vec<ComparisonRow> {
+    let heroic = builds
+        .iter()
+        .map(|b| b.at_level_with(HEROIC_LEVEL_CAP, content))
+        .collect::<Vec<_>>();
+    // `None` for a build with a class that isn't in `content`
+    let class_total = |f: &dyn Fn(Class, u8) -> Option<i16>| {
+        heroic
+            .iter()
+            .map(|h| h.class_levels.iter().map(|&(c, n)| f(c, n)).sum())
+            .collect::<Vec<Option<i16>>>()
+    };
+
+    let mut rows = vec![ComparisonRow {
+        label: "Base Attack".to_owned(),
+        cells: format_totals(class_total(&|c, n| {
+            content
+                .class(c)
+                .map(|ci| i16::from(ci.base_attack.at_level(n)))
+        })),
+    }];
+    rows.extend(SAVES.iter().map(|&save| ComparisonRow {
+        label: save.to_string(),
+        cells: format_totals(class_total(&|c, n| {
+            content.class(c).map(|ci| {
+                i16::from(base_save(ci.good_saves.contains(&save), n))
+            })
+        })),
+    }));
+
+    let hit_dice = class_total(&|c, n| {
+        content
+            .class(c)
+            .map(|ci| i16::from(ci.hit_die) * i16::from(n))
+    });
+    let hit_points = heroic
+        .iter()
+        .zip(hit_dice)
+        .map(|(h, dice)| {
+            let con = h.ability_scores.as_ref()?[Ability::Con];
+            let modifier = (i16::from(con) - 10).div_euclid(2);
+
+            Some(dice? + modifier * i16::from(h.level))
+        })
+        .collect();
+    rows.push(ComparisonRow {
+        label: "Hit Points".to_owned(),
+        cells: format_totals(hit_points),
+    });
+
+    rows
+}
+
+fn format_totals(totals: Vec<Option<i16>>) -> Vec<String> {
+    totals
+        .into_iter()
+        .map(|t| t.map_or_else(String::new, |t| t.to_string()))
+        .collect()
+}
+
+/// The base save granted by `class_levels` levels of a class, with a good or
+/// a poor progression.
+fn base_save(good: bool, class_levels: u8) -> u8 {
+    if good {
+        2 + class_levels / 2
+    } else {
+        class_levels / 3
+    }
+}
+
+/// One row for each feat slot that any of the builds fill, by the character
+/// level that it is taken at and the class or race that grants it, if any.
+fn feats(builds: &[CharacterBuild], content: &Registry) -> Vec<ComparisonRow> {
+    let mut slots: Vec<(u8, String)> = Vec::new();
+    let mut slot_feats: Vec<Vec<Vec<&str>>> = Vec::new();
+    for (i, build) in builds.iter().enumerate() {
+        let top = build.at_level_with(build.max_levels, content);
+        for &(level, feat) in top.feats.iter() {
+            let slot = (level, slot_label(level, &feat.secondary_type));
+            let index = match slots.iter().position(|x| *x == slot) {
+                Some(index) => index,
+                None => {
+                    slots.push(slot);
+                    slot_feats.push(vec![Vec::new(); builds.len()]);
+                    slots.len() - 1
+                },
+            };
+            slot_feats[index][i].push(&feat.name);
+        }
+    }
+
+    let mut rows = slots
+        .into_iter()
+        .zip(slot_feats)
+        .map(|((level, label), feats)| {
+            let row = ComparisonRow {
+                label,
+                cells: feats.iter().map(|fs| fs.join(", ")).collect(),
+            };
+
+            (level, row)
+        })
+        .collect::<Vec<_>>();
+    // Stable, so slots of the same level stay in the order first seen
+    rows.sort_by_key(|(level, _)| *level);
+
+    rows.into_iter().map(|(_, row)| row).collect()
+}
+
+/// E.g. "1", "6 Fighter", "1 Human" or "21 Epic".
+fn slot_label(level: u8, secondary_type: &SecondaryFeatType) -> String {
+    match secondary_type {
+        SecondaryFeatType::Heroic => level.to_string(),
+        SecondaryFeatType::Epic => format!("{} Epic", level),
+        SecondaryFeatType::Destiny => format!("{} Destiny", level),
+        SecondaryFeatType::Legend => format!("{} Legend", level),
+        SecondaryFeatType::Class(c) => format!("{} {}", level, c),
+        SecondaryFeatType::Race(r) => format!("{} {}", level, r),
+    }
+}
+
+/// The tier 5 tree, the total action points, then the action points spent in
+/// each tree that any of the builds spend points in, from the most points
+/// across every build to the fewest.
+fn enhancements(
+    builds: &[CharacterBuild],
+    sections: &[Sections],
+    content: &Registry,
+) -> Vec<ComparisonRow> {
+    let mut trees = Vec::new();
+    for build in builds.iter() {
+        for tree in build.enhancements.trees.iter() {
+            if !trees.contains(&tree.name) {
+                trees.push(tree.name);
+            }
+        }
+    }
+    let mut tree_rows = trees
+        .into_iter()
+        .filter_map(|name| {
+            let ap = builds
+                .iter()
+                .map(|b| {
+                    b.enhancements
+                        .trees
+                        .iter()
+                        .find(|et| et.name == name)
+                        .map_or(0, |et| et.action_points_with(content))
+                })
+                .collect::<Vec<_>>();
+            let total: u16 = ap.iter().sum();
+            if total == 0 {
+                return None;
+            }
+
+            let row = ComparisonRow {
+                label: name.to_string(),
+                cells: ap.iter().map(|&ap| format_ap(ap)).collect(),
+            };
+
+            Some((total, row))
+        })
+        .collect::<Vec<_>>();
+    tree_rows.sort_by_key(|(total, _)| Reverse(*total));
+
+    let mut rows = vec![
+        row(&tier_heading(5), builds, |b| {
+            b.tier_five.map_or_else(String::new, |t| t.to_string())
+        }),
+        ComparisonRow {
+            label: "Total AP".to_owned(),
+            cells: sections.iter().map(|s| format_ap(s.total_ap())).collect(),
+        },
+    ];
+    rows.extend(tree_rows.into_iter().map(|(_, row)| row));
+
+    rows
+}
+
+/// A row with a cell for each build.
+fn row(
+    label: &str,
+    builds: &[CharacterBuild],
+    cell: impl Fn(&CharacterBuild) -> String,
+) -> ComparisonRow {
+    ComparisonRow {
+        label: label.to_owned(),
+        cells: builds.iter().map(cell).collect(),
+    }
+}
+
+/// Empty for no points, so that the trees that a build skips stand out.
+fn format_ap(ap: u16) -> String {
+    if ap == 0 {
+        String::new()
+    } else {
+        ap.to_string()
+    }
+}
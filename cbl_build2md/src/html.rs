@@ -0,0 +1,296 @@
+//! A single HTML page for a build, with its stylesheet embedded so that it
+//! needs no other files.
+
+use crate::{
+    compare::Comparison,
+    sections::{
+        build_type_heading,
+        format_ranks,
+        level_order_grid,
+        tier_heading,
+        Sections,
+    },
+};
+use cbl_build_parser::{Ability, CharacterBuild};
+use std::fmt::Write;
+
+const STYLESHEET: &str = "\
+body { font-family: sans-serif; color: #222; background: #fdfdfb; }
+body { max-width: 60em; margin: 2em auto; padding: 0 1em; }
+header p { margin: 0.25em 0; font-size: 1.1em; }
+h2 { border-bottom: 1px solid #ccc; padding-bottom: 0.2em; }
+table { border-collapse: collapse; margin: 0.5em 0 1em; }
+th, td { border: 1px solid #ccc; padding: 0.2em 0.5em; }
+th { background: #eee; text-align: left; }
+td.num, th.num { text-align: right; }
+table.skills td, table.skills th { padding: 0.2em 0.3em; font-size: 0.9em; }
+tr.total td { font-weight: bold; background: #f4f4f4; }
+table.comparison td { vertical-align: top; }
+";
+
+pub fn build_to_html(build: &CharacterBuild) -> String {
+    let sections = Sections::new(build);
+    let mut out = String::with_capacity(16_384);
+
+    out.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n");
+    out.push_str("<meta charset=\"utf-8\">\n");
+    writeln!(out, "<title>{}</title>", escape(&sections.overview.name))
+        .unwrap();
+    writeln!(out, "<style>\n{}</style>", STYLESHEET).unwrap();
+    out.push_str("</head>\n<body>\n");
+
+    write_overview(&sections, &mut out);
+    write_level_order(&sections, &mut out);
+    write_stats(&sections, &mut out);
+    write_skills(&sections, &mut out);
+    write_feats(&sections, &mut out);
+    write_spells(&sections, &mut out);
+    write_enhancements(&sections, &mut out);
+
+    out.push_str("</body>\n</html>\n");
+
+    out
+}
+
+/// A page with one table for each group of rows, with a column for each
+/// build.
+pub fn comparison_to_html(comparison: &Comparison) -> String {
+    let title = comparison.names.join(" vs. ");
+    let mut out = String::with_capacity(16_384);
+
+    out.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n");
+    out.push_str("<meta charset=\"utf-8\">\n");
+    writeln!(out, "<title>{}</title>", escape(&title)).unwrap();
+    writeln!(out, "<style>\n{}</style>", STYLESHEET).unwrap();
+    out.push_str("</head>\n<body>\n");
+    writeln!(out, "<header>\n<h1>{}</h1>\n</header>", escape(&title)).unwrap();
+
+    for group in comparison.groups.iter() {
+        writeln!(
+            out,
+            "<section>\n<h2>{}</h2>\n<table class=\"comparison\">",
+            escape(group.title),
+        )
+        .unwrap();
+        out.push_str("<tr><th></th>");
+        for name in comparison.names.iter() {
+            write!(out, "<th>{}</th>", escape(name)).unwrap();
+        }
+        out.push_str("</tr>\n");
+        for row in group.rows.iter() {
+            write!(out, "<tr><th>{}</th>", escape(&row.label)).unwrap();
+            for cell in row.cells.iter() {
+                write!(out, "<td>{}</td>", escape(cell)).unwrap();
+            }
+            out.push_str("</tr>\n");
+        }
+        out.push_str("</table>\n</section>\n");
+    }
+
+    out.push_str("</body>\n</html>\n");
+
+    out
+}
+
+fn write_overview(sections: &Sections, out: &mut String) {
+    let overview = &sections.overview;
+
+    out.push_str("<header>\n");
+    writeln!(out, "<h1>{}</h1>", escape(&overview.name)).unwrap();
+    writeln!(out, "<p>{}</p>", escape(&overview.split)).unwrap();
+    writeln!(out, "<p>{}</p>", escape(&overview.alignment)).unwrap();
+    out.push_str("</header>\n");
+}
+
+fn write_level_order(sections: &Sections, out: &mut String) {
+    out.push_str("<section>\n<h2>Level Order</h2>\n<table>\n");
+    for row in level_order_grid(&sections.level_order) {
+        out.push_str("<tr>");
+        for cell in row {
+            match cell {
+                Some((level, class)) => write!(
+                    out,
+                    "<td>{}) {}</td>",
+                    level,
+                    escape(&class.to_string()),
+                )
+                .unwrap(),
+                None => out.push_str("<td></td>"),
+            }
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</table>\n</section>\n");
+}
+
+fn write_stats(sections: &Sections, out: &mut String) {
+    let stats = &sections.stats;
+
+    out.push_str("<section>\n<h2>Stats</h2>\n<table>\n<tr><th></th>");
+    for (build_type, _) in stats.columns.iter() {
+        write!(
+            out,
+            "<th class=\"num\">{}</th>",
+            build_type_heading(*build_type)
+        )
+        .unwrap();
+    }
+    out.push_str("<th class=\"num\">Level Up</th></tr>\n");
+
+    let rows = Ability::ALL.len().max(stats.levelups.len());
+    for i in 0..rows {
+        out.push_str("<tr>");
+        match Ability::ALL.get(i) {
+            Some(ability) => write!(out, "<th>{}</th>", ability).unwrap(),
+            None => out.push_str("<th></th>"),
+        }
+        for (_, scores) in stats.columns.iter() {
+            match scores.get(i) {
+                Some(score) =>
+                    write!(out, "<td class=\"num\">{}</td>", score).unwrap(),
+                None => out.push_str("<td></td>"),
+            }
+        }
+        match stats.levelups.get(i) {
+            Some((level, ability)) => write!(
+                out,
+                "<td class=\"num\">{}: {}</td>",
+                level,
+                ability.abbreviation(),
+            )
+            .unwrap(),
+            None => out.push_str("<td></td>"),
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</table>\n</section>\n");
+}
+
+fn write_skills(sections: &Sections, out: &mut String) {
+    let skills = &sections.skills;
+
+    out.push_str("<section>\n<h2>Skills</h2>\n<table class=\"skills\">\n");
+    out.push_str("<tr><th></th>");
+    for level in 1..=skills.classes.len() {
+        write!(out, "<th class=\"num\">{}</th>", level).unwrap();
+    }
+    out.push_str("<th class=\"num\">Ranks</th></tr>\n<tr><th></th>");
+    for (initial, class) in skills.initials().iter().zip(skills.classes.iter())
+    {
+        write!(
+            out,
+            "<td class=\"num\" title=\"{}\">{}</td>",
+            escape(&class.to_string()),
+            escape(&initial.to_string()),
+        )
+        .unwrap();
+    }
+    out.push_str("<td></td></tr>\n");
+
+    for row in skills.rows.iter() {
+        write!(out, "<tr><th>{}</th>", row.skill).unwrap();
+        for &ranks in row.ranks.iter() {
+            write!(out, "<td class=\"num\">{}</td>", format_ranks(ranks))
+                .unwrap();
+        }
+        writeln!(
+            out,
+            "<td class=\"num\">{}</td></tr>",
+            format_ranks(row.total),
+        )
+        .unwrap();
+    }
+
+    out.push_str("<tr class=\"total\"><th>Points</th>");
+    for points in skills.spent.iter() {
+        write!(out, "<td class=\"num\">{}</td>", points).unwrap();
+    }
+    out.push_str("<td></td></tr>\n</table>\n</section>\n");
+}
+
+fn write_feats(sections: &Sections, out: &mut String) {
+    out.push_str("<section>\n<h2>Feats</h2>\n<table>\n");
+    for feat in sections.feats.iter() {
+        let level = match feat.class {
+            Some(class) => format!("{} {}", feat.level, class),
+            None => feat.level.to_string(),
+        };
+        writeln!(
+            out,
+            "<tr><th>{}</th><td>{}</td></tr>",
+            escape(&level),
+            escape(&feat.name),
+        )
+        .unwrap();
+    }
+    out.push_str("</table>\n</section>\n");
+}
+
+fn write_spells(sections: &Sections, out: &mut String) {
+    if sections.spells.is_empty() {
+        return;
+    }
+
+    out.push_str("<section>\n<h2>Spells</h2>\n<table>\n");
+    for row in sections.spells.iter() {
+        writeln!(
+            out,
+            "<tr><th>{} {}</th><td>{}</td></tr>",
+            escape(&row.class.to_string()),
+            row.spell_level,
+            escape(&row.spells.join(", ")),
+        )
+        .unwrap();
+    }
+    out.push_str("</table>\n</section>\n");
+}
+
+fn write_enhancements(sections: &Sections, out: &mut String) {
+    if sections.enhancements.is_empty() {
+        return;
+    }
+
+    writeln!(
+        out,
+        "<section>\n<h2>Enhancements ({} AP)</h2>",
+        sections.total_ap(),
+    )
+    .unwrap();
+    for tree in sections.enhancements.iter() {
+        writeln!(
+            out,
+            "<h3>{} ({} AP)</h3>\n<table>",
+            escape(&tree.name),
+            tree.ap
+        )
+        .unwrap();
+        for (tier, names) in tree.tiers.iter() {
+            writeln!(
+                out,
+                "<tr><th>{}</th><td>{}</td></tr>",
+                tier_heading(*tier),
+                escape(&names.join(", ")),
+            )
+            .unwrap();
+        }
+        out.push_str("</table>\n");
+    }
+    out.push_str("</section>\n");
+}
+
+/// Escapes the characters that HTML gives a meaning to.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
@@ -1,130 +1,261 @@
 extern crate cbl_build_parser;
 
-use cbl_build_parser::Class;
+mod bbcode;
+mod compare;
+mod html;
+mod sections;
+#[cfg(test)]
+mod tests;
+
+use cbl_build_parser::{content, CharacterBuild};
+use compare::Comparison;
+use sections::{level_order_grid, Sections};
 use std::{
     env,
     fs::File,
     io::{prelude::*, BufReader},
+    process,
 };
 
 fn main() {
-    let mut in_filename = None;
-    let mut out_filename = None;
-    for (i, arg) in env::args().enumerate() {
-        match i {
-            1 => in_filename = Some(arg),
-            2 => out_filename = Some(arg),
-            _ => (),
+    let mut filenames = Vec::new();
+    let mut content_filename = None;
+    let mut format = None;
+    let mut compare = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--content" => content_filename = args.next(),
+            "--format" => format = args.next(),
+            "--compare" => compare = true,
+            _ => filenames.push(arg),
+        }
+    }
+
+    if let Some(content_filename) = content_filename {
+        let mut registry = content::Registry::bundled();
+        if let Err(e) = registry.patch_file(&content_filename) {
+            eprintln!("{}: {}", content_filename, e);
+            process::exit(1);
         }
+        content::set_registry(registry);
     }
 
-    let in_file = File::open(in_filename.unwrap()).unwrap();
+    // When comparing, every file but the last is a build to compare
+    let (out_filename, in_filenames) = if compare {
+        match filenames.split_last() {
+            Some((out, ins)) if !ins.is_empty() => (out.clone(), ins.to_vec()),
+            _ => {
+                eprintln!("Usage: cbl_build2md --compare IN... OUT");
+                process::exit(2);
+            },
+        }
+    } else {
+        filenames.truncate(2);
+        let mut filenames = filenames.into_iter();
+        let in_filename = filenames.next().unwrap();
+
+        (filenames.next().unwrap(), vec![in_filename])
+    };
+
+    let builds = in_filenames
+        .iter()
+        .map(|f| read_build(f))
+        .collect::<Vec<_>>();
+    let output = if compare {
+        let comparison = Comparison::new(&builds);
+        match format.as_deref() {
+            None | Some("markdown") => comparison_to_markdown(&comparison),
+            Some("html") => html::comparison_to_html(&comparison),
+            Some(f) => {
+                eprintln!(
+                    "Unknown format for a comparison: {} (expected markdown \
+                     or html)",
+                    f
+                );
+                process::exit(2);
+            },
+        }
+    } else {
+        build_to(&builds[0], format.as_deref())
+    };
+
+    let mut out_file = File::create(out_filename).unwrap();
+    out_file.write_all(output.as_bytes()).unwrap();
+}
+
+fn read_build(filename: &str) -> CharacterBuild {
+    let in_file = File::open(filename).unwrap();
     let mut in_file_buf = BufReader::new(in_file);
-    let build = cbl_build_parser::parse(&mut in_file_buf).unwrap();
-    let markdown = build_to_markdown(&build);
 
-    let mut out_file = File::create(out_filename.unwrap()).unwrap();
-    out_file.write_all(markdown.as_bytes()).unwrap();
+    cbl_build_parser::parse(&mut in_file_buf).unwrap()
 }
 
-fn build_to_markdown(build: &cbl_build_parser::CharacterBuild) -> String {
+fn build_to(build: &CharacterBuild, format: Option<&str>) -> String {
+    match format {
+        None | Some("markdown") => build_to_markdown(build),
+        Some("html") => html::build_to_html(build),
+        Some("bbcode") => bbcode::build_to_bbcode(build),
+        Some(f) => {
+            eprintln!(
+                "Unknown format: {} (expected markdown, html or bbcode)",
+                f
+            );
+            process::exit(2);
+        },
+    }
+}
+
+fn build_to_markdown(build: &CharacterBuild) -> String {
+    let sections = Sections::new(build);
     let mut out = String::with_capacity(1_024);
 
-    write_overview(build, &mut out);
-    write_level_order(build, &mut out);
+    write_overview(&sections, &mut out);
+    write_level_order(&sections, &mut out);
 
     out
 }
 
-fn write_overview(build: &cbl_build_parser::CharacterBuild, out: &mut String) {
+fn write_overview(sections: &Sections, out: &mut String) {
+    let overview = &sections.overview;
+
     out.push_str("# ");
-    out.push_str(&build.name);
+    out.push_str(&overview.name);
     out.push_str("\n\n");
+    out.push_str(&overview.split);
+    out.push_str("\n\n");
+    out.push_str(&overview.alignment);
+    out.push('\n');
+}
 
-    let mut split = [None, None, None];
-    for class in build.levels.iter() {
-        split
-            .iter_mut()
-            .find(|mcl| {
-                if let Some((c, _)) = mcl {
-                    c == class
-                } else {
-                    true
-                }
-            })
-            .and_then(|mcl| {
-                if let Some((_, l)) = mcl {
-                    *l += 1;
-                } else {
-                    *mcl = Some((*class, 1u8));
-                }
-
-                Some(())
-            })
-            .unwrap()
+fn write_level_order(sections: &Sections, out: &mut String) {
+    out.push_str("\n## Level Order\n\n");
+
+    let grid = level_order_grid(&sections.level_order);
+    let columns = grid.first().map_or(0, |row| row.len());
+    let mut cell_widths = vec![0usize; columns];
+    let mut num_widths = vec![0usize; columns];
+    for (col, (w, nw)) in cell_widths
+        .iter_mut()
+        .zip(num_widths.iter_mut())
+        .enumerate()
+    {
+        let cells = grid.iter().filter_map(|row| row[col]);
+        // Class names come from the content registry, so they can't be
+        // tabulated ahead of time
+        let max_class_width = cells
+            .clone()
+            .fold(0, |accu, (_, c)| c.to_string().chars().count().max(accu));
+        let last_level = cells.map(|(l, _)| l).max().unwrap_or(0);
+
+        // Room for the widest level number, plus ") "
+        *nw = last_level.to_string().len() + 2;
+        *w = *nw + max_class_width;
     }
-    split.sort_by_key(|mcl| {
-        if let Some((_, l)) = mcl {
-            0xFF - l
-        } else {
-            0xFF
+
+    for &w in cell_widths.iter() {
+        out.push_str("| ");
+        out.push_str(&" ".repeat(w));
+        out.push(' ');
+    }
+    out.push_str("|\n");
+    for &w in cell_widths.iter() {
+        out.push_str("| :");
+        out.push_str(&"-".repeat(w - 1));
+        out.push(' ');
+    }
+    out.push_str("|\n");
+
+    for row in grid.iter() {
+        for (col, &w) in cell_widths.iter().enumerate() {
+            out.push_str("| ");
+            if let Some((level, class)) = row[col] {
+                let cell = format!(
+                    "{:<nw$}{}",
+                    format!("{})", level),
+                    class,
+                    nw = num_widths[col],
+                );
+                out.push_str(&format!("{:<w$}", cell, w = w));
+            } else {
+                out.push_str(&" ".repeat(w));
+            }
+            out.push(' ');
         }
-    });
-    out.push_str(&match split {
-        [Some((c0, l0)), Some((c1, l1)), Some((c2, l2))] =>
-            format!("{}/{}/{} {}/{}/{}\n\n", l0, l1, l2, c0, c1, c2),
-        [Some((c0, l0)), Some((c1, l1)), _] =>
-            format!("{}/{} {}/{}\n\n", l0, l1, c0, c1),
-        [Some((c, l)), _, _] => format!("{} {}\n\n", c, l),
-        _ => unimplemented!(),
-    });
-
-    out.push_str(&format!("{} {}\n", build.alignment, build.race));
+        out.push_str("|\n");
+    }
 }
 
-fn write_level_order(
-    build: &cbl_build_parser::CharacterBuild,
-    out: &mut String,
-) {
-    out.push_str("\nLevel order\n\n");
-
-    let class_levels = build.max_levels.min(20);
-    let cols = class_levels / 5 + (class_levels % 5).min(1);
-    let mut max_class_widths = [0u8; 4];
-    for (i, class_slice) in build.levels.as_slice().chunks(5).enumerate() {
-        max_class_widths[i] = class_slice.iter().fold(0, |accu, c| {
-            let width = match c {
-                Class::Artificer => 9,
-                Class::Barbarian => 9,
-                Class::Bard => 4,
-                Class::Cleric => 6,
-                Class::Druid => 5,
-                Class::FavoredSoul => 12,
-                Class::Fighter => 7,
-                Class::Monk => 4,
-                Class::Paladin => 7,
-                Class::Ranger => 6,
-                Class::Rogue => 5,
-                Class::Sorcerer => 8,
-                Class::Warlock => 7,
-                Class::Wizard => 6,
-            };
-
-            width.max(accu)
-        });
-    }
+/// One table for each group of rows, with a column for each build.
+fn comparison_to_markdown(comparison: &Comparison) -> String {
+    let mut out = String::with_capacity(4_096);
+
+    out.push_str("# ");
+    out.push_str(&comparison.names.join(" vs. "));
+    out.push('\n');
 
-    out.push_str("|    ");
-    for _ in 0..max_class_widths[0] {
+    for group in comparison.groups.iter() {
+        out.push_str("\n## ");
+        out.push_str(group.title);
+        out.push_str("\n\n");
+
+        let label_width = group
+            .rows
+            .iter()
+            .fold(0, |accu, row| row.label.chars().count().max(accu));
+        let widths: Vec<usize> = comparison
+            .names
+            .iter()
+            .enumerate()
+            .map(|(col, name)| {
+                group.rows.iter().fold(name.chars().count(), |accu, row| {
+                    row.cells[col].chars().count().max(accu)
+                })
+            })
+            .collect();
+
+        write_markdown_row(
+            "",
+            label_width,
+            &comparison.names,
+            &widths,
+            &mut out,
+        );
+        out.push_str("| :");
+        out.push_str(&"-".repeat(label_width.max(1) - 1));
         out.push(' ');
-    }
-    for &w in max_class_widths[1..].iter().filter(|&&w| w != 0) {
-        out.push_str(" |     ");
-        for _ in 0..w {
+        for &w in widths.iter() {
+            out.push_str("| :");
+            out.push_str(&"-".repeat(w.max(1) - 1));
             out.push(' ');
         }
+        out.push_str("|\n");
+        for row in group.rows.iter() {
+            write_markdown_row(
+                &row.label,
+                label_width,
+                &row.cells,
+                &widths,
+                &mut out,
+            );
+        }
     }
-    out.push_str(" |\n");
 
-    // ...
+    out
+}
+
+fn write_markdown_row(
+    label: &str,
+    label_width: usize,
+    cells: &[String],
+    widths: &[usize],
+    out: &mut String,
+) {
+    out.push_str(&format!("| {:<w$} ", label, w = label_width.max(1)));
+    for (cell, &w) in cells.iter().zip(widths.iter()) {
+        // `|` would end the cell early
+        let cell = cell.replace('|', "\\|");
+        out.push_str(&format!("| {:<w$} ", cell, w = w.max(1)));
+    }
+    out.push_str("|\n");
 }
@@ -0,0 +1,380 @@
+//! The sections of a build summary, worked out once so that each output
+//! format only has to lay them out.
+
+use cbl_build_parser::{
+    content::{self, Registry},
+    snapshot::{ability_score, LevelSnapshot},
+    Ability,
+    BuildType,
+    CharacterBuild,
+    Class,
+    Enhancement,
+    SecondaryFeatType,
+    Skill,
+    HEROIC_LEVEL_CAP,
+};
+use std::cmp::Reverse;
+
+/// The number of rows that the level order is laid out in.
+pub const LEVEL_ORDER_ROWS: usize = 5;
+
+pub struct Overview {
+    pub name:      String,
+    /// E.g. "15/4/1 Bard/Fighter/Rogue".
+    pub split:     String,
+    /// E.g. "True Neutral Drow".
+    pub alignment: String,
+}
+
+pub struct StatsTable {
+    /// The enabled build types, with the ability scores of each in the order
+    /// of `Ability::ALL`, including racial modifiers.
+    pub columns:  Vec<(BuildType, Vec<u8>)>,
+    /// The character level and ability of each ability score increase.
+    pub levelups: Vec<(u8, Ability)>,
+}
+
+pub struct SkillRow {
+    pub skill: Skill,
+    /// The ranks gained at each heroic level.
+    pub ranks: Vec<f32>,
+    pub total: f32,
+}
+
+pub struct SkillsTable {
+    /// The class taken at each heroic level.
+    pub classes: Vec<Class>,
+    /// The skills that have any ranks, from the most ranks to the fewest.
+    pub rows:    Vec<SkillRow>,
+    /// The skill points spent at each heroic level.
+    pub spent:   Vec<u8>,
+}
+
+pub struct FeatRow {
+    /// The character level that the feat is taken at.
+    pub level: u8,
+    /// The class that the feat is a bonus feat of, if any.
+    pub class: Option<Class>,
+    pub name:  String,
+}
+
+pub struct SpellRow {
+    pub class:       Class,
+    pub spell_level: usize,
+    pub spells:      Vec<String>,
+}
+
+pub struct TreeSection {
+    pub name:  String,
+    /// The action points spent in the tree.
+    pub ap:    u16,
+    /// The enhancements of each tier that has any, as (tier, names).
+    pub tiers: Vec<(usize, Vec<String>)>,
+}
+
+pub struct Sections {
+    pub overview:     Overview,
+    /// The class of each heroic level.
+    pub level_order:  Vec<Class>,
+    pub stats:        StatsTable,
+    pub skills:       SkillsTable,
+    pub feats:        Vec<FeatRow>,
+    pub spells:       Vec<SpellRow>,
+    /// The trees with any enhancements, from the most action points to the
+    /// fewest.
+    pub enhancements: Vec<TreeSection>,
+}
+
+impl Sections {
+    pub fn new(build: &CharacterBuild) -> Self {
+        Self::new_with(build, &content::registry())
+    }
+
+    pub fn new_with(build: &CharacterBuild, content: &Registry) -> Self {
+        let heroic = build.at_level_with(HEROIC_LEVEL_CAP, content);
+        let top = build.at_level_with(build.max_levels, content);
+
+        Self {
+            overview:     overview(build),
+            level_order:  heroic.levels.to_vec(),
+            stats:        stats(build, content),
+            skills:       skills(build, &heroic, content),
+            feats:        feats(&top),
+            spells:       spells(build),
+            enhancements: enhancements(build, content),
+        }
+    }
+
+    /// The action points spent in every tree.
+    pub fn total_ap(&self) -> u16 {
+        self.enhancements.iter().map(|t| t.ap).sum()
+    }
+}
+
+impl SkillsTable {
+    /// The first letter of the class taken at each heroic level.
+    pub fn initials(&self) -> Vec<char> {
+        self.classes
+            .iter()
+            .map(|c| c.to_string().chars().next().unwrap_or(' '))
+            .collect()
+    }
+}
+
+/// Lays the level order out in `LEVEL_ORDER_ROWS` rows, filling each column
+/// before the next. Each cell is a level and its class.
+pub fn level_order_grid(levels: &[Class]) -> Vec<Vec<Option<(usize, Class)>>> {
+    let columns = levels.len().div_ceil(LEVEL_ORDER_ROWS);
+
+    (0..LEVEL_ORDER_ROWS.min(levels.len()))
+        .map(|row| {
+            (0..columns)
+                .map(|col| {
+                    let i = col * LEVEL_ORDER_ROWS + row;
+                    levels.get(i).map(|&c| (i + 1, c))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Writes a number of ranks with "½" for half ranks, e.g. "1½". Empty for
+/// `0.0`.
+pub fn format_ranks(ranks: f32) -> String {
+    let whole = ranks.trunc() as u32;
+    let half = ranks.fract() >= 0.5;
+
+    match (whole, half) {
+        (0, false) => String::new(),
+        (0, true) => "½".to_owned(),
+        (w, false) => w.to_string(),
+        (w, true) => format!("{}½", w),
+    }
+}
+
+/// The name of a build type's column, e.g. "28pt".
+pub fn build_type_heading(build_type: BuildType) -> String {
+    format!("{}pt", build_type.build_points())
+}
+
+/// The name of a tier's row, e.g. "Cores" or "Tier 1".
+pub fn tier_heading(tier: usize) -> String {
+    match tier {
+        0 => "Cores".to_owned(),
+        t => format!("Tier {}", t),
+    }
+}
+
+fn overview(build: &CharacterBuild) -> Overview {
+    let split = build.class_split();
+
+    let split = match split.as_slice() {
+        [(c, l)] => format!("{} {}", c, l),
+        _ => {
+            let levels: Vec<String> =
+                split.iter().map(|(_, l)| l.to_string()).collect();
+            let classes: Vec<String> =
+                split.iter().map(|(c, _)| c.to_string()).collect();
+
+            format!("{} {}", levels.join("/"), classes.join("/"))
+        },
+    };
+
+    Overview {
+        name: build.name.clone(),
+        split,
+        alignment: format!("{} {}", build.alignment, build.race),
+    }
+}
+
+fn stats(build: &CharacterBuild, content: &Registry) -> StatsTable {
+    let modifiers = content
+        .race(build.race)
+        .map_or(&[][..], |ri| &ri.ability_modifiers);
+
+    let columns = BuildType::ALL
+        .iter()
+        .filter_map(|&bt| {
+            let points = build.stats(bt)?;
+            let scores = Ability::ALL
+                .iter()
+                .map(|&a| {
+                    let modifier: i16 = modifiers
+                        .iter()
+                        .filter(|(m, _)| *m == a)
+                        .map(|(_, m)| i16::from(*m))
+                        .sum();
+                    let score = i16::from(ability_score(points[a])) + modifier;
+
+                    score.clamp(0, i16::from(u8::MAX)) as u8
+                })
+                .collect();
+
+            Some((bt, scores))
+        })
+        .collect();
+    let levelups = build
+        .levelup_levels()
+        .filter_map(|l| Some((l, build.levelup_at(l)?)))
+        .collect();
+
+    StatsTable { columns, levelups }
+}
+
+fn skills(
+    build: &CharacterBuild,
+    heroic: &LevelSnapshot,
+    content: &Registry,
+) -> SkillsTable {
+    let classes = heroic.levels.to_vec();
+
+    let mut rows: Vec<SkillRow> = heroic
+        .skill_ranks
+        .iter()
+        .map(|&(skill, total)| {
+            let ranks = classes
+                .iter()
+                .enumerate()
+                .map(|(i, &class)| {
+                    let points = f32::from(
+                        build.skills.points_in_skill_at_level(skill, i),
+                    );
+                    let class_skill = content
+                        .class(class)
+                        .is_some_and(|ci| ci.class_skills.contains(&skill));
+
+                    if class_skill {
+                        points
+                    } else {
+                        points / 2.0
+                    }
+                })
+                .collect();
+
+            SkillRow {
+                skill,
+                ranks,
+                total,
+            }
+        })
+        .collect();
+    rows.sort_by(|a, b| b.total.total_cmp(&a.total));
+
+    let spent = (0..classes.len())
+        .map(|i| {
+            Skill::ALL
+                .iter()
+                .map(|&s| build.skills.points_in_skill_at_level(s, i))
+                .sum()
+        })
+        .collect();
+
+    SkillsTable {
+        classes,
+        rows,
+        spent,
+    }
+}
+
+fn feats(top: &LevelSnapshot) -> Vec<FeatRow> {
+    top.feats
+        .iter()
+        .map(|&(level, feat)| FeatRow {
+            level,
+            class: match feat.secondary_type {
+                SecondaryFeatType::Class(c) => Some(c),
+                _ => None,
+            },
+            name: feat.name.clone(),
+        })
+        .collect()
+}
+
+fn spells(build: &CharacterBuild) -> Vec<SpellRow> {
+    build
+        .spells
+        .spellbooks
+        .iter()
+        .flatten()
+        .flat_map(|sb| {
+            sb.spells_by_level
+                .iter()
+                .enumerate()
+                .filter_map(move |(i, ss)| {
+                    let spells: Vec<String> =
+                        ss.iter().flatten().cloned().collect();
+                    if spells.is_empty() {
+                        return None;
+                    }
+
+                    Some(SpellRow {
+                        class: sb.class,
+                        spell_level: i + 1,
+                        spells,
+                    })
+                })
+        })
+        .collect()
+}
+
+fn enhancements(
+    build: &CharacterBuild,
+    content: &Registry,
+) -> Vec<TreeSection> {
+    let mut trees: Vec<TreeSection> = build
+        .enhancements
+        .trees
+        .iter()
+        .filter(|et| et.tiers.iter().any(|t| !t.is_empty()))
+        .map(|et| TreeSection {
+            name:  et.name.to_string(),
+            ap:    et.action_points_with(content),
+            tiers: et
+                .tiers
+                .iter()
+                .enumerate()
+                .filter(|(_, es)| !es.is_empty())
+                .map(|(tier, es)| {
+                    (tier, es.iter().map(enhancement_name).collect())
+                })
+                .collect(),
+        })
+        .collect();
+    trees.sort_by_key(|t| Reverse(t.ap));
+
+    trees
+}
+
+/// The enhancement's name, with its sub-enhancement and, if it was given
+/// one, its rank in Roman numerals, e.g. "Skaldic: Constitution" or
+/// "Poetic Edda III".
+pub fn enhancement_name(enhancement: &Enhancement) -> String {
+    let mut name = enhancement.name.clone();
+    if let Some(sub) = &enhancement.subenhancement {
+        name.push_str(": ");
+        name.push_str(sub);
+    }
+    if let Some(rank) = enhancement.rank {
+        name.push(' ');
+        name.push_str(&roman(rank.get()));
+    }
+
+    name
+}
+
+fn roman(n: u8) -> String {
+    const NUMERALS: [(u8, &str); 4] =
+        [(10, "X"), (9, "IX"), (5, "V"), (4, "IV")];
+
+    let mut n = n;
+    let mut out = String::new();
+    for &(value, numeral) in NUMERALS.iter() {
+        while n >= value {
+            out.push_str(numeral);
+            n -= value;
+        }
+    }
+    out.push_str(&"I".repeat(n.into()));
+
+    out
+}
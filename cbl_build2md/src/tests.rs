@@ -0,0 +1,438 @@
+use super::*;
+use cbl_build_parser::{Ability, BuildType, Class};
+use sections::{format_ranks, LEVEL_ORDER_ROWS};
+
+const WARTRAPPER: &str =
+    "brd15_ftr4_rog1_-_drw_-_trapper_-_WC_StD_-_THFs_none_BPlat_-_Wartrapper.\
+     build";
+
+fn fixture(filename: &str) -> CharacterBuild {
+    read_build(&format!("../cbl_build_parser/test/{}", filename))
+}
+
+/// The text between each `<tag>` and the `</tag>` that follows it.
+fn elements<'a>(html: &'a str, tag: &str) -> Vec<&'a str> {
+    let (open, close) = (format!("<{}>", tag), format!("</{}>", tag));
+
+    html.split(open.as_str())
+        .skip(1)
+        .map(|s| &s[..s.find(close.as_str()).unwrap()])
+        .collect()
+}
+
+#[test]
+fn sections_test() {
+    let build = fixture(WARTRAPPER);
+    let sections = Sections::new(&build);
+
+    assert_eq!(sections.overview.name, "Wartrapper");
+    assert_eq!(sections.overview.split, "15/4/1 Bard/Fighter/Rogue");
+    assert_eq!(sections.overview.alignment, "True Neutral Drow");
+    assert_eq!(sections.level_order, build.levels);
+
+    let grid = level_order_grid(&sections.level_order);
+    assert_eq!(grid.len(), LEVEL_ORDER_ROWS);
+    assert!(grid.iter().all(|row| row.len() == 4));
+    assert_eq!(grid[0][1], Some((6, Class::Bard)));
+    assert_eq!(grid[4][3], Some((20, Class::Bard)));
+    let short = level_order_grid(&[Class::Fighter; 7]);
+    assert_eq!(
+        short[1],
+        vec![Some((2, Class::Fighter)), Some((7, Class::Fighter))]
+    );
+    assert_eq!(short[2], vec![Some((3, Class::Fighter)), None]);
+    assert!(level_order_grid(&[]).is_empty());
+
+    assert_eq!(sections.stats.columns.len(), 1);
+    assert_eq!(sections.stats.columns[0].0, BuildType::Adventurer);
+    assert_eq!(sections.stats.columns[0].1, vec![16, 10, 12, 16, 8, 16]);
+    assert_eq!(sections.stats.levelups.len(), 5);
+    assert_eq!(sections.stats.levelups[4], (20, Ability::Str));
+
+    let skills = &sections.skills;
+    assert_eq!(skills.initials()[..3], ['R', 'B', 'F']);
+    assert_eq!(skills.spent[..3], [44, 9, 5]);
+    assert_eq!(skills.rows.len(), 11);
+    assert!(skills.rows.windows(2).all(|w| w[0].total >= w[1].total));
+    assert_eq!(skills.rows[3].ranks[3], 1.5);
+    assert_eq!(format_ranks(0.0), "");
+    assert_eq!(format_ranks(0.5), "½");
+    assert_eq!(format_ranks(1.5), "1½");
+    assert_eq!(format_ranks(23.0), "23");
+
+    let feats: Vec<(u8, Option<Class>, &str)> = sections
+        .feats
+        .iter()
+        .map(|f| (f.level, f.class, f.name.as_str()))
+        .take(3)
+        .collect();
+    assert_eq!(
+        feats,
+        vec![
+            (1, None, "Power Attack"),
+            (3, None, "Force of Personality"),
+            (3, Some(Class::Fighter), "Cleave"),
+        ],
+    );
+
+    assert_eq!(sections.spells.len(), 5);
+    assert_eq!(sections.spells[4].spell_level, 5);
+    assert_eq!(sections.spells[4].spells.len(), 3);
+
+    let trees: Vec<(&str, u16)> = sections
+        .enhancements
+        .iter()
+        .map(|t| (t.name.as_str(), t.ap))
+        .collect();
+    assert_eq!(
+        trees,
+        vec![
+            ("Warchanter", 41),
+            ("Stalwart Defender", 26),
+            ("Spellsinger", 6),
+            ("Swashbuckler", 4),
+        ],
+    );
+    assert_eq!(sections.total_ap(), 77);
+    assert_eq!(sections.enhancements[0].tiers.len(), 6);
+    assert_eq!(sections.enhancements[0].tiers[1].1[0], "Poetic Edda III");
+
+    // A pure Fighter with no skill points, spells or enhancements
+    let sections = Sections::new(&fixture("test1.build"));
+    assert_eq!(sections.overview.split, "Fighter 20");
+    assert!(sections.skills.rows.is_empty());
+    assert!(sections.spells.is_empty());
+    assert!(sections.enhancements.is_empty());
+    assert_eq!(sections.stats.levelups.len(), 7);
+}
+
+#[test]
+fn html_test() {
+    let html = html::build_to_html(&fixture(WARTRAPPER));
+
+    assert!(html.starts_with("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n"));
+    assert!(html.ends_with("</body>\n</html>\n"));
+    assert_eq!(elements(&html, "title"), vec!["Wartrapper"]);
+    assert_eq!(elements(&html, "h1"), vec!["Wartrapper"]);
+    assert_eq!(
+        elements(&html, "h2"),
+        vec![
+            "Level Order",
+            "Stats",
+            "Skills",
+            "Feats",
+            "Spells",
+            "Enhancements (77 AP)",
+        ],
+    );
+    assert_eq!(elements(&html, "h3")[1], "Stalwart Defender (26 AP)");
+    for tag in ["section", "table", "tr", "header"].iter() {
+        assert_eq!(
+            html.matches(&format!("<{}", tag)).count(),
+            html.matches(&format!("</{}>", tag)).count(),
+            "unbalanced <{}>",
+            tag,
+        );
+    }
+    assert_eq!(html.matches("<section>").count(), 6);
+    // Each section's tables are closed before the next section starts
+    for section in elements(&html, "section") {
+        assert!(!section.contains("<section>"));
+        assert_eq!(
+            section.matches("<table").count(),
+            section.matches("</table>").count(),
+        );
+    }
+
+    let level_order = elements(&html, "section")[0];
+    assert_eq!(elements(level_order, "tr").len(), LEVEL_ORDER_ROWS);
+    assert!(level_order.contains(
+        "<tr><td>1) Rogue</td><td>6) Bard</td><td>11) Fighter</td><td>16) \
+         Bard</td></tr>"
+    ));
+    assert!(html.contains(
+        "<tr><th>Strength</th><td class=\"num\">16</td><td class=\"num\">4: \
+         STR</td></tr>"
+    ));
+    assert!(html.contains("<td class=\"num\" title=\"Rogue\">R</td>"));
+    assert!(html.contains("<tr><th>3 Fighter</th><td>Cleave</td></tr>"));
+    assert!(html.contains(
+        "<tr><th>Bard 5</th><td>Greater Heroism, Mass Cure Light Wounds, \
+         Shadow Walk</td></tr>"
+    ));
+    assert!(html.contains(
+        "<tr><th>Tier 4</th><td>Reckless Chant III, Armorer</td></tr>"
+    ));
+
+    // Sections without anything in them are left out, and the apostrophe
+    // of Knight's Training is escaped
+    let html = html::build_to_html(&fixture("test1.build"));
+    assert_eq!(
+        elements(&html, "h2"),
+        vec!["Level Order", "Stats", "Skills", "Feats"],
+    );
+    assert!(html.contains("<td>Knight&#39;s Training</td>"));
+    assert!(!html.contains("Knight's"));
+
+    let mut build = fixture("test1.build");
+    build.name = "<Sword & \"Board\">".to_owned();
+    let html = html::build_to_html(&build);
+    let escaped = "&lt;Sword &amp; &quot;Board&quot;&gt;";
+    assert_eq!(elements(&html, "title"), vec![escaped]);
+    assert_eq!(elements(&html, "h1"), vec![escaped]);
+    assert!(!html.contains("<Sword"));
+}
+
+/// The tags of a BBCode document, checking that each closing tag matches
+/// the last one opened. `[*]` has no closing tag, and nothing is a tag in
+/// `[code]` or `[plain]`.
+fn bbcode_tags(bbcode: &str) -> Vec<String> {
+    let mut tags = Vec::new();
+    let mut open: Vec<String> = Vec::new();
+    let mut rest = bbcode;
+    while let Some(start) = rest.find('[') {
+        let end = start + rest[start..].find(']').unwrap();
+        let tag = &rest[start + 1..end];
+        rest = &rest[end + 1..];
+
+        if let Some(name) = tag.strip_prefix('/') {
+            assert_eq!(open.pop().as_deref(), Some(name), "in {}", bbcode);
+        } else if tag != "*" {
+            let name = tag.split('=').next().unwrap();
+            if name == "code" || name == "plain" {
+                let close = format!("[/{}]", name);
+                rest =
+                    &rest[rest.find(close.as_str()).unwrap() + close.len()..];
+                tags.push(name.to_owned());
+                continue;
+            }
+            open.push(name.to_owned());
+        }
+        tags.push(tag.to_owned());
+    }
+    assert!(open.is_empty(), "unclosed tags: {:?}", open);
+
+    tags
+}
+
+#[test]
+fn bbcode_test() {
+    let bbcode = bbcode::build_to_bbcode(&fixture(WARTRAPPER));
+
+    assert!(bbcode.starts_with(
+        "[size=5][b]Wartrapper[/b][/size]\n15/4/1 Bard/Fighter/Rogue\nTrue \
+         Neutral Drow\n"
+    ));
+    let tags = bbcode_tags(&bbcode);
+    let opened = |name: &str| tags.iter().filter(|t| *t == name).count();
+    assert_eq!(opened("table"), 1);
+    assert_eq!(opened("tr"), LEVEL_ORDER_ROWS);
+    assert_eq!(opened("td"), 20);
+    assert_eq!(opened("code"), 2);
+    assert_eq!(opened("list"), 2);
+    assert_eq!(opened("*"), 10 + 5);
+    let spoilers: Vec<&String> =
+        tags.iter().filter(|t| t.starts_with("spoiler=")).collect();
+    assert_eq!(
+        spoilers,
+        vec![
+            "spoiler=\"Warchanter (41 AP)\"",
+            "spoiler=\"Stalwart Defender (26 AP)\"",
+            "spoiler=\"Spellsinger (6 AP)\"",
+            "spoiler=\"Swashbuckler (4 AP)\"",
+        ],
+    );
+    assert!(bbcode.contains(
+        "[tr][td]1) Rogue[/td][td]6) Bard[/td][td]11) Fighter[/td][td]16) \
+         Bard[/td][/tr]\n"
+    ));
+    assert!(bbcode.contains("[*]3 Fighter: Cleave\n"));
+    assert!(bbcode.contains("Charisma        16\n[/code]"));
+
+    // Brackets in names can't open or close tags, or end a spoiler's title
+    let mut build = fixture(WARTRAPPER);
+    build.name = "[b]Bold[/b] Trapper]".to_owned();
+    build.feats.standard_feats[0].name = "Power Attack [Toggle]".to_owned();
+    build.spells.spellbooks[0].as_mut().unwrap().spells_by_level[0][0] =
+        Some("[[Cure Light Wounds]]".to_owned());
+    let bbcode = bbcode::build_to_bbcode(&build);
+    assert!(bbcode.starts_with(
+        "[size=5][b][plain][[/plain]b]Bold[plain][[/plain]/b] \
+         Trapper][/b][/size]\n"
+    ));
+    assert!(bbcode.contains("[*]1: Power Attack [plain][[/plain]Toggle]\n"));
+    assert!(bbcode.contains(
+        "[*]Bard 1: [plain][[/plain][plain][[/plain]Cure Light Wounds]], \
+         Focusing Chant"
+    ));
+    assert_eq!(bbcode_tags(&bbcode).len(), tags.len() + 5);
+}
+
+/// The trimmed cells of a Markdown table row.
+fn cells(row: &str) -> Vec<&str> {
+    let row = row.strip_prefix('|').unwrap().strip_suffix('|').unwrap();
+
+    row.split('|').map(str::trim).collect()
+}
+
+#[test]
+fn compare_test() {
+    // 20, 30 and 20 levels, with different races and trees, and none for
+    // the pure Fighter
+    let builds = vec![
+        fixture(WARTRAPPER),
+        fixture("test1.build"),
+        fixture("test10.build"),
+    ];
+    let comparison = Comparison::new(&builds);
+    assert_eq!(
+        comparison.names,
+        vec!["Wartrapper", "New Build", "Old Habits"],
+    );
+    let titles: Vec<&str> =
+        comparison.groups.iter().map(|g| g.title).collect();
+    assert_eq!(
+        titles,
+        vec![
+            "Overview",
+            "Ability Scores",
+            "Combat",
+            "Feats",
+            "Enhancements",
+        ],
+    );
+    assert!(comparison
+        .groups
+        .iter()
+        .flat_map(|g| g.rows.iter())
+        .all(|row| row.cells.len() == builds.len()));
+
+    let markdown = comparison_to_markdown(&comparison);
+    assert!(
+        markdown.starts_with("# Wartrapper vs. New Build vs. Old Habits\n")
+    );
+    assert_eq!(markdown.matches("\n## ").count(), 5);
+    let lines: Vec<&str> = markdown.lines().collect();
+    let table = |title: &str| {
+        let start = lines
+            .iter()
+            .position(|l| *l == format!("## {}", title))
+            .unwrap();
+        lines[start + 2..]
+            .iter()
+            .take_while(|l| l.starts_with('|'))
+            .cloned()
+            .collect::<Vec<&str>>()
+    };
+    let overview = table("Overview");
+    assert_eq!(
+        cells(overview[0]),
+        vec!["", "Wartrapper", "New Build", "Old Habits"],
+    );
+    assert_eq!(
+        cells(overview[4]),
+        vec![
+            "Classes",
+            "15 Bard/4 Fighter/1 Rogue",
+            "20 Fighter",
+            "16 Favored Soul/4 Fighter",
+        ],
+    );
+    assert_eq!(cells(overview[5]), vec!["Levels", "20", "30", "20"]);
+    // Every table's rows line up with its header
+    for title in titles.iter() {
+        let rows = table(title);
+        assert!(rows.len() > 2);
+        assert!(rows
+            .iter()
+            .all(|r| r.chars().count() == rows[0].chars().count()));
+    }
+    let combat = table("Combat");
+    assert_eq!(cells(combat[2]), vec!["Base Attack", "15", "20", "16"]);
+    assert_eq!(cells(combat[6]), vec!["Hit Points", "156", "300", "208"]);
+
+    // Feat slots of every build, in level order, with the epic slots that
+    // only the 30 level build has
+    let feats = table("Feats");
+    let labels: Vec<&str> = feats[2..].iter().map(|r| cells(r)[0]).collect();
+    assert_eq!(
+        labels[..5],
+        ["1", "1 Fighter", "1 Aasimar", "1 Halfling", "2 Fighter"]
+    );
+    assert_eq!(labels.last(), Some(&"30 Legend"));
+    assert!(labels.contains(&"5 Favored Soul"));
+    assert!(feats
+        .iter()
+        .any(|r| cells(r) == vec!["21 Epic", "", "Arcane Insight", ""]));
+
+    // Trees by their total points across the builds, left empty for the
+    // builds that don't spend any in them
+    let enhancements = table("Enhancements");
+    let trees: Vec<Vec<&str>> =
+        enhancements[2..].iter().map(|r| cells(r)).collect();
+    assert_eq!(
+        trees,
+        vec![
+            vec!["Tier 5", "Warchanter", "", "Angel of Vengeance"],
+            vec!["Total AP", "77", "", "7"],
+            vec!["Warchanter", "41", "", ""],
+            vec!["Stalwart Defender", "26", "", "4"],
+            vec!["Spellsinger", "6", "", ""],
+            vec!["Swashbuckler", "4", "", ""],
+            vec!["Halfling", "", "", "1"],
+            vec!["Vanguard", "", "", "1"],
+            vec!["Angel of Vengeance", "", "", "1"],
+        ],
+    );
+
+    let html = html::comparison_to_html(&comparison);
+    assert!(html.starts_with("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n"));
+    assert!(html.ends_with("</body>\n</html>\n"));
+    assert_eq!(
+        elements(&html, "title"),
+        vec!["Wartrapper vs. New Build vs. Old Habits"],
+    );
+    assert_eq!(elements(&html, "h2"), titles);
+    for tag in ["section", "table", "tr"].iter() {
+        assert_eq!(
+            html.matches(&format!("<{}", tag)).count(),
+            html.matches(&format!("</{}>", tag)).count(),
+            "unbalanced <{}>",
+            tag,
+        );
+    }
+    let sections = elements(&html, "section");
+    for (section, group) in sections.iter().zip(comparison.groups.iter()) {
+        let rows = elements(section, "tr");
+        assert_eq!(rows.len(), group.rows.len() + 1);
+        assert_eq!(
+            rows[0],
+            "<th></th><th>Wartrapper</th><th>New Build</th><th>Old \
+             Habits</th>",
+        );
+        assert!(rows[1..].iter().all(|r| r.matches("<td>").count() == 3));
+    }
+    assert!(sections[0].contains(
+        "<tr><th>Levels</th><td>20</td><td>30</td><td>20</td></tr>"
+    ));
+    assert!(sections[3].contains(
+        "<tr><th>4 Fighter</th><td></td><td>Knight&#39;s \
+         Training</td><td>Great Cleave</td></tr>"
+    ));
+    assert!(sections[4].contains(
+        "<tr><th>Stalwart Defender</th><td>26</td><td></td><td>4</td></tr>"
+    ));
+
+    // Two builds make two columns
+    let comparison = Comparison::new(&builds[1..]);
+    let markdown = comparison_to_markdown(&comparison);
+    assert!(markdown.starts_with("# New Build vs. Old Habits\n"));
+    assert!(
+        markdown
+            .lines()
+            .any(|l| l.starts_with('|')
+                && cells(l) == vec!["Levels", "30", "20"])
+    );
+    let html = html::comparison_to_html(&comparison);
+    assert!(html.contains("<tr><th>Total AP</th><td></td><td>7</td></tr>"));
+}
@@ -0,0 +1,163 @@
+//! Filling in a build's skill points from a list of skill priorities.
+
+use crate::{
+    content::{self, Registry},
+    Ability,
+    CharacterBuild,
+    Class,
+    Skill,
+    Skills,
+    HEROIC_LEVEL_CAP,
+};
+use fxhash::FxHashMap as HashMap;
+
+/// How the points of a skill are spent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SkillPolicy {
+    /// As many ranks as allowed at every level.
+    Max,
+    /// As many ranks as allowed at level 1, and none after, e.g. for the
+    /// ranks in Balance, Jump or Tumble that unlock their first benefits.
+    FirstLevel,
+    /// Ranks up to the given number, as early as allowed.
+    UpTo(u8),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SkillPriority {
+    pub skill:  Skill,
+    pub policy: SkillPolicy,
+}
+
+impl CharacterBuild {
+    /// Returns the skill points gained at a heroic level, using the game
+    /// content of `content::registry()`.
+    pub fn skill_points_at(&self, level: u8) -> u8 {
+        self.skill_points_at_with(level, &content::registry())
+    }
+
+    /// Returns the skill points gained at a heroic level: those of the
+    /// level's class and race plus the Intelligence modifier at that level,
+    /// but at least 1, and quadrupled at level 1. `0` past the heroic levels.
+    pub fn skill_points_at_with(&self, level: u8, content: &Registry) -> u8 {
+        let class = match level
+            .checked_sub(1)
+            .and_then(|i| self.levels.get(usize::from(i)))
+        {
+            Some(&class) if level <= HEROIC_LEVEL_CAP => class,
+            _ => return 0,
+        };
+
+        let class_points =
+            content.class(class).map_or(0, |ci| ci.skill_points);
+        let race_points =
+            content.race(self.race).map_or(0, |ri| ri.skill_points);
+        let intelligence = self
+            .at_level_with(level, content)
+            .ability_scores
+            .map_or(10, |scores| scores[Ability::Int]);
+        let modifier = (i16::from(intelligence) - 10).div_euclid(2);
+
+        let points =
+            (i16::from(class_points) + i16::from(race_points) + modifier)
+                .max(1);
+        let points = if level == 1 { points * 4 } else { points };
+
+        points.min(i16::from(u8::MAX)) as u8
+    }
+}
+
+/// Spends the skill points of each of the build's heroic levels on the
+/// skills of `priorities`, in order, using the game content of
+/// `content::registry()`.
+pub fn allocate_skills(
+    build: &CharacterBuild,
+    priorities: &[SkillPriority],
+) -> Skills {
+    allocate_skills_with(build, priorities, &content::registry())
+}
+
+/// Spends the skill points of each of the build's heroic levels on the
+/// skills of `priorities`, in order, according to `content`.
+///
+/// Points spent in a skill that isn't a class skill of the level's class
+/// are worth half a rank. No skill gets more ranks than the character level
+/// plus 3, or half that if it isn't a class skill of any class taken so far.
+/// Points that no priority wants are left unspent, and the build's skill
+/// tomes are kept.
+pub fn allocate_skills_with(
+    build: &CharacterBuild,
+    priorities: &[SkillPriority],
+    content: &Registry,
+) -> Skills {
+    let mut skill_table: HashMap<Skill, [u8; HEROIC_LEVEL_CAP as usize]> =
+        HashMap::default();
+    // The ranks of each skill so far, in half ranks.
+    let mut half_ranks: HashMap<Skill, u16> = HashMap::default();
+
+    let heroic_levels = build.levels.len().min(HEROIC_LEVEL_CAP.into());
+    for (i, &class) in build.levels[..heroic_levels].iter().enumerate() {
+        let level = i as u8 + 1;
+        let class_skills =
+            |c| content.class(c).map_or(&[][..], |ci| &ci.class_skills[..]);
+        let mut remaining = build.skill_points_at_with(level, content);
+
+        for priority in priorities.iter() {
+            let skill = priority.skill;
+            let cap = max_half_ranks(&build.levels[..=i], skill, content);
+            let max_half_ranks = match priority.policy {
+                SkillPolicy::Max => cap,
+                SkillPolicy::FirstLevel if level == 1 => cap,
+                SkillPolicy::FirstLevel => continue,
+                SkillPolicy::UpTo(ranks) => cap.min(2 * u16::from(ranks)),
+            };
+            let half_ranks_per_point = if class_skills(class).contains(&skill)
+            {
+                2
+            } else {
+                1
+            };
+
+            let ranks = half_ranks.entry(skill).or_insert(0);
+            let wanted =
+                max_half_ranks.saturating_sub(*ranks) / half_ranks_per_point;
+            let points = wanted.min(u16::from(remaining)) as u8;
+            if points == 0 {
+                continue;
+            }
+
+            *ranks += u16::from(points) * half_ranks_per_point;
+            remaining -= points;
+            skill_table
+                .entry(skill)
+                .or_insert([0; HEROIC_LEVEL_CAP as usize])[i] += points;
+        }
+    }
+
+    Skills {
+        skill_table,
+        skill_tomes: build.skills.skill_tomes.clone(),
+    }
+}
+
+/// The most ranks, in half ranks, that `skill` can have at the character
+/// level of the last of `levels`: the level plus 3, or half that if the skill
+/// isn't a class skill of any of the classes in `levels`.
+pub(crate) fn max_half_ranks(
+    levels: &[Class],
+    skill: Skill,
+    content: &Registry,
+) -> u16 {
+    let level = levels.len() as u16;
+    let class_skill = levels.iter().any(|&c| {
+        content
+            .class(c)
+            .is_some_and(|ci| ci.class_skills.contains(&skill))
+    });
+
+    if class_skill {
+        2 * (level + 3)
+    } else {
+        level + 3
+    }
+}
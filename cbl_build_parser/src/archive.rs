@@ -0,0 +1,391 @@
+//! An index of a folder of builds that can be searched, e.g. for every Drow
+//! build with Warchanter as its tier 5 tree and Power Attack.
+//!
+//! Queries are made of conditions joined by `and`, each of which is written
+//! as one of:
+//!
+//! - `Drow`: the build's race.
+//! - `Rogue` or `4 Rogue`: at least one (or four) levels of a class.
+//! - `Warchanter tier 5`: the build's tier 5 tree.
+//! - `Stalwart Defender` or `Stalwart Defender 20 AP`: at least one (or 20)
+//!   action points spent in a tree.
+//! - `feat Power Attack`: a feat taken at any level.
+//! - `spell Haste`: a spell learned by any class.
+//!
+//! Names that could refer to a race or a class are read as such, rather than
+//! as the name of a tree.
+
+use crate::{
+    content::{self, Registry},
+    filename::EXTENSION,
+    order::format_split,
+    parse_with,
+    CharacterBuild,
+    Class,
+    EnhancementTreeName,
+    ParseError,
+    Race,
+};
+use regex::Regex;
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// What the index knows of a build.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArchiveEntry {
+    pub path:      PathBuf,
+    pub name:      String,
+    pub race:      Race,
+    /// The number of levels in each class, most levels first.
+    pub split:     Vec<(Class, u8)>,
+    pub tier_five: Option<EnhancementTreeName>,
+    /// The action points spent in each tree that has any, in the order that
+    /// the trees were declared.
+    pub trees:     Vec<(EnhancementTreeName, u16)>,
+    /// The names of the feats taken at any level.
+    pub feats:     Vec<String>,
+    /// The spells learned, as (class, spell level, name).
+    pub spells:    Vec<(Class, usize, String)>,
+}
+
+/// The builds found in a folder and its subfolders.
+#[derive(Debug, Default)]
+pub struct Archive {
+    /// Sorted by path.
+    pub entries: Vec<ArchiveEntry>,
+    /// The files that couldn't be parsed as builds, sorted by path.
+    pub errors:  Vec<(PathBuf, ParseError)>,
+}
+
+/// Something that a build must have to match a query.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Condition {
+    Race(Race),
+    ClassLevels {
+        class:    Class,
+        at_least: u8,
+    },
+    TierFive(EnhancementTreeName),
+    ActionPoints {
+        tree:     EnhancementTreeName,
+        at_least: u16,
+    },
+    /// Matched ignoring case.
+    Feat(String),
+    /// Matched ignoring case.
+    Spell(String),
+}
+
+/// Conditions that a build must all meet. A query without conditions matches
+/// every build.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Query {
+    pub conditions: Vec<Condition>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QueryError {
+    InvalidCondition(String),
+    /// Not the name of a race, class or enhancement tree.
+    UnknownName(String),
+}
+
+impl ArchiveEntry {
+    /// Indexes `build`, which was read from `path`, according to `content`.
+    pub fn new_with(
+        path: PathBuf,
+        build: &CharacterBuild,
+        content: &Registry,
+    ) -> Self {
+        let feats = &build.feats;
+        let feats = feats
+            .standard_feats
+            .iter()
+            .chain(feats.legend_feats.iter())
+            .chain(feats.class_feats.iter())
+            .chain(feats.race_feats.iter())
+            .map(|f| f.name.clone())
+            .collect();
+        let spells = build
+            .spells
+            .spellbooks
+            .iter()
+            .flatten()
+            .flat_map(|sb| {
+                sb.spells_by_level.iter().enumerate().flat_map(
+                    move |(i, names)| {
+                        names
+                            .iter()
+                            .flatten()
+                            .map(move |n| (sb.class, i + 1, n.clone()))
+                    },
+                )
+            })
+            .collect();
+        let trees = build
+            .enhancements
+            .trees
+            .iter()
+            .map(|et| (et.name, et.action_points_with(content)))
+            .filter(|&(_, ap)| ap > 0)
+            .collect();
+
+        Self {
+            path,
+            name: build.name.clone(),
+            race: build.race,
+            split: build.class_split(),
+            tier_five: build.tier_five,
+            trees,
+            feats,
+            spells,
+        }
+    }
+
+    /// Whether the build meets the condition.
+    pub fn matches(&self, condition: &Condition) -> bool {
+        match condition {
+            Condition::Race(race) => self.race == *race,
+            Condition::ClassLevels { class, at_least } =>
+                self.split.iter().any(|(c, n)| c == class && n >= at_least),
+            Condition::TierFive(tree) => self.tier_five == Some(*tree),
+            Condition::ActionPoints { tree, at_least } =>
+                self.trees.iter().any(|(t, ap)| t == tree && ap >= at_least),
+            Condition::Feat(name) =>
+                self.feats.iter().any(|f| f.eq_ignore_ascii_case(name)),
+            Condition::Spell(name) => self
+                .spells
+                .iter()
+                .any(|(_, _, s)| s.eq_ignore_ascii_case(name)),
+        }
+    }
+
+    /// A one-line summary of the build, e.g. "Wartrapper: Drow, 15 Bard/4
+    /// Fighter/1 Rogue, Warchanter tier 5".
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "{}: {}, {}",
+            self.name,
+            self.race,
+            format_split(&self.split),
+        );
+        if let Some(tree) = self.tier_five {
+            summary.push_str(&format!(", {} tier 5", tree));
+        }
+
+        summary
+    }
+}
+
+impl Archive {
+    /// Indexes every build under `root`, using the game content of
+    /// `content::registry()`.
+    pub fn index<P: AsRef<Path>>(root: P) -> io::Result<Self> {
+        Self::index_with(root, &content::registry())
+    }
+
+    /// Indexes every build under `root` (see `build_paths()`), according to
+    /// `content`. Builds that can't be parsed are listed in `errors`; only
+    /// failing to read a folder is an error.
+    pub fn index_with<P: AsRef<Path>>(
+        root: P,
+        content: &Registry,
+    ) -> io::Result<Self> {
+        let mut archive = Self::default();
+        for path in build_paths(root)? {
+            let parsed = File::open(&path)
+                .map_err(ParseError::from)
+                .and_then(|f| parse_with(&mut BufReader::new(f), content));
+            match parsed {
+                Ok(build) => archive
+                    .entries
+                    .push(ArchiveEntry::new_with(path, &build, content)),
+                Err(e) => archive.errors.push((path, e)),
+            }
+        }
+
+        Ok(archive)
+    }
+
+    /// The builds that meet every condition of `query`, sorted by path.
+    pub fn query<'a>(
+        &'a self,
+        query: &'a Query,
+    ) -> impl Iterator<Item = &'a ArchiveEntry> + 'a {
+        self.entries
+            .iter()
+            .filter(move |e| query.conditions.iter().all(|c| e.matches(c)))
+    }
+}
+
+impl Condition {
+    /// Reads a condition, with the names of `content`; see the module
+    /// documentation.
+    pub fn parse_with(
+        s: &str,
+        content: &Registry,
+    ) -> Result<Self, QueryError> {
+        lazy_static! {
+            static ref FEAT_RE: Regex = Regex::new(r"^feat\s+(.+)$").unwrap();
+            static ref SPELL_RE: Regex =
+                Regex::new(r"^spell\s+(.+)$").unwrap();
+            static ref TIER_FIVE_RE: Regex =
+                Regex::new(r"^(.+?)\s+tier\s+5$").unwrap();
+            static ref ACTION_POINTS_RE: Regex =
+                Regex::new(r"^(.+?)\s+(\d+)\s+AP$").unwrap();
+            static ref CLASS_LEVELS_RE: Regex =
+                Regex::new(r"^(\d+)\s+(.+)$").unwrap();
+        }
+
+        let invalid = || QueryError::InvalidCondition(s.to_owned());
+        let tree = |name: &str| {
+            content
+                .tree_by_name(name)
+                .ok_or_else(|| QueryError::UnknownName(name.to_owned()))
+        };
+        let s = s.trim();
+
+        if let Some(caps) = FEAT_RE.captures(s) {
+            Ok(Self::Feat(caps[1].to_owned()))
+        } else if let Some(caps) = SPELL_RE.captures(s) {
+            Ok(Self::Spell(caps[1].to_owned()))
+        } else if let Some(caps) = TIER_FIVE_RE.captures(s) {
+            Ok(Self::TierFive(tree(&caps[1])?))
+        } else if let Some(caps) = ACTION_POINTS_RE.captures(s) {
+            Ok(Self::ActionPoints {
+                tree:     tree(&caps[1])?,
+                at_least: caps[2].parse().map_err(|_| invalid())?,
+            })
+        } else if let Some(caps) = CLASS_LEVELS_RE.captures(s) {
+            Ok(Self::ClassLevels {
+                class:    content.class_by_name(&caps[2]).ok_or_else(
+                    || QueryError::UnknownName(caps[2].to_owned()),
+                )?,
+                at_least: caps[1].parse().map_err(|_| invalid())?,
+            })
+        } else if s.is_empty() {
+            Err(invalid())
+        } else if let Some(race) = content.race_by_name(s) {
+            Ok(Self::Race(race))
+        } else if let Some(class) = content.class_by_name(s) {
+            Ok(Self::ClassLevels { class, at_least: 1 })
+        } else {
+            Ok(Self::ActionPoints {
+                tree:     tree(s)?,
+                at_least: 1,
+            })
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_with(s, &content::registry())
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Self::Race(race) => write!(f, "{}", race),
+            Self::ClassLevels { class, at_least } =>
+                write!(f, "{} {}", at_least, class),
+            Self::TierFive(tree) => write!(f, "{} tier 5", tree),
+            Self::ActionPoints { tree, at_least } =>
+                write!(f, "{} {} AP", tree, at_least),
+            Self::Feat(name) => write!(f, "feat {}", name),
+            Self::Spell(name) => write!(f, "spell {}", name),
+        }
+    }
+}
+
+impl Query {
+    /// Reads conditions joined by `and`, with the names of `content`.
+    pub fn parse_with(
+        s: &str,
+        content: &Registry,
+    ) -> Result<Self, QueryError> {
+        lazy_static! {
+            static ref AND_RE: Regex = Regex::new(r"\s+and\s+").unwrap();
+        }
+
+        let s = s.trim();
+        if s.is_empty() {
+            return Ok(Self::default());
+        }
+        let conditions = AND_RE
+            .split(s)
+            .map(|c| Condition::parse_with(c, content))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { conditions })
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_with(s, &content::registry())
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        for (i, condition) in self.conditions.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" and ")?;
+            }
+            write!(f, "{}", condition)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Self::InvalidCondition(c) => write!(f, "Invalid condition: {}", c),
+            Self::UnknownName(n) =>
+                write!(f, "Not a race, class or enhancement tree: {}", n),
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// Lists every build (i.e. every file with the `.build` extension) under
+/// `root`, sorted by path.
+pub fn build_paths<P: AsRef<Path>>(root: P) -> io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    find_builds(root.as_ref(), &mut paths)?;
+    paths.sort();
+
+    Ok(paths)
+}
+
+/// Adds the path of every build under `dir` to `paths`.
+fn find_builds(dir: &Path, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_builds(&path, paths)?;
+        } else if path
+            .file_name()
+            .and_then(|f| f.to_str())
+            .is_some_and(|f| f.ends_with(EXTENSION))
+        {
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,1345 @@
+//! The registry of game content -- races, classes, enhancement trees, the
+//! enhancements of catalogued trees, and the former names of feats and
+//! enhancements -- that the parser and validators consult.
+//!
+//! The content is defined by `data/content.txt`, which is bundled into the
+//! library. Files of the same format can be loaded on top of it with
+//! `Registry::patch()`, so new names and corrected data don't have to wait for
+//! a new release of this crate.
+//!
+//! A tree's catalog has to list every one of its enhancements, since an
+//! enhancement that a catalogued tree doesn't list is taken to not exist. The
+//! bundled file doesn't catalog any tree yet, so enhancements are only
+//! checked, and their costs only known, for trees that a patch catalogs; the
+//! enhancements of other trees are counted as 1 action point per rank.
+
+use crate::{
+    Ability,
+    Alignment,
+    Class,
+    ClassEnhancementTree,
+    EnhancementTreeName,
+    EnhancementTreeType,
+    GlobalEnhancementTree,
+    Race,
+    RaceClassEnhancementTree,
+    Skill,
+};
+use fxhash::{FxHashMap as HashMap, FxHashSet as HashSet};
+use regex::Regex;
+use std::{
+    fmt,
+    fs::File,
+    io::{self, prelude::*, BufReader},
+    path::Path,
+    sync::{Arc, Mutex, RwLock},
+};
+
+const BUNDLED_CONTENT: &str = include_str!("../data/content.txt");
+
+lazy_static! {
+    static ref REGISTRY: RwLock<Arc<Registry>> =
+        RwLock::new(Arc::new(Registry::bundled()));
+}
+
+/// Returns the registry that is currently in use by `parse()` and by the
+/// `FromStr` and `Display` impls of races, classes and enhancement trees.
+pub fn registry() -> Arc<Registry> {
+    REGISTRY.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Replaces the registry returned by `registry()`.
+pub fn set_registry(registry: Registry) {
+    *REGISTRY.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(registry);
+}
+
+/// The `Id` of a race, class or tree that content files added, i.e. one that
+/// has no variant of its own (see e.g. `Race::Other`). Ids are interned, so
+/// that the enums that hold them stay `Copy`; an interned id is never freed.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ContentId(&'static str);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BaseAttack {
+    Full,
+    ThreeQuarters,
+    Half,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Save {
+    Fortitude,
+    Reflex,
+    Will,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RaceInfo {
+    pub name:              String,
+    pub aliases:           Vec<String>,
+    /// The short name used in canonical build filenames, e.g. `"drw"`.
+    pub abbreviation:      String,
+    /// The racial modifiers to the ability scores. Abilities that aren't
+    /// listed aren't modified.
+    pub ability_modifiers: Vec<(Ability, i8)>,
+    /// Bonus skill points per level. Quadrupled at level 1.
+    pub skill_points:      u8,
+    /// The class that an iconic race starts in, which must be its first
+    /// level.
+    pub starting_class:    Option<Class>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClassInfo {
+    pub name:            String,
+    pub aliases:         Vec<String>,
+    /// The short name used in canonical build filenames, e.g. `"brd"`.
+    pub abbreviation:    String,
+    pub hit_die:         u8,
+    /// Skill points per level, before the Intelligence modifier. Quadrupled
+    /// at level 1.
+    pub skill_points:    u8,
+    pub base_attack:     BaseAttack,
+    pub good_saves:      Vec<Save>,
+    pub class_skills:    Vec<Skill>,
+    /// The highest level of spell that the class can learn. `0` for classes
+    /// that have no spellbook.
+    pub max_spell_level: usize,
+    /// The class level at which each level of spell is learned, indexed by
+    /// `spell_level - 1`.
+    pub spell_unlocks:   Vec<u8>,
+    /// The class level at which each spell slot is learned, indexed by
+    /// `spell_level - 1` and then by slot. Empty for classes whose slots
+    /// aren't given, which get every slot with its level of spell (see
+    /// `slot_unlock`).
+    pub spell_slots:     Vec<Vec<u8>>,
+    /// The alignments that the class can be taken with. Empty if any
+    /// alignment will do.
+    pub alignments:      Vec<Alignment>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TreeInfo {
+    pub name:         String,
+    pub aliases:      Vec<String>,
+    /// The short name used in canonical build filenames, e.g. `"WC"`.
+    /// Racial trees share the abbreviation of their race.
+    pub abbreviation: String,
+    /// The classes whose levels can be spent in the tree. Empty for racial
+    /// and global trees.
+    pub sources:      Vec<Class>,
+    /// The race that a racial or race-class tree belongs to.
+    pub race:         Option<Race>,
+    /// The names that CBL writes for the tree when it is fueled by certain
+    /// classes, instead of `name`.
+    pub source_names: Vec<(Class, String)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FeatInfo {
+    pub name:    String,
+    pub aliases: Vec<String>,
+}
+
+/// An enhancement of a tree. Enhancements that are only listed for their
+/// former names have no `tier`; the others make up the tree's catalog (see
+/// `Registry::has_catalog()`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EnhancementInfo {
+    pub name:     String,
+    pub aliases:  Vec<String>,
+    pub tier:     Option<usize>,
+    pub ranks:    u8,
+    /// Action points spent on each rank.
+    pub cost:     u8,
+    /// The sub-enhancements to choose one of, e.g. the ability score of
+    /// "Skaldic". Empty if there is no choice to make.
+    pub choices:  Vec<String>,
+    /// Enhancements of the same tree that must be taken first.
+    pub requires: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Registry {
+    races:        HashMap<Race, RaceInfo>,
+    race_order:   Vec<Race>,
+    classes:      HashMap<Class, ClassInfo>,
+    class_order:  Vec<Class>,
+    /// Racial trees are not stored here; they are derived from `races`.
+    trees:        HashMap<EnhancementTreeName, TreeInfo>,
+    race_trees:   HashMap<Race, TreeInfo>,
+    feats:        Vec<FeatInfo>,
+    /// Enhancements, grouped by the tree that they belong to.
+    enhancements: HashMap<EnhancementTreeName, Vec<EnhancementInfo>>,
+
+    race_names:  HashMap<String, Race>,
+    class_names: HashMap<String, Class>,
+    /// A name can refer to more than one tree (e.g. "Arcane Archer"), in which
+    /// case the trees are listed in the order that they were defined.
+    tree_names:  HashMap<String, Vec<EnhancementTreeName>>,
+    tree_order:  Vec<EnhancementTreeName>,
+
+    race_abbreviations:  HashMap<String, Race>,
+    class_abbreviations: HashMap<String, Class>,
+    tree_abbreviations:  HashMap<String, EnhancementTreeName>,
+}
+
+#[derive(Debug)]
+pub enum ContentError {
+    IoError(io::Error),
+    UnknownHeader(String),
+    DataBeforeFirstHeader,
+    DataBeforeFirstRecord,
+    MalformedLine(String),
+    UnknownField(String),
+    MissingField(String, &'static str),
+    InvalidId(String),
+    UnknownRace(String),
+    UnknownClass(String),
+    UnknownAlignment(String),
+    UnknownAbility(String),
+    UnknownSkill(String),
+    UnknownSave(String),
+    UnknownBaseAttack(String),
+    UnknownTreeType(String),
+    UnknownTree(String),
+    InvalidNumber(String),
+    InvalidTier(String),
+    InvalidAbbreviation(String),
+}
+
+enum Heading {
+    None,
+    Races,
+    Classes,
+    Trees,
+    Feats,
+    Enhancements,
+}
+
+/// A record as it is written in a content file. Fields that were not given
+/// are `None`, so that patches only overwrite what they specify.
+enum Record {
+    Race {
+        name:              String,
+        id:                Option<String>,
+        aliases:           Vec<String>,
+        abbreviation:      Option<String>,
+        ability_modifiers: Option<Vec<(Ability, i8)>>,
+        skill_points:      Option<u8>,
+        starting_class:    Option<Class>,
+    },
+    Class {
+        name:            String,
+        id:              Option<String>,
+        aliases:         Vec<String>,
+        abbreviation:    Option<String>,
+        hit_die:         Option<u8>,
+        skill_points:    Option<u8>,
+        base_attack:     Option<BaseAttack>,
+        good_saves:      Option<Vec<Save>>,
+        class_skills:    Option<Vec<Skill>>,
+        max_spell_level: Option<usize>,
+        spell_unlocks:   Option<Vec<u8>>,
+        spell_slots:     Option<Vec<Vec<u8>>>,
+        alignments:      Option<Vec<Alignment>>,
+    },
+    Tree {
+        name:         String,
+        id:           Option<String>,
+        aliases:      Vec<String>,
+        abbreviation: Option<String>,
+        tree_type:    Option<EnhancementTreeType>,
+        sources:      Option<Vec<Class>>,
+        race:         Option<Race>,
+        source_names: Vec<(Class, String)>,
+    },
+    Feat {
+        name:    String,
+        aliases: Vec<String>,
+    },
+    Enhancement {
+        name:     String,
+        tree:     Option<EnhancementTreeName>,
+        aliases:  Vec<String>,
+        tier:     Option<usize>,
+        ranks:    Option<u8>,
+        cost:     Option<u8>,
+        choices:  Option<Vec<String>>,
+        requires: Vec<String>,
+    },
+}
+
+impl ContentId {
+    pub fn new(id: &str) -> Self {
+        lazy_static! {
+            static ref IDS: Mutex<HashSet<&'static str>> =
+                Mutex::new(HashSet::default());
+        }
+
+        let mut ids = IDS.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(&interned) = ids.get(id) {
+            return Self(interned);
+        }
+        let interned: &'static str = Box::leak(id.to_owned().into_boxed_str());
+        ids.insert(interned);
+
+        Self(interned)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        self.0
+    }
+}
+
+impl fmt::Debug for ContentId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.write_str(self.0)
+    }
+}
+
+impl ClassInfo {
+    /// The class level at which slot `slot` (counting from 0) of spell level
+    /// `spell_level` is learned, or `None` if the class never learns it.
+    /// Classes without `spell_slots` learn every slot with its level of
+    /// spell.
+    pub fn slot_unlock(&self, spell_level: usize, slot: usize) -> Option<u8> {
+        let i = spell_level.checked_sub(1)?;
+        match self.spell_slots.get(i) {
+            Some(slots) => slots.get(slot).copied(),
+            None if self.spell_slots.is_empty() =>
+                self.spell_unlocks.get(i).copied(),
+            None => None,
+        }
+    }
+}
+
+impl BaseAttack {
+    /// Base attack bonus granted by `class_levels` levels of a class with
+    /// this progression.
+    pub fn at_level(self, class_levels: u8) -> u8 {
+        match self {
+            Self::Full => class_levels,
+            Self::ThreeQuarters => class_levels * 3 / 4,
+            Self::Half => class_levels / 2,
+        }
+    }
+}
+
+impl std::str::FromStr for BaseAttack {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Full" => Ok(Self::Full),
+            "ThreeQuarters" => Ok(Self::ThreeQuarters),
+            "Half" => Ok(Self::Half),
+            _ => Err(()),
+        }
+    }
+}
+
+impl std::str::FromStr for Save {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Fortitude" => Ok(Self::Fortitude),
+            "Reflex" => Ok(Self::Reflex),
+            "Will" => Ok(Self::Will),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Save {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Self::Fortitude => f.write_str("Fortitude"),
+            Self::Reflex => f.write_str("Reflex"),
+            Self::Will => f.write_str("Will"),
+        }
+    }
+}
+
+impl Registry {
+    /// The registry defined by the bundled content file.
+    pub fn bundled() -> Self {
+        Self::load(&mut BUNDLED_CONTENT.as_bytes())
+            .expect("the bundled content file is valid")
+    }
+
+    /// Loads a registry from scratch, i.e. **without** the bundled content.
+    pub fn load<R: BufRead>(input: &mut R) -> Result<Self, ContentError> {
+        let mut registry = Self::default();
+        registry.patch(input)?;
+
+        Ok(registry)
+    }
+
+    /// Applies the records of a content file on top of this registry. If the
+    /// file has an error, the registry is left as it was.
+    pub fn patch<R: BufRead>(
+        &mut self,
+        input: &mut R,
+    ) -> Result<(), ContentError> {
+        let mut patched = self.clone();
+        patched.apply(input)?;
+        patched.reindex();
+        *self = patched;
+
+        Ok(())
+    }
+
+    /// Does the work of `patch()`, leaving the registry half patched if the
+    /// file has an error.
+    fn apply<R: BufRead>(
+        &mut self,
+        input: &mut R,
+    ) -> Result<(), ContentError> {
+        lazy_static! {
+            static ref BLANK_RE: Regex = Regex::new(r"^\s*(;.*)?$").unwrap();
+            static ref HEADING_RE: Regex =
+                Regex::new(r"^\[([A-Z][A-Za-z]*)\]$").unwrap();
+            static ref FIELD_RE: Regex =
+                Regex::new(r"^([A-Z][A-Za-z]*):\s+(.*[^\s])\s*$").unwrap();
+        }
+
+        let mut heading = Heading::None;
+        let mut record: Option<Record> = None;
+
+        for line in input.lines() {
+            let line = line?;
+
+            if BLANK_RE.is_match(&line) {
+                continue;
+            }
+
+            if let Some(heading_caps) = HEADING_RE.captures(&line) {
+                if let Some(r) = record.take() {
+                    self.commit(r)?;
+                }
+
+                match &heading_caps[1] {
+                    "Races" => heading = Heading::Races,
+                    "Classes" => heading = Heading::Classes,
+                    "Trees" => heading = Heading::Trees,
+                    "Feats" => heading = Heading::Feats,
+                    "Enhancements" => heading = Heading::Enhancements,
+                    h =>
+                        return Err(ContentError::UnknownHeader(h.to_owned())),
+                }
+
+                continue;
+            }
+
+            let field_caps = FIELD_RE
+                .captures(&line)
+                .ok_or_else(|| ContentError::MalformedLine(line.clone()))?;
+            let (key, value) = (&field_caps[1], &field_caps[2]);
+
+            match (&heading, key) {
+                (Heading::None, _) =>
+                    return Err(ContentError::DataBeforeFirstHeader),
+                (Heading::Races, "Race") => {
+                    if let Some(r) = record.take() {
+                        self.commit(r)?;
+                    }
+                    record = Some(Record::Race {
+                        name:              value.to_owned(),
+                        id:                None,
+                        aliases:           Vec::new(),
+                        abbreviation:      None,
+                        ability_modifiers: None,
+                        skill_points:      None,
+                        starting_class:    None,
+                    });
+                },
+                (Heading::Classes, "Class") => {
+                    if let Some(r) = record.take() {
+                        self.commit(r)?;
+                    }
+                    record = Some(Record::Class {
+                        name:            value.to_owned(),
+                        id:              None,
+                        aliases:         Vec::new(),
+                        abbreviation:    None,
+                        hit_die:         None,
+                        skill_points:    None,
+                        base_attack:     None,
+                        good_saves:      None,
+                        class_skills:    None,
+                        max_spell_level: None,
+                        spell_unlocks:   None,
+                        spell_slots:     None,
+                        alignments:      None,
+                    });
+                },
+                (Heading::Trees, "Tree") => {
+                    if let Some(r) = record.take() {
+                        self.commit(r)?;
+                    }
+                    record = Some(Record::Tree {
+                        name:         value.to_owned(),
+                        id:           None,
+                        aliases:      Vec::new(),
+                        abbreviation: None,
+                        tree_type:    None,
+                        sources:      None,
+                        race:         None,
+                        source_names: Vec::new(),
+                    });
+                },
+                (Heading::Feats, "Feat") => {
+                    if let Some(r) = record.take() {
+                        self.commit(r)?;
+                    }
+                    record = Some(Record::Feat {
+                        name:    value.to_owned(),
+                        aliases: Vec::new(),
+                    });
+                },
+                (Heading::Enhancements, "Enhancement") => {
+                    if let Some(r) = record.take() {
+                        self.commit(r)?;
+                    }
+                    record = Some(Record::Enhancement {
+                        name:     value.to_owned(),
+                        tree:     None,
+                        aliases:  Vec::new(),
+                        tier:     None,
+                        ranks:    None,
+                        cost:     None,
+                        choices:  None,
+                        requires: Vec::new(),
+                    });
+                },
+                _ => self.set_field(
+                    record
+                        .as_mut()
+                        .ok_or(ContentError::DataBeforeFirstRecord)?,
+                    key,
+                    value,
+                )?,
+            }
+        }
+
+        if let Some(r) = record.take() {
+            self.commit(r)?;
+        }
+
+        Ok(())
+    }
+
+    /// Opens the content file at `path` and applies it on top of this
+    /// registry.
+    pub fn patch_file<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> Result<(), ContentError> {
+        let file = File::open(path)?;
+
+        self.patch(&mut BufReader::new(file))
+    }
+
+    /// Lists the (`Debug` names of the) races, classes and non-racial trees
+    /// that have no entry in this registry.
+    pub fn missing(&self) -> Vec<String> {
+        let races = Race::ALL
+            .iter()
+            .filter(|r| !self.races.contains_key(r))
+            .map(|r| format!("{:?}", r));
+        let classes = Class::ALL
+            .iter()
+            .filter(|c| !self.classes.contains_key(c))
+            .map(|c| format!("{:?}", c));
+        let trees = all_trees()
+            .filter(|t| !self.trees.contains_key(t))
+            .map(|t| format!("{:?}", t));
+
+        races.chain(classes).chain(trees).collect()
+    }
+
+    pub fn race(&self, race: Race) -> Option<&RaceInfo> {
+        self.races.get(&race)
+    }
+
+    /// Looks up a race by its name or by one of its aliases.
+    pub fn race_by_name(&self, name: &str) -> Option<Race> {
+        self.race_names.get(name).copied()
+    }
+
+    /// Looks up a race by its abbreviation.
+    pub fn race_by_abbreviation(&self, abbreviation: &str) -> Option<Race> {
+        self.race_abbreviations.get(abbreviation).copied()
+    }
+
+    pub fn class(&self, class: Class) -> Option<&ClassInfo> {
+        self.classes.get(&class)
+    }
+
+    /// Looks up a class by its name or by one of its aliases.
+    pub fn class_by_name(&self, name: &str) -> Option<Class> {
+        self.class_names.get(name).copied()
+    }
+
+    /// Looks up a class by its abbreviation.
+    pub fn class_by_abbreviation(&self, abbreviation: &str) -> Option<Class> {
+        self.class_abbreviations.get(abbreviation).copied()
+    }
+
+    pub fn tree(&self, tree: EnhancementTreeName) -> Option<&TreeInfo> {
+        match tree {
+            EnhancementTreeName::Race(r) => self.race_trees.get(&r),
+            t => self.trees.get(&t),
+        }
+    }
+
+    /// Looks up an enhancement tree by its name or by one of its aliases. If
+    /// the name is shared by several trees, the first one defined is
+    /// returned; see `resolve_tree()`.
+    pub fn tree_by_name(&self, name: &str) -> Option<EnhancementTreeName> {
+        self.tree_names.get(name).map(|ts| ts[0])
+    }
+
+    /// Looks up an enhancement tree by its abbreviation. If several trees
+    /// share it, the first one defined is returned.
+    pub fn tree_by_abbreviation(
+        &self,
+        abbreviation: &str,
+    ) -> Option<EnhancementTreeName> {
+        self.tree_abbreviations.get(abbreviation).copied()
+    }
+
+    /// Lists every tree that goes by `name` (or has it as an alias), in the
+    /// order that they were defined.
+    pub fn trees_by_name(&self, name: &str) -> &[EnhancementTreeName] {
+        self.tree_names.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// Resolves which tree is meant by `name` when it is declared with the
+    /// given `Type:` and `Source:`. Of the trees that go by `name` and have
+    /// the right type, the first that `source` can be spent in is returned,
+    /// or else the first of them. `None` if no tree of that name has the
+    /// right type.
+    pub fn resolve_tree(
+        &self,
+        name: &str,
+        tree_type: Option<EnhancementTreeType>,
+        source: Option<Class>,
+    ) -> Option<EnhancementTreeName> {
+        let mut candidates = self
+            .trees_by_name(name)
+            .iter()
+            .copied()
+            .filter(|t| tree_type.map_or(true, |tt| t.tree_type() == tt))
+            .peekable();
+        let first = *candidates.peek()?;
+
+        candidates
+            .find(|&t| {
+                source.is_some_and(|s| {
+                    self.tree(t).is_some_and(|ti| ti.sources.contains(&s))
+                })
+            })
+            .or(Some(first))
+    }
+
+    /// The name that CBL writes for `tree` when it is fueled by `source`.
+    /// `None` if the tree isn't in this registry.
+    pub fn tree_name_for_source(
+        &self,
+        tree: EnhancementTreeName,
+        source: Option<Class>,
+    ) -> Option<&str> {
+        let info = self.tree(tree)?;
+
+        Some(
+            info.source_names
+                .iter()
+                .find(|(c, _)| Some(*c) == source)
+                .map_or(&info.name, |(_, n)| n),
+        )
+    }
+
+    /// Iterates over every race in this registry, in the order that they
+    /// were defined.
+    pub fn races(&self) -> impl Iterator<Item = (Race, &RaceInfo)> {
+        self.race_order.iter().map(move |&r| (r, &self.races[&r]))
+    }
+
+    /// Iterates over every class in this registry, in the order that they
+    /// were defined.
+    pub fn classes(&self) -> impl Iterator<Item = (Class, &ClassInfo)> {
+        self.class_order
+            .iter()
+            .map(move |&c| (c, &self.classes[&c]))
+    }
+
+    /// Iterates over every tree in this registry, in the order that they
+    /// were defined (racial trees come last).
+    pub fn trees(
+        &self,
+    ) -> impl Iterator<Item = (EnhancementTreeName, &TreeInfo)> {
+        self.tree_order
+            .iter()
+            .map(move |&t| (t, &self.trees[&t]))
+            .chain(self.race_order.iter().filter_map(move |&r| {
+                self.race_trees
+                    .get(&r)
+                    .map(|ti| (EnhancementTreeName::Race(r), ti))
+            }))
+    }
+
+    /// If `name` is a former name of a feat, returns the feat's current name.
+    pub fn current_feat_name(&self, name: &str) -> Option<&str> {
+        self.feats
+            .iter()
+            .find(|fi| fi.aliases.iter().any(|a| a == name))
+            .map(|fi| fi.name.as_str())
+    }
+
+    /// If `name` is a former name of an enhancement in `tree`, returns the
+    /// enhancement's current name.
+    pub fn current_enhancement_name(
+        &self,
+        tree: EnhancementTreeName,
+        name: &str,
+    ) -> Option<&str> {
+        self.enhancements
+            .get(&tree)?
+            .iter()
+            .find(|ei| ei.aliases.iter().any(|a| a == name))
+            .map(|ei| ei.name.as_str())
+    }
+
+    /// Whether the enhancements of `tree` are catalogued, i.e. whether any of
+    /// them has a tier, in which case the catalog is taken to be complete.
+    /// Only catalogued trees can be checked by
+    /// `validate::validate()`; the others are listed by
+    /// `validate::unchecked_trees()`.
+    pub fn has_catalog(&self, tree: EnhancementTreeName) -> bool {
+        self.enhancements
+            .get(&tree)
+            .is_some_and(|eis| eis.iter().any(|ei| ei.tier.is_some()))
+    }
+
+    /// Looks up the catalogued enhancement of `tree` that is written as
+    /// `name` (or one of its former names) in the given tier.
+    pub fn enhancement(
+        &self,
+        tree: EnhancementTreeName,
+        tier: usize,
+        name: &str,
+    ) -> Option<&EnhancementInfo> {
+        self.enhancements.get(&tree)?.iter().find(|ei| {
+            ei.tier == Some(tier)
+                && (ei.name == name || ei.aliases.iter().any(|a| a == name))
+        })
+    }
+
+    fn set_field(
+        &self,
+        record: &mut Record,
+        key: &str,
+        value: &str,
+    ) -> Result<(), ContentError> {
+        match (record, key) {
+            (Record::Race { id, .. }, "Id")
+            | (Record::Class { id, .. }, "Id")
+            | (Record::Tree { id, .. }, "Id") => {
+                let mut chars = value.chars();
+                if !chars.next().is_some_and(|c| c.is_ascii_uppercase())
+                    || !chars.all(|c| c.is_ascii_alphanumeric())
+                {
+                    return Err(ContentError::InvalidId(value.to_owned()));
+                }
+                *id = Some(value.to_owned());
+            },
+            (Record::Race { aliases, .. }, "Alias")
+            | (Record::Class { aliases, .. }, "Alias")
+            | (Record::Tree { aliases, .. }, "Alias")
+            | (Record::Feat { aliases, .. }, "Alias")
+            | (Record::Enhancement { aliases, .. }, "Alias") =>
+                aliases.push(value.to_owned()),
+            (Record::Race { abbreviation, .. }, "Abbreviation")
+            | (Record::Class { abbreviation, .. }, "Abbreviation")
+            | (Record::Tree { abbreviation, .. }, "Abbreviation") => {
+                if !value.chars().all(|c| c.is_ascii_alphanumeric()) {
+                    return Err(ContentError::InvalidAbbreviation(
+                        value.to_owned(),
+                    ));
+                }
+                *abbreviation = Some(value.to_owned());
+            },
+            (Record::Class { hit_die, .. }, "HitDie") =>
+                *hit_die = Some(parse_number(value)?),
+            (Record::Race { skill_points, .. }, "SkillPoints")
+            | (Record::Class { skill_points, .. }, "SkillPoints") =>
+                *skill_points = Some(parse_number(value)?),
+            (Record::Class { base_attack, .. }, "BaseAttack") =>
+                *base_attack = Some(value.parse().map_err(|_| {
+                    ContentError::UnknownBaseAttack(value.to_owned())
+                })?),
+            (Record::Class { good_saves, .. }, "GoodSaves") =>
+                *good_saves = Some(parse_list(value, |s| {
+                    s.parse()
+                        .map_err(|_| ContentError::UnknownSave(s.to_owned()))
+                })?),
+            (Record::Class { class_skills, .. }, "ClassSkills") =>
+                *class_skills = Some(parse_list(value, |s| {
+                    s.parse()
+                        .map_err(|_| ContentError::UnknownSkill(s.to_owned()))
+                })?),
+            (
+                Record::Class {
+                    max_spell_level, ..
+                },
+                "SpellLevels",
+            ) => *max_spell_level = Some(parse_number(value)?),
+            (Record::Class { spell_unlocks, .. }, "SpellUnlocks") =>
+                *spell_unlocks = Some(parse_list(value, parse_number)?),
+            (Record::Class { spell_slots, .. }, "SpellSlots") =>
+                *spell_slots = Some(parse_list(value, |s| {
+                    s.split_whitespace().map(parse_number).collect()
+                })?),
+            (Record::Class { alignments, .. }, "Alignments") =>
+                *alignments = Some(parse_list(value, |s| {
+                    s.parse().map_err(|_| {
+                        ContentError::UnknownAlignment(s.to_owned())
+                    })
+                })?),
+            (
+                Record::Race {
+                    ability_modifiers, ..
+                },
+                "Abilities",
+            ) =>
+                *ability_modifiers = Some(parse_list(value, |s| {
+                    let (ability_str, modifier) =
+                        s.split_once(' ').ok_or_else(|| {
+                            ContentError::MalformedLine(s.to_owned())
+                        })?;
+                    let ability = ability_str.parse().map_err(|_| {
+                        ContentError::UnknownAbility(ability_str.to_owned())
+                    })?;
+
+                    Ok((ability, parse_number(modifier)?))
+                })?),
+            (Record::Race { starting_class, .. }, "StartingClass") =>
+                *starting_class =
+                    Some(self.class_by_name(value).ok_or_else(|| {
+                        ContentError::UnknownClass(value.to_owned())
+                    })?),
+            (Record::Tree { tree_type, .. }, "Type") =>
+                *tree_type = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|&tt| tt != EnhancementTreeType::Race)
+                        .ok_or_else(|| {
+                            ContentError::UnknownTreeType(value.to_owned())
+                        })?,
+                ),
+            (Record::Tree { sources, .. }, "Source") => {
+                let class = self.class_by_name(value).ok_or_else(|| {
+                    ContentError::UnknownClass(value.to_owned())
+                })?;
+                sources.get_or_insert_with(Vec::new).push(class);
+            },
+            (Record::Tree { source_names, .. }, "SourceName") => {
+                let (class_str, name) =
+                    value.split_once(": ").ok_or_else(|| {
+                        ContentError::MalformedLine(value.to_owned())
+                    })?;
+                let class =
+                    self.class_by_name(class_str).ok_or_else(|| {
+                        ContentError::UnknownClass(class_str.to_owned())
+                    })?;
+                source_names.push((class, name.to_owned()));
+            },
+            (Record::Tree { race, .. }, "Race") =>
+                *race = Some(self.race_by_name(value).ok_or_else(|| {
+                    ContentError::UnknownRace(value.to_owned())
+                })?),
+            (Record::Enhancement { tier, .. }, "Tier") =>
+                *tier =
+                    Some(value.parse().ok().filter(|&t| t <= 5).ok_or_else(
+                        || ContentError::InvalidTier(value.to_owned()),
+                    )?),
+            (Record::Enhancement { ranks, .. }, "Ranks") =>
+                *ranks = Some(parse_number(value)?),
+            (Record::Enhancement { cost, .. }, "Cost") =>
+                *cost = Some(parse_number(value)?),
+            (Record::Enhancement { choices, .. }, "Choices") =>
+                *choices = Some(parse_list(value, |s| Ok(s.to_owned()))?),
+            (Record::Enhancement { requires, .. }, "Requires") =>
+                requires.push(value.to_owned()),
+            (Record::Enhancement { tree, .. }, "Tree") =>
+                *tree = Some(self.tree_by_name(value).ok_or_else(|| {
+                    ContentError::UnknownTree(value.to_owned())
+                })?),
+            (_, k) => return Err(ContentError::UnknownField(k.to_owned())),
+        }
+
+        Ok(())
+    }
+
+    fn commit(&mut self, record: Record) -> Result<(), ContentError> {
+        match record {
+            Record::Race {
+                name,
+                id,
+                aliases,
+                abbreviation,
+                ability_modifiers,
+                skill_points,
+                starting_class,
+            } => {
+                let id = id.ok_or_else(|| {
+                    ContentError::MissingField(name.clone(), "Id")
+                })?;
+                let race = race_by_id(&id);
+                if !self.races.contains_key(&race) {
+                    self.race_order.push(race);
+                }
+
+                let info =
+                    self.races.entry(race).or_insert_with(|| RaceInfo {
+                        name:              String::new(),
+                        aliases:           Vec::new(),
+                        abbreviation:      default_abbreviation(&name),
+                        ability_modifiers: Vec::new(),
+                        skill_points:      0,
+                        starting_class:    None,
+                    });
+                info.name = name;
+                info.aliases.extend(aliases);
+                if let Some(a) = abbreviation {
+                    info.abbreviation = a;
+                }
+                if let Some(mods) = ability_modifiers {
+                    info.ability_modifiers = mods;
+                }
+                if let Some(sp) = skill_points {
+                    info.skill_points = sp;
+                }
+                if starting_class.is_some() {
+                    info.starting_class = starting_class;
+                }
+
+                self.index_race(race);
+                self.index_tree(EnhancementTreeName::Race(race));
+            },
+            Record::Class {
+                name,
+                id,
+                aliases,
+                abbreviation,
+                hit_die,
+                skill_points,
+                base_attack,
+                good_saves,
+                class_skills,
+                max_spell_level,
+                spell_unlocks,
+                spell_slots,
+                alignments,
+            } => {
+                let id = id.ok_or_else(|| {
+                    ContentError::MissingField(name.clone(), "Id")
+                })?;
+                let class = class_by_id(&id);
+
+                if let Some(info) = self.classes.get_mut(&class) {
+                    info.name = name;
+                    info.aliases.extend(aliases);
+                    if let Some(a) = abbreviation {
+                        info.abbreviation = a;
+                    }
+                    if let Some(hd) = hit_die {
+                        info.hit_die = hd;
+                    }
+                    if let Some(sp) = skill_points {
+                        info.skill_points = sp;
+                    }
+                    if let Some(bab) = base_attack {
+                        info.base_attack = bab;
+                    }
+                    if let Some(saves) = good_saves {
+                        info.good_saves = saves;
+                    }
+                    if let Some(skills) = class_skills {
+                        info.class_skills = skills;
+                    }
+                    if let Some(msl) = max_spell_level {
+                        info.max_spell_level = msl;
+                    }
+                    if let Some(su) = spell_unlocks {
+                        info.spell_unlocks = su;
+                    }
+                    if let Some(ss) = spell_slots {
+                        info.spell_slots = ss;
+                    }
+                    if let Some(als) = alignments {
+                        info.alignments = als;
+                    }
+                } else {
+                    let missing = |field| {
+                        ContentError::MissingField(name.clone(), field)
+                    };
+                    let info = ClassInfo {
+                        hit_die: hit_die.ok_or_else(|| missing("HitDie"))?,
+                        skill_points: skill_points
+                            .ok_or_else(|| missing("SkillPoints"))?,
+                        base_attack: base_attack
+                            .ok_or_else(|| missing("BaseAttack"))?,
+                        good_saves: good_saves.unwrap_or_default(),
+                        class_skills: class_skills.unwrap_or_default(),
+                        max_spell_level: max_spell_level.unwrap_or(0),
+                        spell_unlocks: spell_unlocks.unwrap_or_default(),
+                        spell_slots: spell_slots.unwrap_or_default(),
+                        alignments: alignments.unwrap_or_default(),
+                        abbreviation: abbreviation
+                            .unwrap_or_else(|| default_abbreviation(&name)),
+                        name,
+                        aliases,
+                    };
+                    self.classes.insert(class, info);
+                    self.class_order.push(class);
+                }
+
+                self.index_class(class);
+            },
+            Record::Tree {
+                name,
+                id,
+                aliases,
+                abbreviation,
+                tree_type,
+                sources,
+                race,
+                source_names,
+            } => {
+                let id = id.ok_or_else(|| {
+                    ContentError::MissingField(name.clone(), "Id")
+                })?;
+                let tree_type = tree_type.ok_or_else(|| {
+                    ContentError::MissingField(name.clone(), "Type")
+                })?;
+                let tree = tree_by_id(tree_type, &id);
+
+                if let Some(info) = self.trees.get_mut(&tree) {
+                    info.name = name;
+                    info.aliases.extend(aliases);
+                    if let Some(a) = abbreviation {
+                        info.abbreviation = a;
+                    }
+                    if let Some(s) = sources {
+                        info.sources = s;
+                    }
+                    if race.is_some() {
+                        info.race = race;
+                    }
+                    for (class, name) in source_names {
+                        info.source_names.retain(|(c, _)| *c != class);
+                        info.source_names.push((class, name));
+                    }
+                } else {
+                    self.trees.insert(
+                        tree,
+                        TreeInfo {
+                            abbreviation: abbreviation.unwrap_or_else(|| {
+                                default_abbreviation(&name)
+                            }),
+                            name,
+                            aliases,
+                            sources: sources.unwrap_or_default(),
+                            race,
+                            source_names,
+                        },
+                    );
+                    self.tree_order.push(tree);
+                }
+
+                self.index_tree(tree);
+            },
+            Record::Feat { name, aliases } => {
+                if let Some(info) =
+                    self.feats.iter_mut().find(|fi| fi.name == name)
+                {
+                    info.aliases.extend(aliases);
+                } else {
+                    self.feats.push(FeatInfo { name, aliases });
+                }
+            },
+            Record::Enhancement {
+                name,
+                tree,
+                aliases,
+                tier,
+                ranks,
+                cost,
+                choices,
+                requires,
+            } => {
+                let tree = tree.ok_or_else(|| {
+                    ContentError::MissingField(name.clone(), "Tree")
+                })?;
+                let infos = self.enhancements.entry(tree).or_default();
+
+                // The same name can be used in several tiers, so a record
+                // with a tier only patches the enhancement of that tier.
+                if let Some(info) = infos.iter_mut().find(|ei| {
+                    ei.name == name && (tier.is_none() || ei.tier == tier)
+                }) {
+                    info.aliases.extend(aliases);
+                    if let Some(r) = ranks {
+                        info.ranks = r;
+                    }
+                    if let Some(c) = cost {
+                        info.cost = c;
+                    }
+                    if let Some(cs) = choices {
+                        info.choices = cs;
+                    }
+                    if !requires.is_empty() {
+                        info.requires = requires;
+                    }
+                } else {
+                    infos.push(EnhancementInfo {
+                        name,
+                        aliases,
+                        tier,
+                        ranks: ranks.unwrap_or(1),
+                        cost: cost.unwrap_or(1),
+                        choices: choices.unwrap_or_default(),
+                        requires,
+                    });
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Rebuilds the name lookup tables and the racial trees. While a file is
+    /// being applied, `commit()` only adds the names of each record that it
+    /// commits, so that later records can refer to them; this puts every
+    /// table back in the order that the entries were defined.
+    fn reindex(&mut self) {
+        self.race_names.clear();
+        self.race_abbreviations.clear();
+        self.race_trees.clear();
+        self.class_names.clear();
+        self.class_abbreviations.clear();
+        self.tree_names.clear();
+        self.tree_abbreviations.clear();
+
+        for race in self.race_order.clone() {
+            self.index_race(race);
+        }
+        for class in self.class_order.clone() {
+            self.index_class(class);
+        }
+        let trees: Vec<_> = self.trees().map(|(tree, _)| tree).collect();
+        for tree in trees {
+            self.index_tree(tree);
+        }
+    }
+
+    /// Adds the names of `race` to the lookup tables, and derives its racial
+    /// tree.
+    fn index_race(&mut self, race: Race) {
+        if let Some(info) = self.races.get(&race) {
+            for name in std::iter::once(&info.name).chain(&info.aliases) {
+                self.race_names.insert(name.clone(), race);
+            }
+            self.race_abbreviations
+                .entry(info.abbreviation.clone())
+                .or_insert(race);
+            self.race_trees.insert(
+                race,
+                TreeInfo {
+                    name:         info.name.clone(),
+                    aliases:      info.aliases.clone(),
+                    abbreviation: info.abbreviation.clone(),
+                    sources:      Vec::new(),
+                    race:         Some(race),
+                    source_names: Vec::new(),
+                },
+            );
+        }
+    }
+
+    fn index_class(&mut self, class: Class) {
+        if let Some(info) = self.classes.get(&class) {
+            for name in std::iter::once(&info.name).chain(&info.aliases) {
+                self.class_names.insert(name.clone(), class);
+            }
+            self.class_abbreviations
+                .entry(info.abbreviation.clone())
+                .or_insert(class);
+        }
+    }
+
+    fn index_tree(&mut self, tree: EnhancementTreeName) {
+        let info = match tree {
+            EnhancementTreeName::Race(r) => self.race_trees.get(&r),
+            _ => self.trees.get(&tree),
+        };
+        if let Some(info) = info {
+            self.tree_abbreviations
+                .entry(info.abbreviation.clone())
+                .or_insert(tree);
+            let source_names = info.source_names.iter().map(|(_, n)| n);
+            for name in std::iter::once(&info.name)
+                .chain(&info.aliases)
+                .chain(source_names)
+            {
+                let names = self.tree_names.entry(name.clone()).or_default();
+                if !names.contains(&tree) {
+                    names.push(tree);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for ContentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Self::IoError(ioe) => ioe.fmt(f),
+            Self::UnknownHeader(h) => write!(f, "Unknown header: [{}]", h),
+            Self::DataBeforeFirstHeader =>
+                f.write_str("Data found before first header"),
+            Self::DataBeforeFirstRecord =>
+                f.write_str("Data found before first record"),
+            Self::MalformedLine(l) => write!(f, "Malformed line: {}", l),
+            Self::UnknownField(k) => write!(f, "Unknown field: {}", k),
+            Self::MissingField(r, k) =>
+                write!(f, "Record {} is missing the field {}", r, k),
+            Self::InvalidId(id) => write!(f, "Invalid id: {}", id),
+            Self::UnknownRace(r) => write!(f, "Unknown race: {}", r),
+            Self::UnknownClass(c) => write!(f, "Unknown class: {}", c),
+            Self::UnknownAlignment(a) => write!(f, "Unknown alignment: {}", a),
+            Self::UnknownAbility(a) => write!(f, "Unknown ability: {}", a),
+            Self::UnknownSkill(s) => write!(f, "Unknown skill: {}", s),
+            Self::UnknownSave(s) => write!(f, "Unknown save: {}", s),
+            Self::UnknownBaseAttack(b) =>
+                write!(f, "Unknown base attack progression: {}", b),
+            Self::UnknownTreeType(t) =>
+                write!(f, "Unknown enhancement tree type: {}", t),
+            Self::UnknownTree(t) =>
+                write!(f, "Unknown enhancement tree: {}", t),
+            Self::InvalidNumber(n) => write!(f, "Invalid number: {}", n),
+            Self::InvalidTier(t) => write!(f, "Invalid tier: {}", t),
+            Self::InvalidAbbreviation(a) =>
+                write!(f, "Invalid abbreviation: {}", a),
+        }
+    }
+}
+
+impl std::error::Error for ContentError {}
+
+impl std::convert::From<io::Error> for ContentError {
+    fn from(ioe: io::Error) -> Self {
+        Self::IoError(ioe)
+    }
+}
+
+/// The abbreviation of an entry that wasn't given one: its name, without
+/// anything that isn't a letter or a digit.
+fn default_abbreviation(name: &str) -> String {
+    name.chars().filter(char::is_ascii_alphanumeric).collect()
+}
+
+/// Every non-racial tree that the crate knows of.
+fn all_trees() -> impl Iterator<Item = EnhancementTreeName> {
+    ClassEnhancementTree::ALL
+        .iter()
+        .map(|&t| EnhancementTreeName::Class(t))
+        .chain(
+            GlobalEnhancementTree::ALL
+                .iter()
+                .map(|&t| EnhancementTreeName::Global(t)),
+        )
+        .chain(
+            RaceClassEnhancementTree::ALL
+                .iter()
+                .map(|&t| EnhancementTreeName::RaceClass(t)),
+        )
+}
+
+/// The race with the `Id` `id`: the variant of that name, or else an
+/// `Other` race. `Halfing` is still accepted for `Halfling`, the variant's
+/// name before it was corrected.
+fn race_by_id(id: &str) -> Race {
+    let id = match id {
+        "Halfing" => "Halfling",
+        id => id,
+    };
+
+    Race::ALL
+        .iter()
+        .copied()
+        .find(|r| format!("{:?}", r) == id)
+        .unwrap_or_else(|| Race::Other(ContentId::new(id)))
+}
+
+/// The class with the `Id` `id`: the variant of that name, or else an
+/// `Other` class.
+fn class_by_id(id: &str) -> Class {
+    Class::ALL
+        .iter()
+        .copied()
+        .find(|c| format!("{:?}", c) == id)
+        .unwrap_or_else(|| Class::Other(ContentId::new(id)))
+}
+
+/// The non-racial tree of type `tree_type` with the `Id` `id`: the variant
+/// of that name, or else an `Other` tree of that type.
+fn tree_by_id(
+    tree_type: EnhancementTreeType,
+    id: &str,
+) -> EnhancementTreeName {
+    all_trees()
+        .find(|t| t.tree_type() == tree_type && tree_id(t) == id)
+        .unwrap_or_else(|| {
+            let id = ContentId::new(id);
+            match tree_type {
+                EnhancementTreeType::Global => EnhancementTreeName::Global(
+                    GlobalEnhancementTree::Other(id),
+                ),
+                EnhancementTreeType::RaceClass =>
+                    EnhancementTreeName::RaceClass(
+                        RaceClassEnhancementTree::Other(id),
+                    ),
+                _ => EnhancementTreeName::Class(
+                    ClassEnhancementTree::Other(id),
+                ),
+            }
+        })
+}
+
+/// The `Id` of a tree, i.e. the name of its innermost enum variant.
+fn tree_id(tree: &EnhancementTreeName) -> String {
+    match tree {
+        EnhancementTreeName::Class(t) => format!("{:?}", t),
+        EnhancementTreeName::Race(r) => format!("{:?}", r),
+        EnhancementTreeName::Global(t) => format!("{:?}", t),
+        EnhancementTreeName::RaceClass(t) => format!("{:?}", t),
+    }
+}
+
+fn parse_number<N: std::str::FromStr>(s: &str) -> Result<N, ContentError> {
+    s.parse()
+        .map_err(|_| ContentError::InvalidNumber(s.to_owned()))
+}
+
+fn parse_list<T, F>(s: &str, parse_item: F) -> Result<Vec<T>, ContentError>
+where
+    F: Fn(&str) -> Result<T, ContentError>,
+{
+    s.split(',').map(|item| parse_item(item.trim())).collect()
+}
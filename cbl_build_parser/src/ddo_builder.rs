@@ -0,0 +1,1014 @@
+//! Reading and writing DDO Builder's XML character files.
+//!
+//! A character file looks like this, with one `<AbilitySpend>` per enabled
+//! build type, one `<TrainedLevel>` per heroic level and one `<TrainedSkill>`
+//! per skill point:
+//!
+//! ```text
+//! <DDOBuilderCharacterData>
+//!   <Character>
+//!     <Name>Wartrapper</Name>
+//!     <Race>Drow</Race>
+//!     <Alignment>TrueNeutral</Alignment>
+//!     <MaxLevels>20</MaxLevels>
+//!     <BuildType>Adventurer</BuildType>
+//!     <AbilitySpend>
+//!       <AvailableSpend>28</AvailableSpend>
+//!       <StrSpend>10</StrSpend>
+//!       ...
+//!     </AbilitySpend>
+//!     <StrTome>0</StrTome>
+//!     <Level4>Strength</Level4>
+//!     <LevelTraining>
+//!       <TrainedLevel>
+//!         <Class>Rogue</Class>
+//!         <TrainedSkill><Skill>DisableDevice</Skill></TrainedSkill>
+//!       </TrainedLevel>
+//!     </LevelTraining>
+//!     <TrainedFeats>
+//!       <TrainedFeat>
+//!         <FeatName>Cleave</FeatName>
+//!         <Type>FighterBonus</Type>
+//!         <LevelTrainedAt>2</LevelTrainedAt>
+//!       </TrainedFeat>
+//!     </TrainedFeats>
+//!     ...
+//!   </Character>
+//! </DDOBuilderCharacterData>
+//! ```
+//!
+//! Races, alignments, classes, skills and enhancement trees are named
+//! without spaces, e.g. "TrueNeutral" or "StalwartDefender", though names
+//! with spaces are read as well. `<MaxLevels>` and `<BuildType>` (the
+//! preferred build type) may be left out, in which case the max level is
+//! the last level that a feat is trained at, if past the heroic levels, and
+//! the preferred build type is that of the first `<AbilitySpend>`. An empty
+//! `<SpellName>` is an empty spell slot.
+//!
+//! Feats are trained at a character level counted from 0. An enhancement's
+//! tier is read from its `<Tier>` element, which is written for every
+//! enhancement, since a name can be taken at several tiers (e.g. "Improved
+//! Stalwart Defense"). Files without one fall back on the content catalog.
+//!
+//! Neither format holds everything that the other does, so both directions
+//! report what didn't map across as `Unmapped`s.
+
+use crate::{
+    content::{self, Registry},
+    forum::resolve_tree,
+    write::{class_name, race_name, tree_name},
+    Ability,
+    Alignment,
+    BuildType,
+    CharacterBuild,
+    Class,
+    Enhancement,
+    EnhancementTreeName,
+    Enhancements,
+    Feat,
+    Feats,
+    Race,
+    SecondaryFeatType,
+    Skill,
+    Skills,
+    Spells,
+    Stats,
+    HEROIC_LEVEL_CAP,
+    LEVELUP_COUNT,
+    LEVELUP_INTERVAL,
+    LEVEL_CAP,
+};
+use quick_xml::{escape::escape, events::Event, Reader};
+use std::{
+    fmt,
+    io::{self, prelude::*},
+    num::NonZeroU8,
+};
+
+/// Why a character file couldn't be imported at all.
+#[derive(Debug)]
+pub enum XmlError {
+    Xml(quick_xml::Error),
+    /// The file has no `<DDOBuilderCharacterData><Character>`.
+    NotACharacter,
+    NoRace,
+    UnknownRace(String),
+    UnknownAlignment(String),
+    UnknownClass(String),
+    /// No `<TrainedLevel>`s, or more than `HEROIC_LEVEL_CAP`.
+    WrongLevelNumber(usize),
+}
+
+/// Something that didn't map across from one format to the other.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Unmapped {
+    /// An element of `<Character>` that has no counterpart in a build, e.g.
+    /// gear.
+    Element(String),
+    /// An `<AvailableSpend>` that isn't the build points of any build type.
+    /// The stats are kept as those of the preferred build type.
+    AvailableSpend(String),
+    UnknownBuildType(String),
+    UnknownAbility(String),
+    UnknownSkill(String),
+    /// A feat, with its `<Type>` and `<LevelTrainedAt>` as written, that has
+    /// no slot in the other format.
+    Feat {
+        name:      String,
+        feat_type: String,
+        level:     String,
+    },
+    /// A spell of a class or level that the build has no room for.
+    Spell {
+        class: String,
+        level: String,
+        name:  String,
+    },
+    UnknownTree(String),
+    /// An enhancement whose tier is neither given nor in the catalog.
+    EnhancementTier {
+        tree: String,
+        name: String,
+    },
+}
+
+impl fmt::Display for XmlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Self::Xml(xe) => xe.fmt(f),
+            Self::NotACharacter =>
+                f.write_str("Not a DDO Builder character file"),
+            Self::NoRace => f.write_str("No race specified"),
+            Self::UnknownRace(r) => write!(f, "Unknown race: {}", r),
+            Self::UnknownAlignment(a) => write!(f, "Unknown alignment: {}", a),
+            Self::UnknownClass(c) => write!(f, "Unknown class: {}", c),
+            Self::WrongLevelNumber(n) => write!(
+                f,
+                "Wrong number of trained levels; expected 1 to {}, got {}",
+                HEROIC_LEVEL_CAP, n,
+            ),
+        }
+    }
+}
+
+impl std::error::Error for XmlError {}
+
+impl std::convert::From<quick_xml::Error> for XmlError {
+    fn from(xe: quick_xml::Error) -> Self {
+        Self::Xml(xe)
+    }
+}
+
+impl std::convert::From<io::Error> for XmlError {
+    fn from(ioe: io::Error) -> Self {
+        Self::Xml(quick_xml::Error::from(ioe))
+    }
+}
+
+impl fmt::Display for Unmapped {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Self::Element(e) => write!(f, "<{}>", e),
+            Self::AvailableSpend(s) =>
+                write!(f, "Available spend of {} build points", s),
+            Self::UnknownBuildType(bt) =>
+                write!(f, "Unknown build type: {}", bt),
+            Self::UnknownAbility(a) => write!(f, "Unknown ability: {}", a),
+            Self::UnknownSkill(s) => write!(f, "Unknown skill: {}", s),
+            Self::Feat {
+                name,
+                feat_type,
+                level,
+            } => write!(f, "Feat: {} ({} at {})", name, feat_type, level),
+            Self::Spell { class, level, name } =>
+                write!(f, "Spell: {} ({} {})", name, class, level),
+            Self::UnknownTree(t) =>
+                write!(f, "Unknown enhancement tree: {}", t),
+            Self::EnhancementTier { tree, name } =>
+                write!(f, "Enhancement without a tier: {} ({})", name, tree),
+        }
+    }
+}
+
+/// An XML element, with its text and the elements inside of it.
+#[derive(Debug, Default)]
+struct Element {
+    name:     String,
+    text:     String,
+    children: Vec<Element>,
+}
+
+impl Element {
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|e| e.name == name)
+    }
+
+    fn children<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> impl Iterator<Item = &'a Element> + 'a {
+        self.children.iter().filter(move |e| e.name == name)
+    }
+
+    /// The text of the named child, or `""` if there is none.
+    fn child_text(&self, name: &str) -> &str {
+        self.child(name).map_or("", |e| e.text.as_str())
+    }
+}
+
+/// Reads a document into its root element.
+fn read_document<R: BufRead>(input: R) -> Result<Element, XmlError> {
+    let mut reader = Reader::from_reader(input);
+    reader.trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut stack = vec![Element::default()];
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(start) => stack.push(Element {
+                name: String::from_utf8_lossy(start.name().as_ref())
+                    .into_owned(),
+                ..Element::default()
+            }),
+            Event::Empty(empty) => {
+                let element = Element {
+                    name: String::from_utf8_lossy(empty.name().as_ref())
+                        .into_owned(),
+                    ..Element::default()
+                };
+                stack.last_mut().unwrap().children.push(element);
+            },
+            Event::Text(text) =>
+                stack.last_mut().unwrap().text.push_str(&text.unescape()?),
+            Event::CData(cdata) => stack
+                .last_mut()
+                .unwrap()
+                .text
+                .push_str(&String::from_utf8_lossy(&cdata)),
+            Event::End(_) => {
+                let element = stack.pop().unwrap();
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Err(XmlError::NotACharacter),
+                }
+            },
+            Event::Eof => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+
+    let mut document = stack.swap_remove(0);
+    if document.children.len() != 1 {
+        return Err(XmlError::NotACharacter);
+    }
+
+    Ok(document.children.remove(0))
+}
+
+/// The elements of `<Character>` that are read by `import_with()`.
+const KNOWN_ELEMENTS: [&str; 12] = [
+    "Name",
+    "Race",
+    "Alignment",
+    "MaxLevels",
+    "BuildType",
+    "AbilitySpend",
+    "LevelTraining",
+    "TrainedFeats",
+    "TrainedSpells",
+    "SkillTomes",
+    "Tier5Tree",
+    "EnhancementTreeSpend",
+];
+
+/// Reads a DDO Builder character file using the game content of
+/// `content::registry()`.
+pub fn import<R: BufRead>(
+    input: &mut R,
+) -> Result<(CharacterBuild, Vec<Unmapped>), XmlError> {
+    import_with(input, &content::registry())
+}
+
+/// Reads a DDO Builder character file, looking up the names of races,
+/// classes and enhancement trees in `content`. Returns the build along with
+/// everything in the file that has no counterpart in it.
+pub fn import_with<R: BufRead>(
+    input: &mut R,
+    content: &Registry,
+) -> Result<(CharacterBuild, Vec<Unmapped>), XmlError> {
+    let root = read_document(input)?;
+    if root.name != "DDOBuilderCharacterData" {
+        return Err(XmlError::NotACharacter);
+    }
+    let character = root.child("Character").ok_or(XmlError::NotACharacter)?;
+
+    let mut unmapped = Vec::new();
+    for element in character.children.iter() {
+        let name = element.name.as_str();
+        let known = KNOWN_ELEMENTS.contains(&name)
+            || is_tome_element(name)
+            || levelup_element_level(name).is_some();
+        if !known {
+            unmapped.push(Unmapped::Element(element.name.clone()));
+        }
+    }
+
+    // Overview
+    let name = character.child_text("Name").to_owned();
+    let race_str = character.child("Race").ok_or(XmlError::NoRace)?;
+    let race = read_race(&race_str.text, content)
+        .ok_or_else(|| XmlError::UnknownRace(race_str.text.clone()))?;
+    let alignment_str = character.child_text("Alignment");
+    let alignment = read_alignment(alignment_str)
+        .ok_or_else(|| XmlError::UnknownAlignment(alignment_str.to_owned()))?;
+
+    let trained_levels: Vec<&Element> = character
+        .child("LevelTraining")
+        .map(|lt| lt.children("TrainedLevel").collect())
+        .unwrap_or_default();
+    if trained_levels.is_empty()
+        || trained_levels.len() > usize::from(HEROIC_LEVEL_CAP)
+    {
+        return Err(XmlError::WrongLevelNumber(trained_levels.len()));
+    }
+    let levels = trained_levels
+        .iter()
+        .map(|tl| {
+            let class_str = tl.child_text("Class");
+            read_class(class_str, content)
+                .ok_or_else(|| XmlError::UnknownClass(class_str.to_owned()))
+        })
+        .collect::<Result<Vec<Class>, XmlError>>()?;
+
+    // Stats
+    let build_type = character.child("BuildType").and_then(|bt| {
+        let build_type = bt.text.parse().ok();
+        if build_type.is_none() {
+            unmapped.push(Unmapped::UnknownBuildType(bt.text.clone()));
+        }
+
+        build_type
+    });
+    let mut stat_sets: Vec<(BuildType, Stats)> = Vec::with_capacity(4);
+    for spend in character.children("AbilitySpend") {
+        let available = spend.child_text("AvailableSpend");
+        let spend_type = match BuildType::ALL
+            .iter()
+            .find(|bt| available.parse() == Ok(bt.build_points()))
+        {
+            Some(&bt) => bt,
+            None => {
+                unmapped.push(Unmapped::AvailableSpend(available.to_owned()));
+                build_type.unwrap_or(BuildType::Adventurer)
+            },
+        };
+        let mut stats = Stats::default();
+        for &ability in Ability::ALL.iter() {
+            stats[ability] = spend
+                .child_text(&format!("{}Spend", short_name(ability)))
+                .parse()
+                .unwrap_or(0);
+        }
+        stat_sets.push((spend_type, stats));
+    }
+    let preferred_build_type = build_type
+        .or_else(|| stat_sets.first().map(|&(bt, _)| bt))
+        .unwrap_or(BuildType::Adventurer);
+    let stats_of = |build_type| {
+        stat_sets
+            .iter()
+            .find(|&&(bt, _)| bt == build_type)
+            .map(|(_, stats)| stats.clone())
+    };
+
+    let mut stat_tomes = Stats::default();
+    for &ability in Ability::ALL.iter() {
+        stat_tomes[ability] = character
+            .child_text(&format!("{}Tome", short_name(ability)))
+            .parse()
+            .unwrap_or(0);
+    }
+
+    let mut stat_levelups = [None; LEVELUP_COUNT];
+    for element in character.children.iter() {
+        if let Some(level) = levelup_element_level(&element.name) {
+            match element.text.parse() {
+                Ok(ability) =>
+                    stat_levelups[usize::from(level / LEVELUP_INTERVAL - 1)] =
+                        Some(ability),
+                Err(()) => unmapped
+                    .push(Unmapped::UnknownAbility(element.text.clone())),
+            }
+        }
+    }
+
+    // Skills
+    let mut skills = Skills::default();
+    for (i, tl) in trained_levels.iter().enumerate() {
+        for ts in tl.children("TrainedSkill") {
+            let skill_str = ts.child_text("Skill");
+            match read_skill(skill_str) {
+                Some(skill) =>
+                    skills
+                        .skill_table
+                        .entry(skill)
+                        .or_insert([0; HEROIC_LEVEL_CAP as usize])[i] += 1,
+                None =>
+                    unmapped.push(Unmapped::UnknownSkill(skill_str.to_owned())),
+            }
+        }
+    }
+    for tome in character
+        .child("SkillTomes")
+        .into_iter()
+        .flat_map(|st| st.children("SkillTome"))
+    {
+        let skill_str = tome.child_text("Skill");
+        match read_skill(skill_str) {
+            Some(skill) => {
+                let value = tome.child_text("Value").parse().unwrap_or(0);
+                if value > 0 {
+                    skills.skill_tomes.insert(skill, value);
+                }
+            },
+            None =>
+                unmapped.push(Unmapped::UnknownSkill(skill_str.to_owned())),
+        }
+    }
+
+    // Feats
+    let mut feats = Feats::default();
+    let mut max_levels = character
+        .child_text("MaxLevels")
+        .parse::<u8>()
+        .ok()
+        .filter(|&m| m <= LEVEL_CAP)
+        .map_or(levels.len() as u8, |m| m.max(levels.len() as u8));
+    for tf in character
+        .child("TrainedFeats")
+        .into_iter()
+        .flat_map(|f| f.children("TrainedFeat"))
+    {
+        let feat_name = tf.child_text("FeatName");
+        let feat_type = tf.child_text("Type");
+        let level_str = tf.child_text("LevelTrainedAt");
+        let feat = level_str
+            .parse::<u8>()
+            .ok()
+            .map(|l| l + 1)
+            .filter(|l| (1..=LEVEL_CAP).contains(l))
+            .and_then(|level| {
+                let secondary_type =
+                    secondary_feat_type(feat_type, race, content)?;
+                let feat_level = match secondary_type {
+                    SecondaryFeatType::Class(c) => {
+                        let class_level = levels
+                            .iter()
+                            .take(level.into())
+                            .filter(|&&l| l == c)
+                            .count()
+                            as u8;
+                        if class_level == 0 || level > HEROIC_LEVEL_CAP {
+                            return None;
+                        }
+
+                        class_level
+                    },
+                    _ => level,
+                };
+                max_levels = max_levels.max(level);
+
+                Some(Feat::new(
+                    secondary_type,
+                    feat_level,
+                    feat_name.to_owned(),
+                ))
+            });
+
+        match feat {
+            Some(feat) => match feat.secondary_type {
+                SecondaryFeatType::Heroic
+                | SecondaryFeatType::Epic
+                | SecondaryFeatType::Destiny =>
+                    feats.standard_feats.push(feat),
+                SecondaryFeatType::Legend => feats.legend_feats.push(feat),
+                SecondaryFeatType::Class(_) => feats.class_feats.push(feat),
+                SecondaryFeatType::Race(_) => feats.race_feats.push(feat),
+            },
+            None => unmapped.push(Unmapped::Feat {
+                name:      feat_name.to_owned(),
+                feat_type: feat_type.to_owned(),
+                level:     level_str.to_owned(),
+            }),
+        }
+    }
+
+    // Spells
+    let mut spells = Spells::default();
+    for ts in character
+        .child("TrainedSpells")
+        .into_iter()
+        .flat_map(|s| s.children("TrainedSpell"))
+    {
+        let class_str = ts.child_text("Class");
+        let level_str = ts.child_text("Level");
+        let spell_name = ts.child_text("SpellName");
+        let inserted = read_class(class_str, content)
+            .zip(
+                level_str
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|l| (1..=9).contains(l)),
+            )
+            .and_then(|(class, level)| {
+                let name =
+                    Some(spell_name.to_owned()).filter(|n| !n.is_empty());
+                spells.insert(class, level, name).ok()
+            });
+
+        if inserted.is_none() {
+            unmapped.push(Unmapped::Spell {
+                class: class_str.to_owned(),
+                level: level_str.to_owned(),
+                name:  spell_name.to_owned(),
+            });
+        }
+    }
+
+    // Enhancements
+    let mut enhancements = Enhancements::default();
+    for spend in character
+        .child("EnhancementTreeSpend")
+        .into_iter()
+        .flat_map(|ets| ets.children("EnhancementSpendInTree"))
+    {
+        let tree_str = spend.child_text("TreeName");
+        let tree = match read_tree(tree_str, &levels, race, content) {
+            Some(tree) => tree,
+            None => {
+                unmapped.push(Unmapped::UnknownTree(tree_str.to_owned()));
+                continue;
+            },
+        };
+        // DDO Builder has no say in which class fuels a tree, so it is the
+        // first of the build's classes that can.
+        let declared = enhancements.declare(tree);
+        declared.source = content.tree(tree).and_then(|ti| {
+            ti.sources.iter().copied().find(|s| levels.contains(s))
+        });
+        declared.class_levels = Some(match declared.source {
+            Some(class) =>
+                levels.iter().filter(|&&c| c == class).count() as u8,
+            None => max_levels,
+        });
+
+        for te in spend.children("TrainedEnhancement") {
+            let enhancement_name = te.child_text("EnhancementName");
+            let tier = te
+                .child_text("Tier")
+                .parse()
+                .ok()
+                .filter(|&t| t <= 5)
+                .or_else(|| {
+                    (0..=5).find(|&t| {
+                        content
+                            .enhancement(tree, t, enhancement_name)
+                            .is_some()
+                    })
+                });
+            let tier = match tier {
+                Some(tier) => tier,
+                None => {
+                    unmapped.push(Unmapped::EnhancementTier {
+                        tree: tree_str.to_owned(),
+                        name: enhancement_name.to_owned(),
+                    });
+                    continue;
+                },
+            };
+            let subenhancement = te
+                .child("Selection")
+                .map(|s| s.text.clone())
+                .filter(|s| !s.is_empty());
+            let rank =
+                te.child_text("Ranks").parse().ok().and_then(NonZeroU8::new);
+
+            enhancements.insert(
+                tree,
+                tier,
+                Enhancement::new(
+                    enhancement_name.to_owned(),
+                    subenhancement,
+                    rank,
+                ),
+            );
+        }
+    }
+    let tier_five = character.child("Tier5Tree").and_then(|t| {
+        let tree = read_tree(&t.text, &levels, race, content);
+        if tree.is_none() {
+            unmapped.push(Unmapped::UnknownTree(t.text.clone()));
+        }
+
+        tree
+    });
+
+    let build = CharacterBuild {
+        name,
+        race,
+        alignment,
+        max_levels,
+        levels,
+        preferred_build_type,
+        adventurer_stats: stats_of(BuildType::Adventurer),
+        champion_stats: stats_of(BuildType::Champion),
+        hero_stats: stats_of(BuildType::Hero),
+        legend_stats: stats_of(BuildType::Legend),
+        stat_tomes,
+        stat_levelups,
+        skills,
+        feats,
+        spells,
+        tier_five,
+        enhancements,
+    };
+
+    Ok((build, unmapped))
+}
+
+/// Writes a build as a DDO Builder character file, using the game content of
+/// `content::registry()`.
+pub fn export<W: Write>(
+    build: &CharacterBuild,
+    out: &mut W,
+) -> io::Result<Vec<Unmapped>> {
+    export_with(build, out, &content::registry())
+}
+
+/// Writes a build as a DDO Builder character file, with the names of races,
+/// classes and enhancement trees from `content`. Returns everything in the
+/// build that has no counterpart in the file.
+pub fn export_with<W: Write>(
+    build: &CharacterBuild,
+    out: &mut W,
+    content: &Registry,
+) -> io::Result<Vec<Unmapped>> {
+    let mut unmapped = Vec::new();
+    let mut xml = String::with_capacity(16_384);
+
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<DDOBuilderCharacterData>\n  <Character>\n");
+    push_text(&mut xml, 2, "Name", &build.name);
+    push_text(
+        &mut xml,
+        2,
+        "Race",
+        &ddob_name(&race_name(content, build.race)),
+    );
+    push_text(
+        &mut xml,
+        2,
+        "Alignment",
+        &ddob_name(&build.alignment.to_string()),
+    );
+    push_text(&mut xml, 2, "MaxLevels", &build.max_levels.to_string());
+    push_text(
+        &mut xml,
+        2,
+        "BuildType",
+        &build.preferred_build_type.to_string(),
+    );
+
+    // Stats
+    for &build_type in BuildType::ALL.iter() {
+        let stats = match build.stats(build_type) {
+            Some(stats) => stats,
+            None => continue,
+        };
+        xml.push_str("    <AbilitySpend>\n");
+        push_text(
+            &mut xml,
+            3,
+            "AvailableSpend",
+            &build_type.build_points().to_string(),
+        );
+        for &ability in Ability::ALL.iter() {
+            push_text(
+                &mut xml,
+                3,
+                &format!("{}Spend", short_name(ability)),
+                &stats[ability].to_string(),
+            );
+        }
+        xml.push_str("    </AbilitySpend>\n");
+    }
+    for &ability in Ability::ALL.iter() {
+        push_text(
+            &mut xml,
+            2,
+            &format!("{}Tome", short_name(ability)),
+            &build.stat_tomes[ability].to_string(),
+        );
+    }
+    for (i, levelup) in build.stat_levelups.iter().enumerate() {
+        if let Some(ability) = levelup {
+            push_text(
+                &mut xml,
+                2,
+                &format!("Level{}", (i as u8 + 1) * LEVELUP_INTERVAL),
+                &ability.to_string(),
+            );
+        }
+    }
+
+    // Levels and skills
+    xml.push_str("    <LevelTraining>\n");
+    for (i, &class) in build.levels.iter().enumerate() {
+        xml.push_str("      <TrainedLevel>\n");
+        push_text(
+            &mut xml,
+            4,
+            "Class",
+            &ddob_name(&class_name(content, class)),
+        );
+        for &skill in Skill::ALL.iter() {
+            for _ in 0..build.skills.points_in_skill_at_level(skill, i) {
+                xml.push_str("        <TrainedSkill>");
+                push_inline(&mut xml, "Skill", &ddob_name(&skill.to_string()));
+                xml.push_str("</TrainedSkill>\n");
+            }
+        }
+        xml.push_str("      </TrainedLevel>\n");
+    }
+    xml.push_str("    </LevelTraining>\n");
+    let tomes: Vec<(Skill, u8)> = Skill::ALL
+        .iter()
+        .map(|&s| (s, build.skills.tome(s)))
+        .filter(|&(_, t)| t > 0)
+        .collect();
+    if !tomes.is_empty() {
+        xml.push_str("    <SkillTomes>\n");
+        for (skill, tome) in tomes {
+            xml.push_str("      <SkillTome>");
+            push_inline(&mut xml, "Skill", &ddob_name(&skill.to_string()));
+            push_inline(&mut xml, "Value", &tome.to_string());
+            xml.push_str("</SkillTome>\n");
+        }
+        xml.push_str("    </SkillTomes>\n");
+    }
+
+    // Feats
+    let feats = &build.feats;
+    xml.push_str("    <TrainedFeats>\n");
+    for feat in feats
+        .standard_feats
+        .iter()
+        .chain(feats.class_feats.iter())
+        .chain(feats.race_feats.iter())
+        .chain(feats.legend_feats.iter())
+    {
+        let feat_type = feat_type_name(feat.secondary_type, content);
+        let level = match feat.secondary_type {
+            SecondaryFeatType::Class(c) =>
+                build.character_level_of(c, feat.level),
+            _ => Some(feat.level),
+        };
+        let level = match level {
+            Some(level) => level,
+            None => {
+                unmapped.push(Unmapped::Feat {
+                    name: feat.name.clone(),
+                    feat_type,
+                    level: feat.level.to_string(),
+                });
+                continue;
+            },
+        };
+
+        xml.push_str("      <TrainedFeat>");
+        push_inline(&mut xml, "FeatName", &feat.name);
+        push_inline(&mut xml, "Type", &feat_type);
+        push_inline(&mut xml, "LevelTrainedAt", &(level - 1).to_string());
+        xml.push_str("</TrainedFeat>\n");
+    }
+    xml.push_str("    </TrainedFeats>\n");
+
+    // Spells
+    xml.push_str("    <TrainedSpells>\n");
+    for spellbook in build.spells.spellbooks.iter().flatten() {
+        for (i, spells) in spellbook.spells_by_level.iter().enumerate() {
+            for spell in spells.iter() {
+                xml.push_str("      <TrainedSpell>");
+                push_inline(
+                    &mut xml,
+                    "Class",
+                    &ddob_name(&class_name(content, spellbook.class)),
+                );
+                push_inline(&mut xml, "Level", &(i + 1).to_string());
+                push_inline(
+                    &mut xml,
+                    "SpellName",
+                    spell.as_deref().unwrap_or(""),
+                );
+                xml.push_str("</TrainedSpell>\n");
+            }
+        }
+    }
+    xml.push_str("    </TrainedSpells>\n");
+
+    // Enhancements
+    if let Some(tier_five) = build.tier_five {
+        push_text(
+            &mut xml,
+            2,
+            "Tier5Tree",
+            &ddob_name(&tree_name(content, tier_five)),
+        );
+    }
+    xml.push_str("    <EnhancementTreeSpend>\n");
+    for tree in build.enhancements.trees.iter() {
+        xml.push_str("      <EnhancementSpendInTree>\n");
+        push_text(
+            &mut xml,
+            4,
+            "TreeName",
+            &ddob_name(&tree_name(content, tree.name)),
+        );
+        for (tier, enhancements) in tree.tiers.iter().enumerate() {
+            for enhancement in enhancements.iter() {
+                xml.push_str("        <TrainedEnhancement>");
+                push_inline(&mut xml, "EnhancementName", &enhancement.name);
+                push_inline(&mut xml, "Tier", &tier.to_string());
+                if let Some(sub) = &enhancement.subenhancement {
+                    push_inline(&mut xml, "Selection", sub);
+                }
+                if let Some(rank) = enhancement.rank {
+                    push_inline(&mut xml, "Ranks", &rank.to_string());
+                }
+                xml.push_str("</TrainedEnhancement>\n");
+            }
+        }
+        xml.push_str("      </EnhancementSpendInTree>\n");
+    }
+    xml.push_str("    </EnhancementTreeSpend>\n");
+
+    xml.push_str("  </Character>\n</DDOBuilderCharacterData>\n");
+    out.write_all(xml.as_bytes())?;
+
+    Ok(unmapped)
+}
+
+/// Writes `<name>text</name>` on a line of its own, indented by `depth`
+/// levels.
+fn push_text(xml: &mut String, depth: usize, name: &str, text: &str) {
+    xml.push_str(&"  ".repeat(depth));
+    push_inline(xml, name, text);
+    xml.push('\n');
+}
+
+fn push_inline(xml: &mut String, name: &str, text: &str) {
+    xml.push('<');
+    xml.push_str(name);
+    xml.push('>');
+    xml.push_str(&escape(text));
+    xml.push_str("</");
+    xml.push_str(name);
+    xml.push('>');
+}
+
+/// DDO Builder's name for a race, alignment, class, skill or enhancement
+/// tree: its name without spaces, e.g. "TrueNeutral" for "True Neutral".
+fn ddob_name(name: &str) -> String {
+    name.replace(' ', "")
+}
+
+/// Reads a race by its name, or by DDO Builder's name for it.
+fn read_race(name: &str, content: &Registry) -> Option<Race> {
+    content.race_by_name(name).or_else(|| {
+        content
+            .races()
+            .find(|(_, ri)| ddob_name(&ri.name) == name)
+            .map(|(r, _)| r)
+    })
+}
+
+fn read_alignment(name: &str) -> Option<Alignment> {
+    name.parse().ok().or_else(|| {
+        Alignment::ALL
+            .iter()
+            .copied()
+            .find(|a| ddob_name(&a.to_string()) == name)
+    })
+}
+
+fn read_class(name: &str, content: &Registry) -> Option<Class> {
+    content.class_by_name(name).or_else(|| {
+        content
+            .classes()
+            .find(|(_, ci)| ddob_name(&ci.name) == name)
+            .map(|(c, _)| c)
+    })
+}
+
+fn read_skill(name: &str) -> Option<Skill> {
+    name.parse().ok().or_else(|| {
+        Skill::ALL
+            .iter()
+            .copied()
+            .find(|s| ddob_name(&s.to_string()) == name)
+    })
+}
+
+/// Reads a tree by its name, or by DDO Builder's name for it. A name shared
+/// by several trees is resolved as in `forum::resolve_tree()`.
+fn read_tree(
+    name: &str,
+    levels: &[Class],
+    race: Race,
+    content: &Registry,
+) -> Option<EnhancementTreeName> {
+    resolve_tree(name, levels, race, content).or_else(|| {
+        let (_, ti) = content
+            .trees()
+            .find(|(_, ti)| ddob_name(&ti.name) == name)?;
+
+        resolve_tree(&ti.name, levels, race, content)
+    })
+}
+
+/// The ability's name in element names, e.g. "Str" in `<StrSpend>`.
+fn short_name(ability: Ability) -> &'static str {
+    match ability {
+        Ability::Str => "Str",
+        Ability::Dex => "Dex",
+        Ability::Con => "Con",
+        Ability::Int => "Int",
+        Ability::Wis => "Wis",
+        Ability::Cha => "Cha",
+    }
+}
+
+fn is_tome_element(name: &str) -> bool {
+    Ability::ALL
+        .iter()
+        .any(|&a| name.strip_suffix("Tome") == Some(short_name(a)))
+}
+
+/// The level of an ability score increase element, e.g. 4 for `<Level4>`.
+fn levelup_element_level(name: &str) -> Option<u8> {
+    let level: u8 = name.strip_prefix("Level")?.parse().ok()?;
+
+    if level > 0
+        && level % LEVELUP_INTERVAL == 0
+        && level <= LEVEL_CAP
+    {
+        Some(level)
+    } else {
+        None
+    }
+}
+
+/// A feat's `<Type>`, e.g. "Standard", "Legendary" or "FighterBonus".
+fn feat_type_name(
+    secondary_type: SecondaryFeatType,
+    content: &Registry,
+) -> String {
+    match secondary_type {
+        SecondaryFeatType::Heroic => "Standard".to_owned(),
+        SecondaryFeatType::Epic => "Epic".to_owned(),
+        SecondaryFeatType::Destiny => "Destiny".to_owned(),
+        SecondaryFeatType::Legend => "Legendary".to_owned(),
+        SecondaryFeatType::Class(c) =>
+            format!("{}Bonus", ddob_name(&class_name(content, c))),
+        SecondaryFeatType::Race(r) =>
+            format!("{}Bonus", ddob_name(&race_name(content, r))),
+    }
+}
+
+/// Reads a feat's `<Type>`. A racial bonus feat must be of the build's race.
+fn secondary_feat_type(
+    feat_type: &str,
+    race: Race,
+    content: &Registry,
+) -> Option<SecondaryFeatType> {
+    match feat_type {
+        "Standard" => Some(SecondaryFeatType::Heroic),
+        "Epic" => Some(SecondaryFeatType::Epic),
+        "Destiny" => Some(SecondaryFeatType::Destiny),
+        "Legendary" => Some(SecondaryFeatType::Legend),
+        _ => {
+            let bonus = feat_type.strip_suffix("Bonus")?;
+            let matches = |name: String| ddob_name(&name) == bonus;
+
+            content
+                .classes()
+                .find(|&(c, _)| matches(class_name(content, c)))
+                .map(|(c, _)| SecondaryFeatType::Class(c))
+                .or_else(|| {
+                    Some(SecondaryFeatType::Race(race))
+                        .filter(|_| matches(race_name(content, race)))
+                })
+        },
+    }
+}
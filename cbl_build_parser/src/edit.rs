@@ -0,0 +1,554 @@
+//! Editing a build without breaking it.
+//!
+//! The fields of `CharacterBuild` can be changed directly, but then it's up
+//! to the caller to keep them consistent with each other: the skill points of
+//! each level, the class levels that class feats and enhancement trees refer
+//! to, and so on. The methods here change one thing at a time, carry along
+//! whatever depends on it, and leave the build untouched if the change
+//! would break it: if it can't be carried along, or if the edited build
+//! would break a rule of `validate::validate()` that the build didn't
+//! already break.
+
+use crate::{
+    content::{self, Registry},
+    validate::{self, Violation},
+    Ability,
+    BaseFeatType,
+    BuildType,
+    CharacterBuild,
+    Class,
+    EnhancementTreeName,
+    Feat,
+    SecondaryFeatType,
+    Skill,
+    DESTINY_FEAT_LEVELS,
+    EPIC_FEAT_LEVELS,
+    HEROIC_FEAT_LEVELS,
+    HEROIC_LEVEL_CAP,
+    LEGEND_FEAT_LEVELS,
+};
+use std::{fmt, num::NonZeroU8};
+
+/// The most build points that can be spent on a single ability.
+pub const MAX_STAT_POINTS: u8 = 16;
+/// The most ranks that an enhancement can have when its tree has no catalog
+/// to say otherwise.
+const MAX_ENHANCEMENT_RANK: u8 = 9;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EditError {
+    /// There is no such character level (or, for skills, heroic level).
+    InvalidLevel(u8),
+    TooManyClasses,
+    /// The new levels aren't the build's levels in another order.
+    NotAReordering,
+    /// A class feat would be left at a class level that the build no longer
+    /// has.
+    ClassFeatOutOfRange {
+        feat:        String,
+        class:       Class,
+        class_level: u8,
+    },
+    /// A spellbook would be left for a class that the build no longer has.
+    SpellbookWithoutLevels(Class),
+    /// An enhancement tree would be left without levels of its source.
+    TreeWithoutLevels(EnhancementTreeName),
+    NoSuchFeat(String),
+    /// The level has no feat slot of the feat's kind. Class and racial
+    /// bonus feat slots are only known where the build already has a feat
+    /// of that class or race.
+    NoFeatSlot {
+        feat:  String,
+        level: u8,
+    },
+    BuildTypeDisabled(BuildType),
+    InvalidStatPoints(u8),
+    TooManyStatPoints {
+        build_type: BuildType,
+        total:      u16,
+        max:        u16,
+    },
+    NoSuchEnhancement {
+        tree: EnhancementTreeName,
+        tier: usize,
+        name: String,
+    },
+    /// The rank is 0, or more than the enhancement's `max`.
+    InvalidRank {
+        name: String,
+        rank: u8,
+        max:  u8,
+    },
+    /// The edited build would break a rule.
+    Violation(Violation),
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Self::InvalidLevel(l) => write!(f, "Invalid level: {}", l),
+            Self::TooManyClasses =>
+                f.write_str("More than three classes specified"),
+            Self::NotAReordering => f.write_str(
+                "The new levels aren't a reordering of the build's levels",
+            ),
+            Self::ClassFeatOutOfRange {
+                feat,
+                class,
+                class_level,
+            } => write!(
+                f,
+                "{} is taken at {} level {}, which the build wouldn't have",
+                feat, class, class_level,
+            ),
+            Self::SpellbookWithoutLevels(c) =>
+                write!(f, "The {} spellbook would have no {} levels", c, c),
+            Self::TreeWithoutLevels(t) =>
+                write!(f, "No levels could be spent in {}", t),
+            Self::NoSuchFeat(n) => write!(f, "No such feat: {}", n),
+            Self::NoFeatSlot { feat, level } =>
+                write!(f, "Level {} has no feat slot for {}", level, feat),
+            Self::BuildTypeDisabled(bt) =>
+                write!(f, "The {} build type is disabled", bt),
+            Self::InvalidStatPoints(p) => write!(
+                f,
+                "Invalid build points: {} (at most {} per ability)",
+                p, MAX_STAT_POINTS,
+            ),
+            Self::TooManyStatPoints {
+                build_type,
+                total,
+                max,
+            } => write!(
+                f,
+                "{} build points spent, but a {} build has {}",
+                total, build_type, max,
+            ),
+            Self::NoSuchEnhancement { tree, tier, name } =>
+                write!(f, "{} has no {} in tier {}", tree, name, tier),
+            Self::InvalidRank { name, rank, max } => write!(
+                f,
+                "Invalid rank of {}: {} (it has 1 to {})",
+                name, rank, max,
+            ),
+            Self::Violation(v) => v.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for EditError {}
+
+impl CharacterBuild {
+    /// Swaps the classes taken at two heroic levels, using the game content
+    /// of `content::registry()`.
+    pub fn swap_levels(&mut self, a: u8, b: u8) -> Result<(), EditError> {
+        self.swap_levels_with(a, b, &content::registry())
+    }
+
+    /// Swaps the classes taken at two heroic levels, along with the skill
+    /// points spent at them. Fails if e.g. an iconic race would no longer
+    /// start in its class, or a skill would have too many ranks at a level.
+    pub fn swap_levels_with(
+        &mut self,
+        a: u8,
+        b: u8,
+        content: &Registry,
+    ) -> Result<(), EditError> {
+        let (i, j) = (self.level_index(a)?, self.level_index(b)?);
+
+        let mut edited = self.clone();
+        edited.swap_level_indices(i, j);
+
+        self.commit_edit(edited, content)
+    }
+
+    /// Takes the build's levels in the order of `levels`, using the game
+    /// content of `content::registry()`.
+    pub fn reorder_levels(
+        &mut self,
+        levels: &[Class],
+    ) -> Result<(), EditError> {
+        self.reorder_levels_with(levels, &content::registry())
+    }
+
+    /// Takes the build's levels in the order of `levels`, e.g. one found by
+    /// `order::best_order()`, moving the skill points spent at each level
+    /// along with it. Only the build in its new order has to follow the
+    /// rules, not every step on the way there.
+    pub fn reorder_levels_with(
+        &mut self,
+        levels: &[Class],
+        content: &Registry,
+    ) -> Result<(), EditError> {
+        let count = |ls: &[Class], c| ls.iter().filter(|&&x| x == c).count();
+        if levels.len() != self.levels.len()
+            || levels
+                .iter()
+                .any(|&c| count(levels, c) != count(&self.levels, c))
+        {
+            return Err(EditError::NotAReordering);
+        }
+
+        let mut edited = self.clone();
+        for (i, &class) in levels.iter().enumerate() {
+            if edited.levels[i] != class {
+                let j = (i + 1..edited.levels.len())
+                    .find(|&j| edited.levels[j] == class)
+                    .unwrap();
+                edited.swap_level_indices(i, j);
+            }
+        }
+
+        self.commit_edit(edited, content)
+    }
+
+    /// Changes the class taken at a heroic level, using the game content of
+    /// `content::registry()`.
+    pub fn set_class_at(
+        &mut self,
+        level: u8,
+        class: Class,
+    ) -> Result<(), EditError> {
+        self.set_class_at_with(level, class, &content::registry())
+    }
+
+    /// Changes the class taken at a heroic level. Fails if this would leave
+    /// class feats, spells or enhancement trees of the former class without
+    /// the levels that they need, or would break a rule, e.g. by locking
+    /// spells or enhancement tiers, by taking a class that the alignment
+    /// forbids, or by leaving more skill points or ranks at a level than it
+    /// allows. Otherwise, the class levels of enhancement trees are updated.
+    pub fn set_class_at_with(
+        &mut self,
+        level: u8,
+        class: Class,
+        content: &Registry,
+    ) -> Result<(), EditError> {
+        let i = self.level_index(level)?;
+        let mut levels = self.levels.clone();
+        let former = std::mem::replace(&mut levels[i], class);
+        if former == class {
+            return Ok(());
+        }
+
+        let mut classes: Vec<Class> = Vec::with_capacity(4);
+        for &c in levels.iter() {
+            if !classes.contains(&c) {
+                classes.push(c);
+            }
+        }
+        if classes.len() > 3 {
+            return Err(EditError::TooManyClasses);
+        }
+
+        let count = |c| levels.iter().filter(|&&l| l == c).count() as u8;
+        let former_levels = count(former);
+        if let Some(feat) = self.feats.class_feats.iter().find(|f| {
+            f.secondary_type == SecondaryFeatType::Class(former)
+                && f.level > former_levels
+        }) {
+            return Err(EditError::ClassFeatOutOfRange {
+                feat:        feat.name.clone(),
+                class:       former,
+                class_level: feat.level,
+            });
+        }
+        if former_levels == 0 {
+            let has_spellbook =
+                self.spells.spellbooks.iter().flatten().any(|sb| {
+                    sb.class == former && !sb.spells_by_level.is_empty()
+                });
+            if has_spellbook {
+                return Err(EditError::SpellbookWithoutLevels(former));
+            }
+            if let Some(et) = self
+                .enhancements
+                .trees
+                .iter()
+                .find(|et| et.source == Some(former))
+            {
+                return Err(EditError::TreeWithoutLevels(et.name));
+            }
+        }
+
+        let mut edited = self.clone();
+        for et in edited.enhancements.trees.iter_mut() {
+            if let Some(source) = et.source {
+                et.class_levels = Some(count(source));
+            }
+        }
+        edited.levels = levels;
+
+        self.commit_edit(edited, content)
+    }
+
+    /// Moves the feat named `name` to the feat slot of the same kind at
+    /// `level`, swapping it with the feat already there, if any. For class
+    /// feats, `level` is a level of the class, as in `Feat::level`. Fails if
+    /// `level` has no slot of that kind (see `EditError::NoFeatSlot`).
+    pub fn move_feat(
+        &mut self,
+        name: &str,
+        level: u8,
+    ) -> Result<(), EditError> {
+        let (base_type, i) = self
+            .find_feat(name)
+            .ok_or_else(|| EditError::NoSuchFeat(name.to_owned()))?;
+        let secondary_type = self.feats_of(base_type)[i].secondary_type;
+
+        let max_level = match secondary_type {
+            SecondaryFeatType::Class(c) =>
+                self.levels.iter().filter(|&&l| l == c).count() as u8,
+            _ => self.max_levels,
+        };
+        if level == 0 || level > max_level {
+            return Err(EditError::InvalidLevel(level));
+        }
+
+        let has_slot = match secondary_type {
+            SecondaryFeatType::Heroic => HEROIC_FEAT_LEVELS.contains(&level),
+            SecondaryFeatType::Epic => EPIC_FEAT_LEVELS.contains(&level),
+            SecondaryFeatType::Destiny => DESTINY_FEAT_LEVELS.contains(&level),
+            SecondaryFeatType::Legend => LEGEND_FEAT_LEVELS.contains(&level),
+            // The registry doesn't say which levels grant bonus feats
+            SecondaryFeatType::Class(_) | SecondaryFeatType::Race(_) =>
+                self.feats_of(base_type).iter().any(|f| {
+                    f.secondary_type == secondary_type && f.level == level
+                }),
+        };
+        if !has_slot {
+            return Err(EditError::NoFeatSlot {
+                feat: name.to_owned(),
+                level,
+            });
+        }
+
+        let feats = self.feats_of_mut(base_type);
+        let former_level = feats[i].level;
+        if let Some(other) = feats
+            .iter_mut()
+            .find(|f| f.secondary_type == secondary_type && f.level == level)
+        {
+            other.level = former_level;
+        }
+        feats[i].level = level;
+
+        Ok(())
+    }
+
+    /// Sets the build points spent on an ability, for a build type that
+    /// isn't disabled. The total may not exceed the build type's points.
+    pub fn set_stat_points(
+        &mut self,
+        build_type: BuildType,
+        ability: Ability,
+        points: u8,
+    ) -> Result<(), EditError> {
+        if points > MAX_STAT_POINTS {
+            return Err(EditError::InvalidStatPoints(points));
+        }
+        let stats = match build_type {
+            BuildType::Adventurer => self.adventurer_stats.as_mut(),
+            BuildType::Champion => self.champion_stats.as_mut(),
+            BuildType::Hero => self.hero_stats.as_mut(),
+            BuildType::Legend => self.legend_stats.as_mut(),
+        }
+        .ok_or(EditError::BuildTypeDisabled(build_type))?;
+
+        let total: u16 = Ability::ALL
+            .iter()
+            .filter(|&&a| a != ability)
+            .map(|&a| u16::from(stats[a]))
+            .sum::<u16>()
+            + u16::from(points);
+        if total > build_type.build_points() {
+            return Err(EditError::TooManyStatPoints {
+                build_type,
+                total,
+                max: build_type.build_points(),
+            });
+        }
+        stats[ability] = points;
+
+        Ok(())
+    }
+
+    /// Sets the skill points spent on a skill at a heroic level, using the
+    /// class skills of `content::registry()`.
+    pub fn set_skill_points(
+        &mut self,
+        skill: Skill,
+        level: u8,
+        points: u8,
+    ) -> Result<(), EditError> {
+        self.set_skill_points_with(skill, level, points, &content::registry())
+    }
+
+    /// Sets the skill points spent on a skill at a heroic level. Fails if
+    /// more points would be spent at the level than it grants, or if the
+    /// skill would have more ranks than allowed at that level or any later
+    /// one: the character level plus 3, or half that if it isn't a class
+    /// skill of any class taken so far.
+    pub fn set_skill_points_with(
+        &mut self,
+        skill: Skill,
+        level: u8,
+        points: u8,
+        content: &Registry,
+    ) -> Result<(), EditError> {
+        let i = self.level_index(level)?;
+        let mut edited = self.clone();
+        edited
+            .skills
+            .skill_table
+            .entry(skill)
+            .or_insert([0; HEROIC_LEVEL_CAP as usize]);
+        // Drops the skill's entry if no points are left in it.
+        edited.set_skill_table_points(skill, i, points);
+
+        self.commit_edit(edited, content)
+    }
+
+    /// Sets the rank of an enhancement that was taken, using the catalog of
+    /// `content::registry()`.
+    pub fn set_enhancement_rank(
+        &mut self,
+        tree: EnhancementTreeName,
+        tier: usize,
+        name: &str,
+        rank: u8,
+    ) -> Result<(), EditError> {
+        self.set_enhancement_rank_with(
+            tree,
+            tier,
+            name,
+            rank,
+            &content::registry(),
+        )
+    }
+
+    /// Sets the rank of an enhancement that was taken. The rank must be at
+    /// least 1, and no more than the enhancement's ranks in the tree's
+    /// catalog, if it has one.
+    pub fn set_enhancement_rank_with(
+        &mut self,
+        tree: EnhancementTreeName,
+        tier: usize,
+        name: &str,
+        rank: u8,
+        content: &Registry,
+    ) -> Result<(), EditError> {
+        let no_such_enhancement = || EditError::NoSuchEnhancement {
+            tree,
+            tier,
+            name: name.to_owned(),
+        };
+        let enhancement = self
+            .enhancements
+            .get_mut(tree)
+            .and_then(|et| et.tiers.get_mut(tier))
+            .and_then(|es| es.iter_mut().find(|e| e.name == name))
+            .ok_or_else(no_such_enhancement)?;
+
+        let max = content
+            .enhancement(tree, tier, name)
+            .map_or(MAX_ENHANCEMENT_RANK, |ei| ei.ranks);
+        if rank > max {
+            return Err(EditError::InvalidRank {
+                name: name.to_owned(),
+                rank,
+                max,
+            });
+        }
+        enhancement.rank = Some(NonZeroU8::new(rank).ok_or_else(|| {
+            EditError::InvalidRank {
+                name: name.to_owned(),
+                rank,
+                max,
+            }
+        })?);
+
+        Ok(())
+    }
+
+    /// Replaces the build with `edited`, unless `edited` breaks a rule that
+    /// the build doesn't already break.
+    fn commit_edit(
+        &mut self,
+        edited: CharacterBuild,
+        content: &Registry,
+    ) -> Result<(), EditError> {
+        let broken = validate::validate_with(self, content);
+        if let Some(v) = validate::validate_with(&edited, content)
+            .into_iter()
+            .find(|v| !broken.contains(v))
+        {
+            return Err(EditError::Violation(v));
+        }
+        *self = edited;
+
+        Ok(())
+    }
+
+    /// Swaps the classes taken at two indices into `levels`, along with the
+    /// skill points spent at them.
+    fn swap_level_indices(&mut self, i: usize, j: usize) {
+        self.levels.swap(i, j);
+        for points in self.skills.skill_table.values_mut() {
+            points.swap(i, j);
+        }
+    }
+
+    /// The index into `levels` of a heroic level.
+    fn level_index(&self, level: u8) -> Result<usize, EditError> {
+        if level == 0 || usize::from(level) > self.levels.len() {
+            return Err(EditError::InvalidLevel(level));
+        }
+
+        Ok(usize::from(level - 1))
+    }
+
+    fn set_skill_table_points(&mut self, skill: Skill, i: usize, points: u8) {
+        let table = &mut self.skills.skill_table;
+        if let Some(ps) = table.get_mut(&skill) {
+            ps[i] = points;
+            if ps.iter().all(|&p| p == 0) {
+                table.remove(&skill);
+            }
+        }
+    }
+
+    fn find_feat(&self, name: &str) -> Option<(BaseFeatType, usize)> {
+        [
+            BaseFeatType::Standard,
+            BaseFeatType::Legend,
+            BaseFeatType::Class,
+            BaseFeatType::Race,
+        ]
+        .iter()
+        .find_map(|&bt| {
+            self.feats_of(bt)
+                .iter()
+                .position(|f| f.name == name)
+                .map(|i| (bt, i))
+        })
+    }
+
+    fn feats_of(&self, base_type: BaseFeatType) -> &Vec<Feat> {
+        match base_type {
+            BaseFeatType::Standard => &self.feats.standard_feats,
+            BaseFeatType::Legend => &self.feats.legend_feats,
+            BaseFeatType::Class => &self.feats.class_feats,
+            BaseFeatType::Race => &self.feats.race_feats,
+        }
+    }
+
+    fn feats_of_mut(&mut self, base_type: BaseFeatType) -> &mut Vec<Feat> {
+        match base_type {
+            BaseFeatType::Standard => &mut self.feats.standard_feats,
+            BaseFeatType::Legend => &mut self.feats.legend_feats,
+            BaseFeatType::Class => &mut self.feats.class_feats,
+            BaseFeatType::Race => &mut self.feats.race_feats,
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! Canonical build filenames, which sum up a build so that an archive of
+//! them sorts and reads well, e.g.
+//!
+//! ```text
+//! brd15_ftr4_rog1_-_drw_-_trapper_-_WC_StD_-_THFs_none_BPlat_-_Wartrapper.build
+//! ```
+//!
+//! A filename is made of six parts, separated by `_-_`:
+//!
+//! 1. The class split, most levels first, e.g. `brd15_ftr4_rog1`.
+//! 2. The race, e.g. `drw`.
+//! 3. The role that the build is made for, e.g. `trapper`.
+//! 4. The tier 5 tree, followed by the other key trees, e.g. `WC_StD`.
+//! 5. Tags for whatever else the build is known by, such as its weapons and
+//!    armor, e.g. `THFs_none_BPlat`.
+//! 6. The name of the build, with `_` for spaces.
+//!
+//! Classes, races and trees are written as their abbreviations in the
+//! content registry. A build file says nothing about roles or tags, so they
+//! are up to the caller; a part that is left empty is written as `none`.
+
+use crate::{
+    content::{self, Registry},
+    CharacterBuild,
+    Class,
+    EnhancementTreeName,
+    Race,
+};
+use std::fmt;
+
+/// Written for a part of a filename that is left empty.
+pub const NONE: &str = "none";
+/// The extension of build files.
+pub const EXTENSION: &str = ".build";
+/// Separates the parts of a filename.
+const PART_SEPARATOR: &str = "_-_";
+/// The lowest tier that makes a tree (other than the tier 5 tree) a key tree.
+const KEY_TREE_TIER: usize = 3;
+
+/// The parts of a canonical build filename.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BuildFilename {
+    /// The number of levels in each class, most levels first. Classes with
+    /// as many levels as each other are in the order that they were first
+    /// taken.
+    pub split: Vec<(Class, u8)>,
+    pub race:  Race,
+    pub role:  Option<String>,
+    /// The tier 5 tree, if any, followed by every other tree that has
+    /// enhancements taken in tier 3 or above, in the order that they were
+    /// declared.
+    pub trees: Vec<EnhancementTreeName>,
+    pub tags:  Vec<String>,
+    pub name:  String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FilenameError {
+    /// The filename doesn't have the six parts of a canonical filename; the
+    /// number of parts that it has.
+    PartCount(usize),
+    /// A class in the split isn't written as an abbreviation followed by a
+    /// number of levels.
+    MalformedClass(String),
+    UnknownClass(String),
+    UnknownRace(String),
+    UnknownTree(String),
+}
+
+impl BuildFilename {
+    /// Sums up `build`, without a role or tags.
+    pub fn new(build: &CharacterBuild) -> Self {
+        let key_trees = build.enhancements.trees.iter().filter(|et| {
+            Some(et.name) != build.tier_five
+                && et.tiers[KEY_TREE_TIER..].iter().any(|t| !t.is_empty())
+        });
+        let trees = build
+            .tier_five
+            .into_iter()
+            .chain(key_trees.map(|et| et.name))
+            .collect();
+
+        Self {
+            split: build.class_split(),
+            race: build.race,
+            role: None,
+            trees,
+            tags: Vec::new(),
+            name: build.name.clone(),
+        }
+    }
+
+    /// Writes the filename, with the abbreviations of
+    /// `content::registry()`.
+    pub fn filename(&self) -> String {
+        self.filename_with(&content::registry())
+    }
+
+    /// Writes the filename, with the abbreviations of `content`. Anything
+    /// in the role, tags or name that can't be written in a filename is left
+    /// out, and whitespace is written as `_`.
+    pub fn filename_with(&self, content: &Registry) -> String {
+        let split = self
+            .split
+            .iter()
+            .map(|&(class, n)| {
+                let abbreviation = content.class(class).map_or_else(
+                    || format!("{:?}", class),
+                    |ci| ci.abbreviation.clone(),
+                );
+
+                format!("{}{}", abbreviation, n)
+            })
+            .collect::<Vec<_>>();
+        let race = content.race(self.race).map_or_else(
+            || format!("{:?}", self.race),
+            |ri| ri.abbreviation.clone(),
+        );
+        let role = self.role.as_deref().map(sanitize);
+        let trees = self
+            .trees
+            .iter()
+            .map(|&t| {
+                content.tree(t).map_or_else(
+                    || format!("{:?}", t),
+                    |ti| ti.abbreviation.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let tags = self.tags.iter().map(|t| sanitize(t)).collect::<Vec<_>>();
+
+        let parts = [
+            split.join("_"),
+            race,
+            role.unwrap_or_default(),
+            trees.join("_"),
+            tags.join("_"),
+            sanitize(&self.name),
+        ];
+        let mut filename = parts
+            .iter()
+            .map(|p| if p.is_empty() { NONE } else { p })
+            .collect::<Vec<_>>()
+            .join(PART_SEPARATOR);
+        filename.push_str(EXTENSION);
+
+        filename
+    }
+
+    /// Reads the parts of a canonical filename, with the abbreviations of
+    /// `content`. The filename may be given with or without its extension,
+    /// but without any directories. Spaces in the name can't be told apart
+    /// from `_`, so `_` is read as a space.
+    pub fn parse_with(
+        filename: &str,
+        content: &Registry,
+    ) -> Result<Self, FilenameError> {
+        let filename = filename.strip_suffix(EXTENSION).unwrap_or(filename);
+        // The name is last, so it is free to contain the separator
+        let parts = filename.splitn(6, PART_SEPARATOR).collect::<Vec<_>>();
+        if parts.len() != 6 {
+            return Err(FilenameError::PartCount(parts.len()));
+        }
+
+        let split = optional(parts[0])
+            .into_iter()
+            .flat_map(|p| p.split('_'))
+            .map(|token| {
+                let digits = token
+                    .rfind(|c: char| !c.is_ascii_digit())
+                    .map_or(0, |i| i + 1);
+                let (abbreviation, n) = token.split_at(digits);
+                let n = n
+                    .parse()
+                    .ok()
+                    .filter(|_| !abbreviation.is_empty())
+                    .ok_or_else(|| {
+                        FilenameError::MalformedClass(token.to_owned())
+                    })?;
+                let class = content
+                    .class_by_abbreviation(abbreviation)
+                    .ok_or_else(|| {
+                        FilenameError::UnknownClass(abbreviation.to_owned())
+                    })?;
+
+                Ok((class, n))
+            })
+            .collect::<Result<_, _>>()?;
+        let race = content
+            .race_by_abbreviation(parts[1])
+            .ok_or_else(|| FilenameError::UnknownRace(parts[1].to_owned()))?;
+        let trees = optional(parts[3])
+            .into_iter()
+            .flat_map(|p| p.split('_'))
+            .map(|t| {
+                content
+                    .tree_by_abbreviation(t)
+                    .ok_or_else(|| FilenameError::UnknownTree(t.to_owned()))
+            })
+            .collect::<Result<_, _>>()?;
+        let tags = optional(parts[4])
+            .into_iter()
+            .flat_map(|p| p.split('_'))
+            .map(str::to_owned)
+            .collect();
+
+        Ok(Self {
+            split,
+            race,
+            role: optional(parts[2]).map(str::to_owned),
+            trees,
+            tags,
+            name: optional(parts[5]).unwrap_or("").replace('_', " "),
+        })
+    }
+}
+
+impl std::str::FromStr for BuildFilename {
+    type Err = FilenameError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_with(s, &content::registry())
+    }
+}
+
+impl fmt::Display for BuildFilename {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.write_str(&self.filename())
+    }
+}
+
+impl fmt::Display for FilenameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Self::PartCount(n) => write!(
+                f,
+                "Not a canonical build filename: {} parts instead of 6",
+                n,
+            ),
+            Self::MalformedClass(c) =>
+                write!(f, "Not a class and its levels: {}", c),
+            Self::UnknownClass(c) =>
+                write!(f, "Unknown class abbreviation: {}", c),
+            Self::UnknownRace(r) =>
+                write!(f, "Unknown race abbreviation: {}", r),
+            Self::UnknownTree(t) =>
+                write!(f, "Unknown enhancement tree abbreviation: {}", t),
+        }
+    }
+}
+
+impl std::error::Error for FilenameError {}
+
+/// `None` for a part that was left empty.
+fn optional(part: &str) -> Option<&str> {
+    Some(part).filter(|&p| p != NONE)
+}
+
+/// `s` as it can be written in one part of a filename: whitespace becomes
+/// `_`, and characters that some file systems don't allow are left out.
+fn sanitize(s: &str) -> String {
+    s.split_whitespace()
+        .collect::<Vec<_>>()
+        .join("_")
+        .chars()
+        .filter(|c| {
+            !matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|')
+        })
+        .collect()
+}
@@ -0,0 +1,723 @@
+//! Importing builds from CBL's plain-text forum export.
+//!
+//! Many builds only survive as forum posts, so `import()` reads the exported
+//! text back into a `CharacterBuild`. The export looks like this (sections
+//! other than the header may be missing or in any order):
+//!
+//! ```text
+//! Wartrapper
+//! Level 20 True Neutral Drow
+//! (15 Bard / 4 Fighter / 1 Rogue)
+//!
+//! Abilities     28pt
+//! Strength        16
+//! ...
+//!
+//! Level Ups
+//!  4: Strength
+//!
+//! Level Order
+//!  1. Rogue
+//!  2. Bard
+//!
+//! Feats
+//!  1: Power Attack
+//!  3: Cleave (Fighter bonus feat)
+//!
+//! Spells
+//! Bard 1: Cure Light Wounds, Remove Fear
+//!
+//! Enhancements (80 AP)
+//! Warchanter (44 AP)
+//!   Cores: Skaldic: Constitution, Weapon Training
+//!   Tier 1: Poetic Edda III
+//! ```
+//!
+//! The export never has skill points, and may have lines that don't fit the
+//! layout. Whatever couldn't be carried over into the build is reported as
+//! an `Unrecovered`.
+
+use crate::{
+    content::{self, Registry},
+    order,
+    snapshot::{BASE_ABILITY_SCORE, SCORE_COSTS},
+    Ability,
+    Alignment,
+    BuildType,
+    CharacterBuild,
+    Class,
+    Enhancement,
+    EnhancementTreeName,
+    Enhancements,
+    Feat,
+    Feats,
+    Race,
+    SecondaryFeatType,
+    Skills,
+    Spells,
+    Stats,
+    HEROIC_LEVEL_CAP,
+    LEVELUP_COUNT,
+    LEVELUP_INTERVAL,
+    LEVEL_CAP,
+};
+use regex::Regex;
+use std::{
+    fmt,
+    io::{self, prelude::*},
+    num::NonZeroU8,
+};
+
+/// Why an export couldn't be imported at all.
+#[derive(Debug)]
+pub enum ImportError {
+    IoError(io::Error),
+    /// The export doesn't start with a name and a "Level 20 True Neutral
+    /// Drow" line.
+    NoHeader,
+    UnknownRace(String),
+    UnknownAlignment(String),
+    /// Neither a level order nor a class split that levels could be put in
+    /// order from.
+    NoLevels,
+}
+
+/// Something in the export that couldn't be carried over into the build.
+/// Line numbers start at 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Unrecovered {
+    /// A line that doesn't fit the layout of the export.
+    UnknownLine(usize, String),
+    UnknownClass(usize, String),
+    UnknownAbility(usize, String),
+    UnknownTree(usize, String),
+    /// An ability score that no number of build points buys for the race.
+    /// The closest score that can be bought was used instead.
+    UnbuyableScore {
+        line:    usize,
+        ability: Ability,
+        score:   u8,
+    },
+    /// A feat whose slot couldn't be worked out, e.g. a bonus feat of a
+    /// class that hasn't been taken by the feat's level.
+    FeatSlot(usize, String),
+    /// The level order doesn't have as many levels as the header says. The
+    /// build's levels are cut down to those of the level order.
+    LevelCount {
+        expected: u8,
+        found:    usize,
+    },
+    /// There was no level order, so the levels of the class split were put
+    /// in the best order that `order::best_order()` could find.
+    LevelOrder,
+    /// There was no ability table, so every build type is left at 0 points.
+    Abilities,
+    /// The export never has skill points.
+    Skills,
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Self::IoError(ioe) => ioe.fmt(f),
+            Self::NoHeader => f.write_str(
+                "No header; expected a name followed by e.g. \"Level 20 True \
+                 Neutral Drow\"",
+            ),
+            Self::UnknownRace(r) => write!(f, "Unknown race: {}", r),
+            Self::UnknownAlignment(a) => write!(f, "Unknown alignment: {}", a),
+            Self::NoLevels =>
+                f.write_str("Neither a level order nor a class split"),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+impl std::convert::From<io::Error> for ImportError {
+    fn from(ioe: io::Error) -> Self {
+        Self::IoError(ioe)
+    }
+}
+
+impl fmt::Display for Unrecovered {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Self::UnknownLine(l, s) =>
+                write!(f, "line {}: Not understood: {}", l, s),
+            Self::UnknownClass(l, c) =>
+                write!(f, "line {}: Unknown class: {}", l, c),
+            Self::UnknownAbility(l, a) =>
+                write!(f, "line {}: Unknown ability: {}", l, a),
+            Self::UnknownTree(l, t) =>
+                write!(f, "line {}: Unknown enhancement tree: {}", l, t),
+            Self::UnbuyableScore {
+                line,
+                ability,
+                score,
+            } => write!(
+                f,
+                "line {}: {} {} can't be bought with build points",
+                line, ability, score,
+            ),
+            Self::FeatSlot(l, feat) =>
+                write!(f, "line {}: No feat slot for {}", l, feat),
+            Self::LevelCount { expected, found } => write!(
+                f,
+                "The level order has {} levels instead of {}",
+                found, expected,
+            ),
+            Self::LevelOrder => f.write_str(
+                "No level order; the levels of the class split were put in \
+                 order",
+            ),
+            Self::Abilities => f.write_str("No ability table"),
+            Self::Skills => f.write_str("Skill points aren't in the export"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Section {
+    Header,
+    Abilities,
+    LevelUps,
+    LevelOrder,
+    Feats,
+    Spells,
+    Enhancements,
+}
+
+/// A feat line, kept until the level order is known.
+struct FeatLine {
+    line:  usize,
+    level: u8,
+    name:  String,
+    label: Option<String>,
+}
+
+/// A tree and its enhancements, kept until the level order is known.
+struct TreeLines {
+    line:  usize,
+    name:  String,
+    tiers: Vec<(usize, Vec<Enhancement>)>,
+}
+
+/// Reads a forum export using the game content of `content::registry()`.
+pub fn import<R: BufRead>(
+    input: &mut R,
+) -> Result<(CharacterBuild, Vec<Unrecovered>), ImportError> {
+    import_with(input, &content::registry())
+}
+
+/// Reads a forum export, looking up the names of races, classes and
+/// enhancement trees in `content`. Returns the build along with everything
+/// that couldn't be carried over into it, in the order that it was found.
+pub fn import_with<R: BufRead>(
+    input: &mut R,
+    content: &Registry,
+) -> Result<(CharacterBuild, Vec<Unrecovered>), ImportError> {
+    lazy_static! {
+        static ref LEVEL_RE: Regex =
+            Regex::new(r"^Level\s+([0-9]{1,2})\s+(.+)$").unwrap();
+        static ref SPLIT_RE: Regex = Regex::new(r"^\((.+)\)$").unwrap();
+        static ref SPLIT_PART_RE: Regex =
+            Regex::new(r"^([0-9]{1,2})\s+(.+)$").unwrap();
+        static ref SECTION_RE: Regex = Regex::new(
+            r"^(Abilities|Level Ups|Level Order|Feats|Spells|Enhancements)\b\s*(.*)$"
+        ).unwrap();
+        static ref SCORES_RE: Regex =
+            Regex::new(r"^([A-Za-z]+)((\s+[0-9]{1,2})+)$").unwrap();
+        static ref NUMBERED_RE: Regex =
+            Regex::new(r"^([0-9]{1,2})[.):]\s+(.+)$").unwrap();
+        static ref FEAT_LABEL_RE: Regex =
+            Regex::new(r"^(.+) \((epic|destiny|legendary|(.+) bonus feat)\)$")
+                .unwrap();
+        static ref SPELLS_RE: Regex =
+            Regex::new(r"^([A-Za-z ]+) ([1-9]):\s*(.*)$").unwrap();
+        static ref TIER_RE: Regex =
+            Regex::new(r"^(Cores|Tier ([1-5])):\s*(.*)$").unwrap();
+        static ref TREE_RE: Regex =
+            Regex::new(r"^(.+?)(\s+\([0-9]+ AP\))?:?$").unwrap();
+        static ref RANK_RE: Regex =
+            Regex::new(r"^(.+) ([IVX]+)$").unwrap();
+    }
+
+    let mut unrecovered = Vec::new();
+
+    let mut name = None;
+    let mut race = None;
+    let mut alignment = None;
+    let mut max_levels = HEROIC_LEVEL_CAP;
+    let mut split: Vec<(Class, u8)> = Vec::new();
+
+    let mut columns: Vec<BuildType> = Vec::new();
+    let mut scores: Vec<(usize, Ability, Vec<u8>)> = Vec::new();
+    let mut stat_levelups = [None; LEVELUP_COUNT];
+    let mut levels: Vec<Class> = Vec::with_capacity(HEROIC_LEVEL_CAP.into());
+    let mut level_order_seen = false;
+    let mut feat_lines: Vec<FeatLine> = Vec::new();
+    let mut spells = Spells::default();
+    let mut tree_lines: Vec<TreeLines> = Vec::new();
+
+    let mut section = Section::Header;
+
+    for (i, line) in input.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        let line_num = i + 1;
+        let not_understood =
+            || Unrecovered::UnknownLine(line_num, line.to_owned());
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(section_caps) = SECTION_RE.captures(line) {
+            if name.is_some() && race.is_some() {
+                section = match &section_caps[1] {
+                    "Abilities" => Section::Abilities,
+                    "Level Ups" => Section::LevelUps,
+                    "Level Order" => Section::LevelOrder,
+                    "Feats" => Section::Feats,
+                    "Spells" => Section::Spells,
+                    _ => Section::Enhancements,
+                };
+
+                if section == Section::Abilities {
+                    for column in section_caps[2].split_whitespace() {
+                        let build_type =
+                            BuildType::ALL.iter().copied().find(|bt| {
+                                column.parse() == Ok(*bt)
+                                    || column
+                                        .strip_suffix("pt")
+                                        .and_then(|p| p.parse().ok())
+                                        == Some(bt.build_points())
+                            });
+                        match build_type {
+                            Some(bt) => columns.push(bt),
+                            None => unrecovered.push(not_understood()),
+                        }
+                    }
+                }
+                if section == Section::LevelOrder {
+                    level_order_seen = true;
+                }
+
+                continue;
+            }
+        }
+
+        match section {
+            Section::Header =>
+                if name.is_none() {
+                    name = Some(line.to_owned());
+                } else if race.is_none() {
+                    let level_caps = LEVEL_RE
+                        .captures(line)
+                        .ok_or(ImportError::NoHeader)?;
+                    max_levels = level_caps[1].parse().unwrap();
+                    if !(1..=LEVEL_CAP).contains(&max_levels) {
+                        return Err(ImportError::NoHeader);
+                    }
+
+                    let (a, r) =
+                        split_alignment(&level_caps[2]).ok_or_else(|| {
+                            ImportError::UnknownAlignment(
+                                level_caps[2].to_owned(),
+                            )
+                        })?;
+                    alignment = Some(a);
+                    race = Some(content.race_by_name(r).ok_or_else(|| {
+                        ImportError::UnknownRace(r.to_owned())
+                    })?);
+                } else if let Some(split_caps) = SPLIT_RE.captures(line) {
+                    for part in split_caps[1].split('/') {
+                        let part_caps =
+                            match SPLIT_PART_RE.captures(part.trim()) {
+                                Some(caps) => caps,
+                                None => {
+                                    unrecovered.push(not_understood());
+                                    continue;
+                                },
+                            };
+                        match content.class_by_name(&part_caps[2]) {
+                            Some(class) => split
+                                .push((class, part_caps[1].parse().unwrap())),
+                            None =>
+                                unrecovered.push(Unrecovered::UnknownClass(
+                                    line_num,
+                                    part_caps[2].to_owned(),
+                                )),
+                        }
+                    }
+                } else {
+                    unrecovered.push(not_understood());
+                },
+            Section::Abilities =>
+                if let Some(scores_caps) = SCORES_RE.captures(line) {
+                    match scores_caps[1].parse::<Ability>() {
+                        Ok(ability) => scores.push((
+                            line_num,
+                            ability,
+                            scores_caps[2]
+                                .split_whitespace()
+                                .map(|s| s.parse().unwrap())
+                                .collect(),
+                        )),
+                        Err(()) =>
+                            unrecovered.push(Unrecovered::UnknownAbility(
+                                line_num,
+                                scores_caps[1].to_owned(),
+                            )),
+                    }
+                } else {
+                    unrecovered.push(not_understood());
+                },
+            Section::LevelUps => {
+                let levelup = NUMBERED_RE.captures(line).and_then(|caps| {
+                    let level: u8 = caps[1].parse().unwrap();
+                    let ability = caps[2].parse::<Ability>().ok()?;
+
+                    if level == 0
+                        || level % LEVELUP_INTERVAL != 0
+                        || level > LEVEL_CAP
+                    {
+                        None
+                    } else {
+                        Some((level, ability))
+                    }
+                });
+                match levelup {
+                    Some((level, ability)) => {
+                        stat_levelups
+                            [usize::from(level / LEVELUP_INTERVAL - 1)] =
+                            Some(ability);
+                    },
+                    None => unrecovered.push(not_understood()),
+                }
+            },
+            Section::LevelOrder => match NUMBERED_RE.captures(line) {
+                Some(caps) if caps[1].parse() == Ok(levels.len() + 1) =>
+                    match content.class_by_name(&caps[2]) {
+                        Some(class) => levels.push(class),
+                        None => unrecovered.push(Unrecovered::UnknownClass(
+                            line_num,
+                            caps[2].to_owned(),
+                        )),
+                    },
+                _ => unrecovered.push(not_understood()),
+            },
+            Section::Feats => match NUMBERED_RE.captures(line) {
+                Some(caps) => {
+                    let (name, label) = match FEAT_LABEL_RE.captures(&caps[2])
+                    {
+                        Some(label_caps) => (
+                            label_caps[1].to_owned(),
+                            Some(label_caps[2].to_owned()),
+                        ),
+                        None => (caps[2].to_owned(), None),
+                    };
+
+                    feat_lines.push(FeatLine {
+                        line: line_num,
+                        level: caps[1].parse().unwrap(),
+                        name,
+                        label,
+                    });
+                },
+                None => unrecovered.push(not_understood()),
+            },
+            Section::Spells => {
+                let spells_caps = match SPELLS_RE.captures(line) {
+                    Some(caps) => caps,
+                    None => {
+                        unrecovered.push(not_understood());
+                        continue;
+                    },
+                };
+                let class = match content.class_by_name(&spells_caps[1]) {
+                    Some(class) => class,
+                    None => {
+                        unrecovered.push(Unrecovered::UnknownClass(
+                            line_num,
+                            spells_caps[1].to_owned(),
+                        ));
+                        continue;
+                    },
+                };
+                let spell_level = spells_caps[2].parse().unwrap();
+
+                for spell in spells_caps[3].split(',').map(str::trim) {
+                    let spell =
+                        Some(spell.to_owned()).filter(|s| !s.is_empty());
+                    if spells.insert(class, spell_level, spell).is_err() {
+                        unrecovered.push(not_understood());
+                        break;
+                    }
+                }
+            },
+            Section::Enhancements =>
+                if let Some(tier_caps) = TIER_RE.captures(line) {
+                    let tree = match tree_lines.last_mut() {
+                        Some(tree) => tree,
+                        None => {
+                            unrecovered.push(not_understood());
+                            continue;
+                        },
+                    };
+                    let tier = tier_caps
+                        .get(2)
+                        .map_or(0, |t| t.as_str().parse().unwrap());
+                    let enhancements = tier_caps[3]
+                        .split(", ")
+                        .map(str::trim)
+                        .filter(|e| !e.is_empty())
+                        .map(|e| {
+                            let (e, rank) = match RANK_RE.captures(e) {
+                                Some(rank_caps) =>
+                                    match roman_value(&rank_caps[2]) {
+                                        Some(rank) => (
+                                            rank_caps.get(1).unwrap().as_str(),
+                                            Some(rank),
+                                        ),
+                                        None => (e, None),
+                                    },
+                                None => (e, None),
+                            };
+                            let (name, sub) = match e.find(": ") {
+                                Some(i) =>
+                                    (&e[..i], Some(e[i + 2..].to_owned())),
+                                None => (e, None),
+                            };
+
+                            Enhancement::new(name.to_owned(), sub, rank)
+                        })
+                        .collect();
+
+                    tree.tiers.push((tier, enhancements));
+                } else {
+                    let tree_caps = TREE_RE.captures(line).unwrap();
+                    tree_lines.push(TreeLines {
+                        line:  line_num,
+                        name:  tree_caps[1].to_owned(),
+                        tiers: Vec::new(),
+                    });
+                },
+        }
+    }
+
+    let (name, race, alignment) = match (name, race, alignment) {
+        (Some(n), Some(r), Some(a)) => (n, r, a),
+        _ => return Err(ImportError::NoHeader),
+    };
+
+    // [Overview]
+    let expected_levels = max_levels.min(HEROIC_LEVEL_CAP);
+    if !level_order_seen || levels.is_empty() {
+        levels = order::orderings_with(&split, &[], content)
+            .ok()
+            .and_then(|mut orderings| orderings.next())
+            .ok_or(ImportError::NoLevels)?
+            .levels;
+        unrecovered.push(Unrecovered::LevelOrder);
+    }
+    if levels.len() != usize::from(expected_levels) {
+        unrecovered.push(Unrecovered::LevelCount {
+            expected: expected_levels,
+            found:    levels.len(),
+        });
+        levels.truncate(expected_levels.into());
+        if levels.len() < usize::from(expected_levels) {
+            max_levels = levels.len() as u8;
+        }
+    }
+
+    // [Stats]
+    let mut stats: Vec<Stats> = vec![Stats::default(); columns.len()];
+    let modifiers = content
+        .race(race)
+        .map_or(&[][..], |ri| &ri.ability_modifiers);
+    for (line, ability, row) in scores.iter() {
+        let modifier: i16 = modifiers
+            .iter()
+            .filter(|(a, _)| a == ability)
+            .map(|(_, m)| i16::from(*m))
+            .sum();
+
+        for (col, &score) in row.iter().enumerate().take(columns.len()) {
+            let raised =
+                i16::from(score) - modifier - i16::from(BASE_ABILITY_SCORE);
+            let max_raised = SCORE_COSTS.len() as i16 - 1;
+            if !(0..=max_raised).contains(&raised) {
+                unrecovered.push(Unrecovered::UnbuyableScore {
+                    line: *line,
+                    ability: *ability,
+                    score,
+                });
+            }
+            stats[col][*ability] =
+                SCORE_COSTS[raised.clamp(0, max_raised) as usize];
+        }
+    }
+    if columns.is_empty() {
+        unrecovered.push(Unrecovered::Abilities);
+    }
+    let stats_of =
+        |build_type| match columns.iter().position(|&bt| bt == build_type) {
+            Some(col) => Some(stats[col].clone()),
+            None if columns.is_empty() => Some(Stats::default()),
+            None => None,
+        };
+
+    // [Skills]
+    unrecovered.push(Unrecovered::Skills);
+
+    // [Feats]
+    let mut feats = Feats::default();
+    for feat_line in feat_lines.into_iter() {
+        let FeatLine {
+            line,
+            level,
+            name,
+            label,
+        } = feat_line;
+        let no_slot = || Unrecovered::FeatSlot(line, name.clone());
+        if !(1..=max_levels).contains(&level) {
+            unrecovered.push(no_slot());
+            continue;
+        }
+
+        let (secondary_type, feat_level) = match label.as_deref() {
+            None => (SecondaryFeatType::Heroic, level),
+            Some("epic") => (SecondaryFeatType::Epic, level),
+            Some("destiny") => (SecondaryFeatType::Destiny, level),
+            Some("legendary") => (SecondaryFeatType::Legend, level),
+            Some(bonus) => {
+                let bonus = bonus.trim_end_matches(" bonus feat");
+                if let Some(class) = content.class_by_name(bonus) {
+                    let class_level = levels
+                        .iter()
+                        .take(level.into())
+                        .filter(|&&c| c == class)
+                        .count() as u8;
+                    if class_level == 0 || level > HEROIC_LEVEL_CAP {
+                        unrecovered.push(no_slot());
+                        continue;
+                    }
+
+                    (SecondaryFeatType::Class(class), class_level)
+                } else if let Some(r) =
+                    content.race_by_name(bonus).filter(|&r| r == race)
+                {
+                    (SecondaryFeatType::Race(r), level)
+                } else {
+                    unrecovered.push(no_slot());
+                    continue;
+                }
+            },
+        };
+
+        let feat = Feat::new(secondary_type, feat_level, name);
+        match secondary_type {
+            SecondaryFeatType::Heroic
+            | SecondaryFeatType::Epic
+            | SecondaryFeatType::Destiny => feats.standard_feats.push(feat),
+            SecondaryFeatType::Legend => feats.legend_feats.push(feat),
+            SecondaryFeatType::Class(_) => feats.class_feats.push(feat),
+            SecondaryFeatType::Race(_) => feats.race_feats.push(feat),
+        }
+    }
+
+    // [Enhancements]
+    let mut tier_five = None;
+    let mut enhancements = Enhancements::default();
+    for tree_line in tree_lines.into_iter() {
+        let tree = match resolve_tree(&tree_line.name, &levels, race, content)
+        {
+            Some(tree) => tree,
+            None => {
+                unrecovered.push(Unrecovered::UnknownTree(
+                    tree_line.line,
+                    tree_line.name,
+                ));
+                continue;
+            },
+        };
+
+        enhancements.declare(tree);
+        for (tier, tier_enhancements) in tree_line.tiers.into_iter() {
+            if tier == 5 && !tier_enhancements.is_empty() {
+                tier_five = Some(tree);
+            }
+            for enhancement in tier_enhancements.into_iter() {
+                enhancements.insert(tree, tier, enhancement);
+            }
+        }
+    }
+
+    let build = CharacterBuild {
+        name,
+        race,
+        alignment,
+        max_levels,
+        levels,
+        preferred_build_type: columns
+            .first()
+            .copied()
+            .unwrap_or(BuildType::Adventurer),
+        adventurer_stats: stats_of(BuildType::Adventurer),
+        champion_stats: stats_of(BuildType::Champion),
+        hero_stats: stats_of(BuildType::Hero),
+        legend_stats: stats_of(BuildType::Legend),
+        stat_tomes: Stats::default(),
+        stat_levelups,
+        skills: Skills::default(),
+        feats,
+        spells,
+        tier_five,
+        enhancements,
+    };
+
+    Ok((build, unrecovered))
+}
+
+/// Splits e.g. "True Neutral Drow" into its alignment and the rest.
+fn split_alignment(s: &str) -> Option<(Alignment, &str)> {
+    let mut words = s.splitn(3, ' ');
+    let alignment = format!("{} {}", words.next()?, words.next()?);
+
+    Some((alignment.parse().ok()?, words.next()?.trim()))
+}
+
+/// Of the trees that go by `name`, the first that one of `levels` can be
+/// spent in, or that belongs to `race`, or else the first of them.
+pub(crate) fn resolve_tree(
+    name: &str,
+    levels: &[Class],
+    race: Race,
+    content: &Registry,
+) -> Option<EnhancementTreeName> {
+    let candidates = content.trees_by_name(name);
+
+    candidates
+        .iter()
+        .copied()
+        .find(|&t| {
+            content.tree(t).is_some_and(|ti| {
+                ti.sources.iter().any(|s| levels.contains(s))
+                    || ti.race == Some(race)
+            })
+        })
+        .or_else(|| candidates.first().copied())
+}
+
+/// The value of a Roman numeral from I to X, e.g. a rank.
+fn roman_value(numeral: &str) -> Option<NonZeroU8> {
+    const NUMERALS: [&str; 10] =
+        ["I", "II", "III", "IV", "V", "VI", "VII", "VIII", "IX", "X"];
+
+    let i = NUMERALS.iter().position(|&n| n == numeral)?;
+
+    NonZeroU8::new(i as u8 + 1)
+}
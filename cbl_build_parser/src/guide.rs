@@ -0,0 +1,250 @@
+//! Leveling guides: a checklist, for each character level, of everything that
+//! is chosen or gained at that level.
+
+use crate::{
+    content::{self, Registry},
+    snapshot::LevelSnapshot,
+    write::{class_name, race_name, tree_name},
+    Ability,
+    CharacterBuild,
+    Class,
+    EnhancementTreeName,
+    Feat,
+    SecondaryFeatType,
+    Skill,
+    HEROIC_LEVEL_CAP,
+};
+use std::fmt::Write;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GuideFormat {
+    Markdown,
+    PlainText,
+}
+
+/// What is chosen or gained at one character level.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GuideLevel<'a> {
+    pub level:            u8,
+    /// The class taken at this level, and the class's level after taking
+    /// it. `None` past the heroic levels.
+    pub class:            Option<(Class, u8)>,
+    pub ability_increase: Option<Ability>,
+    pub skill_points:     Vec<(Skill, u8)>,
+    pub feats:            Vec<&'a Feat>,
+    /// The spells learned at this level, as (class, spell level, name): those
+    /// in the spell slots that are learned at this level.
+    pub spells:           Vec<(Class, usize, &'a str)>,
+    /// The enhancement tiers that become available at this level.
+    pub tiers:            Vec<(EnhancementTreeName, usize)>,
+}
+
+/// Writes the leveling guide of a build, using the game content of
+/// `content::registry()`.
+pub fn leveling_guide(build: &CharacterBuild, format: GuideFormat) -> String {
+    leveling_guide_with(build, &content::registry(), format)
+}
+
+pub fn leveling_guide_with(
+    build: &CharacterBuild,
+    content: &Registry,
+    format: GuideFormat,
+) -> String {
+    let levels = guide_levels(build, content);
+
+    match format {
+        GuideFormat::Markdown => to_markdown(&levels, content),
+        GuideFormat::PlainText => to_plain_text(&levels, content),
+    }
+}
+
+/// Works out what is gained at each character level, up to `max_levels`, by
+/// comparing the build's snapshot at each level with the one before.
+pub fn guide_levels<'a>(
+    build: &'a CharacterBuild,
+    content: &Registry,
+) -> Vec<GuideLevel<'a>> {
+    let mut guide = Vec::with_capacity(build.max_levels.into());
+    let mut previous: Option<LevelSnapshot> = None;
+
+    for level in 1..=build.max_levels {
+        let snapshot = build.at_level_with(level, content);
+        let i = usize::from(level - 1);
+
+        let class = build.levels.get(i).map(|&c| {
+            let class_level =
+                build.levels[..=i].iter().filter(|&&cl| cl == c).count();
+            (c, class_level as u8)
+        });
+        let skill_points = if level <= HEROIC_LEVEL_CAP {
+            Skill::ALL
+                .iter()
+                .map(|&s| (s, build.skills.points_in_skill_at_level(s, i)))
+                .filter(|&(_, points)| points > 0)
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let feats = snapshot
+            .feats
+            .iter()
+            .filter(|(l, _)| *l == level)
+            .map(|(_, f)| *f)
+            .collect();
+
+        let (spells, tiers) = match &previous {
+            None => (snapshot.spells.clone(), new_tiers(&[], &snapshot)),
+            Some(prev) => (
+                snapshot
+                    .spells
+                    .iter()
+                    .filter(|s| !prev.spells.contains(s))
+                    .copied()
+                    .collect(),
+                new_tiers(&prev.unlocked_tiers, &snapshot),
+            ),
+        };
+
+        guide.push(GuideLevel {
+            level,
+            class,
+            ability_increase: build.levelup_at(level),
+            skill_points,
+            feats,
+            spells,
+            tiers,
+        });
+        previous = Some(snapshot);
+    }
+
+    guide
+}
+
+fn new_tiers(
+    unlocked_before: &[(EnhancementTreeName, usize)],
+    snapshot: &LevelSnapshot,
+) -> Vec<(EnhancementTreeName, usize)> {
+    snapshot
+        .unlocked_tiers
+        .iter()
+        .flat_map(|&(tree, tier)| {
+            let first = unlocked_before
+                .iter()
+                .find(|(t, _)| *t == tree)
+                .map_or(0, |(_, before)| before + 1);
+
+            (first..=tier).map(move |t| (tree, t))
+        })
+        .collect()
+}
+
+fn to_markdown(levels: &[GuideLevel], content: &Registry) -> String {
+    let mut out = String::with_capacity(4_096);
+
+    out.push_str("## Leveling Guide\n");
+    for guide_level in levels.iter() {
+        out.push_str(&format!("\n### {}\n\n", heading(guide_level, content)));
+        for (label, items) in checklist(guide_level, content) {
+            writeln!(out, "- **{}:** {}", label, items).unwrap();
+        }
+    }
+
+    out
+}
+
+fn to_plain_text(levels: &[GuideLevel], content: &Registry) -> String {
+    let mut out = String::with_capacity(4_096);
+
+    out.push_str("Leveling Guide\n");
+    for guide_level in levels.iter() {
+        out.push_str(&format!("\n{}\n", heading(guide_level, content)));
+        for (label, items) in checklist(guide_level, content) {
+            writeln!(out, "    {}: {}", label, items).unwrap();
+        }
+    }
+
+    out
+}
+
+fn heading(guide_level: &GuideLevel, content: &Registry) -> String {
+    match guide_level.class {
+        Some((class, class_level)) => format!(
+            "Level {}: {} {}",
+            guide_level.level,
+            class_name(content, class),
+            class_level,
+        ),
+        None => format!("Level {}", guide_level.level),
+    }
+}
+
+/// The non-empty lines of a level's checklist, as (label, items).
+fn checklist(
+    guide_level: &GuideLevel,
+    content: &Registry,
+) -> Vec<(&'static str, String)> {
+    let mut lines = Vec::with_capacity(5);
+
+    if let Some(ability) = guide_level.ability_increase {
+        lines.push(("Ability increase", ability.to_string()));
+    }
+    if !guide_level.skill_points.is_empty() {
+        let skills: Vec<String> = guide_level
+            .skill_points
+            .iter()
+            .map(|(skill, points)| format!("{} +{}", skill, points))
+            .collect();
+        lines.push(("Skills", skills.join(", ")));
+    }
+    if !guide_level.feats.is_empty() {
+        let feats: Vec<String> = guide_level
+            .feats
+            .iter()
+            .map(|f| feat_name(content, f))
+            .collect();
+        lines.push(("Feats", feats.join(", ")));
+    }
+    if !guide_level.spells.is_empty() {
+        let spells: Vec<String> = guide_level
+            .spells
+            .iter()
+            .map(|(class, level, name)| {
+                format!(
+                    "{} ({}, level {})",
+                    name,
+                    class_name(content, *class),
+                    level,
+                )
+            })
+            .collect();
+        lines.push(("Spells", spells.join(", ")));
+    }
+    if !guide_level.tiers.is_empty() {
+        let tiers: Vec<String> = guide_level
+            .tiers
+            .iter()
+            .map(|(tree, tier)| match tier {
+                0 => format!("{} cores", tree_name(content, *tree)),
+                t => format!("{} tier {}", tree_name(content, *tree), t),
+            })
+            .collect();
+        lines.push(("Enhancements", tiers.join(", ")));
+    }
+
+    lines
+}
+
+/// The feat's name, followed by the kind of feat slot that it takes up if
+/// it's not a regular heroic one.
+fn feat_name(content: &Registry, feat: &Feat) -> String {
+    match feat.secondary_type {
+        SecondaryFeatType::Heroic => feat.name.clone(),
+        SecondaryFeatType::Epic => format!("{} (epic)", feat.name),
+        SecondaryFeatType::Destiny => format!("{} (destiny)", feat.name),
+        SecondaryFeatType::Legend => format!("{} (legendary)", feat.name),
+        SecondaryFeatType::Class(c) =>
+            format!("{} ({} bonus feat)", feat.name, class_name(content, c)),
+        SecondaryFeatType::Race(r) =>
+            format!("{} ({} bonus feat)", feat.name, race_name(content, r)),
+    }
+}
@@ -1,14 +1,35 @@
 #![warn(clippy::all)]
 #![allow(clippy::cognitive_complexity)]
 
+pub mod allocate;
+pub mod archive;
+pub mod content;
+pub mod ddo_builder;
+pub mod edit;
+pub mod filename;
+pub mod forum;
+pub mod guide;
+pub mod migrate;
+pub mod order;
+pub mod point_buy;
+pub mod share;
+pub mod snapshot;
+pub mod spreadsheet;
+pub mod statistics;
+#[cfg(test)]
 mod tests;
+pub mod validate;
+mod write;
 
 extern crate fxhash;
 #[macro_use]
 extern crate lazy_static;
+extern crate quick_xml;
 extern crate regex;
 
+use content::{ContentId, Registry};
 use fxhash::FxHashMap as HashMap;
+use migrate::{Migration, NameKind};
 use regex::Regex;
 use std::{
     self,
@@ -16,6 +37,26 @@ use std::{
     io::{self, prelude::*},
     num::NonZeroU8,
 };
+pub use write::{write, write_with};
+
+/// The highest character level that a build can reach. Raising this (and
+/// nothing else) is enough to support a new level cap.
+pub const LEVEL_CAP: u8 = 34;
+/// The highest heroic level. Class levels are only chosen up to this level;
+/// every level after it is an epic or legendary level.
+pub const HEROIC_LEVEL_CAP: u8 = 20;
+/// Ability score increases are gained every this many character levels.
+pub const LEVELUP_INTERVAL: u8 = 4;
+/// The number of ability score increases gained on the way to `LEVEL_CAP`.
+pub const LEVELUP_COUNT: usize = (LEVEL_CAP / LEVELUP_INTERVAL) as usize;
+/// The character levels with a heroic feat slot.
+pub const HEROIC_FEAT_LEVELS: [u8; 7] = [1, 3, 6, 9, 12, 15, 18];
+/// The character levels with an epic feat slot.
+pub const EPIC_FEAT_LEVELS: [u8; 5] = [21, 24, 27, 30, 33];
+/// The character levels with an epic destiny feat slot.
+pub const DESTINY_FEAT_LEVELS: [u8; 4] = [26, 28, 29, 31];
+/// The character levels with a legendary feat slot.
+pub const LEGEND_FEAT_LEVELS: [u8; 3] = [30, 32, 34];
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct CharacterBuild {
@@ -32,7 +73,8 @@ pub struct CharacterBuild {
     pub hero_stats:           Option<Stats>,
     pub legend_stats:         Option<Stats>,
     pub stat_tomes:           Stats,
-    pub stat_levelups:        [Option<Ability>; 7],
+    /// Indexed by `level / LEVELUP_INTERVAL - 1`; see `levelup_at()`.
+    pub stat_levelups:        [Option<Ability>; LEVELUP_COUNT],
     // [Skills]
     pub skills: Skills,
     // [Feats]
@@ -50,22 +92,32 @@ pub enum Race {
     AasimarScourge,
     Bladeforged,
     DeepGnome,
+    Dhampir,
+    DhampirDarkBargainer,
     Dragonborn,
     Drow,
     Dwarf,
+    Eladrin,
+    EladrinChaosmancer,
     Elf,
     Gnome,
     HalfElf,
-    Halfing,
+    Halfling,
     HalfOrc,
     Human,
     Morninglord,
     PurpleDragonKnight,
+    RazorclawShifter,
     ShadarKai,
+    Shifter,
+    Tabaxi,
+    TabaxiTrailblazer,
     Tiefling,
     TieflingScoundrel,
     Warforged,
     WoodElf,
+    /// A race that content files added (see `content::Registry::patch()`).
+    Other(ContentId),
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -78,8 +130,9 @@ pub enum Alignment {
     ChaoticNeutral,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Class {
+    Alchemist,
     Artificer,
     Barbarian,
     Bard,
@@ -94,6 +147,8 @@ pub enum Class {
     Sorcerer,
     Warlock,
     Wizard,
+    /// A class that content files added (see `content::Registry::patch()`).
+    Other(ContentId),
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -157,7 +212,7 @@ pub struct Skills {
     /// Stores number of **points** put into each skill at each level, not the
     /// number of ranks. A missing entry means that no points were spent in
     /// that skill.
-    skill_table: HashMap<Skill, [u8; 20]>,
+    skill_table: HashMap<Skill, [u8; HEROIC_LEVEL_CAP as usize]>,
     /// Stores skill tome values. A missing entry means no tomes for that skill
     /// were eaten.
     skill_tomes: HashMap<Skill, u8>,
@@ -193,7 +248,10 @@ pub struct Feat {
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct Feats {
     pub standard_feats: Vec<Feat>,
-    pub legend_feats:   Option<Feat>,
+    /// Legendary feats, in the order that they were listed. There is at most
+    /// one for each level of `LEGEND_FEAT_LEVELS` up to `max_levels`, taken at
+    /// that level.
+    pub legend_feats:   Vec<Feat>,
     pub class_feats:    Vec<Feat>,
     pub race_feats:     Vec<Feat>,
 }
@@ -222,6 +280,9 @@ pub struct Spells {
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum ClassEnhancementTree {
+    Apothecary,
+    Bombardier,
+    VileChemist,
     Arcanotechnician,
     BattleEngineer,
     RenegadeMastermaker,
@@ -230,10 +291,13 @@ pub enum ClassEnhancementTree {
     Ravager,
     Swashbuckler,
     Spellsinger,
+    Stormsinger,
     Warchanter,
+    DarkApostate,
     DivineDisciple,
     RadiantServant,
     Warpriest,
+    Blightcaster,
     NaturesWarrior,
     NaturesProtector,
     SeasonsHerald,
@@ -242,14 +306,14 @@ pub enum ClassEnhancementTree {
     WarSoul,
     Kensei,
     StalwartDefender,
-    VanguardFighter,
+    Vanguard,
     HenshinMystic,
     NinjaSpy,
     Shintao,
     KnightOfTheChalice,
     SacredDefender,
-    VanguardPaladin,
     ArcaneArcher,
+    DarkHunter,
     DeepwoodStalker,
     Tempest,
     Assassin,
@@ -257,15 +321,18 @@ pub enum ClassEnhancementTree {
     ThiefAcrobat,
     AirSavant,
     EarthSavant,
-    EldritchKnightSorcerer,
+    EldritchKnight,
     FireSavant,
     WaterSavant,
+    WildMage,
+    AcolyteOfTheSkin,
     EnlightenedSpirit,
     SoulEater,
     TaintedScholar,
     Archmage,
-    EldritchKnightWizard,
     PaleMaster,
+    /// A tree that content files added (see `content::Registry::patch()`).
+    Other(ContentId),
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -274,11 +341,16 @@ pub enum GlobalEnhancementTree {
     Falconry,
     VistaniKnifeFighter,
     Inquisitive,
+    FeydarkIllusionist,
+    /// A tree that content files added (see `content::Registry::patch()`).
+    Other(ContentId),
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum RaceClassEnhancementTree {
     ElfArcaneArcher,
+    /// A tree that content files added (see `content::Registry::patch()`).
+    Other(ContentId),
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -289,18 +361,35 @@ pub enum EnhancementTreeName {
     RaceClass(RaceClassEnhancementTree),
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EnhancementTreeType {
+    Race,
+    Class,
+    Global,
+    RaceClass,
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct Enhancement {
     pub name: String,
     pub subenhancement: Option<String>,
     /// `rank` is `Some(n)` iff the enhancement is written as
-    /// "`name` (Rank `n`)", and is `None` otherwise. It is assumed that
-    /// `rank <= 3`, and, more importantly, that `rank >= 1`.
+    /// "`name` (Rank `n`)", and is `None` otherwise. The most ranks that an
+    /// enhancement can have is given by its catalog entry, if any (see
+    /// `content::EnhancementInfo`).
     pub rank: Option<NonZeroU8>,
 }
 
-#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct EnhancementTree {
+    pub name:         EnhancementTreeName,
+    /// The class whose levels are spent in the tree, as given by its
+    /// `Source:` line. `None` for trees that weren't given one, e.g. racial
+    /// trees.
+    pub source:       Option<Class>,
+    /// The tree's `ClassLevels:`, i.e. the number of levels of `source`, or
+    /// of character levels for trees without a source.
+    pub class_levels: Option<u8>,
     /// Indexed by tier. Tier 0 represents "core" enhancements. The
     /// `Vec<Enhancement>`s are in no particular order other than whatever
     /// order it was written in.
@@ -309,7 +398,10 @@ pub struct EnhancementTree {
 
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct Enhancements {
-    pub trees: HashMap<EnhancementTreeName, EnhancementTree>,
+    /// The trees in the order that they were declared, including trees that
+    /// were declared without any enhancements. There is at most one entry per
+    /// tree.
+    pub trees: Vec<EnhancementTree>,
 }
 
 #[derive(Debug)]
@@ -335,10 +427,16 @@ pub enum ParseError {
     UnknownBaseFeatType(String),
     UnknownSecondaryFeatType(String),
     InvalidFeatLevel(u8),
-    MultipleLegendFeats,
+    /// More legendary feats than there are legendary feat slots up to the
+    /// build's `MaxLevels`.
+    TooManyLegendFeats,
+    /// Two legendary feats in the slot of the given level.
+    DuplicateLegendFeat(u8),
     TooManySpellbooks,
+    InvalidSpellLevel(Class, usize),
     UnknownEnhancementTree(String),
     WrongEnhancementTreeType,
+    WrongEnhancementTreeSource(EnhancementTreeName, Class),
     EnhancementTreeNotDeclared,
 }
 
@@ -352,63 +450,166 @@ enum Heading {
     Enhancements,
 }
 
+impl CharacterBuild {
+    /// The number of heroic levels that the build has, i.e. the expected
+    /// length of `levels`.
+    pub fn heroic_levels(&self) -> u8 {
+        self.max_levels.min(HEROIC_LEVEL_CAP)
+    }
+
+    /// Iterates over the character levels, up to `max_levels`, at which an
+    /// ability score increase is gained.
+    pub fn levelup_levels(&self) -> impl Iterator<Item = u8> {
+        let max_levels = self.max_levels;
+
+        (1..=LEVELUP_COUNT as u8)
+            .map(|i| i * LEVELUP_INTERVAL)
+            .take_while(move |&l| l <= max_levels)
+    }
+
+    /// Returns the ability score increased at the given character level.
+    /// `None` if no increase is gained at that level, or if none was chosen.
+    pub fn levelup_at(&self, level: u8) -> Option<Ability> {
+        if level == 0
+            || level % LEVELUP_INTERVAL != 0
+            || level > LEVEL_CAP
+        {
+            return None;
+        }
+
+        self.stat_levelups[(level / LEVELUP_INTERVAL - 1) as usize]
+    }
+
+    /// Returns the build points spent for the given build type. `None` if
+    /// the build type is disabled.
+    pub fn stats(&self, build_type: BuildType) -> Option<&Stats> {
+        match build_type {
+            BuildType::Adventurer => self.adventurer_stats.as_ref(),
+            BuildType::Champion => self.champion_stats.as_ref(),
+            BuildType::Hero => self.hero_stats.as_ref(),
+            BuildType::Legend => self.legend_stats.as_ref(),
+        }
+    }
+
+    /// Returns the build points spent for the preferred build type or, if
+    /// that one is disabled, for the first build type that isn't. `None` if
+    /// every build type is disabled.
+    pub fn preferred_stats(&self) -> Option<&Stats> {
+        self.stats(self.preferred_build_type)
+            .or_else(|| BuildType::ALL.iter().find_map(|&bt| self.stats(bt)))
+    }
+
+    /// The number of levels taken in each class, most levels first. Classes
+    /// with as many levels as each other are in the order that they were
+    /// first taken.
+    pub fn class_split(&self) -> Vec<(Class, u8)> {
+        let mut split = class_levels(&self.levels);
+        // A stable sort, so ties stay in the order that they were taken
+        split.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+        split
+    }
+
+    /// Returns the character level at which the `class_level`th level of
+    /// `class` is taken. `None` if the build doesn't have that many levels of
+    /// the class.
+    pub fn character_level_of(
+        &self,
+        class: Class,
+        class_level: u8,
+    ) -> Option<u8> {
+        if class_level == 0 {
+            return None;
+        }
+
+        self.levels
+            .iter()
+            .enumerate()
+            .filter(|(_, &c)| c == class)
+            .nth(class_level as usize - 1)
+            .map(|(i, _)| i as u8 + 1)
+    }
+}
+
+/// The number of levels of each class in `levels`, in the order that the
+/// classes were first taken.
+pub fn class_levels(levels: &[Class]) -> Vec<(Class, u8)> {
+    let mut counts: Vec<(Class, u8)> = Vec::with_capacity(3);
+    for &class in levels.iter() {
+        match counts.iter_mut().find(|(c, _)| *c == class) {
+            Some((_, n)) => *n += 1,
+            None => counts.push((class, 1)),
+        }
+    }
+
+    counts
+}
+
+impl Race {
+    #[deprecated(note = "renamed to `Race::Halfling`")]
+    #[allow(non_upper_case_globals)]
+    pub const Halfing: Self = Self::Halfling;
+
+    pub const ALL: [Self; 28] = [
+        Self::Aasimar,
+        Self::AasimarScourge,
+        Self::Bladeforged,
+        Self::DeepGnome,
+        Self::Dhampir,
+        Self::DhampirDarkBargainer,
+        Self::Dragonborn,
+        Self::Drow,
+        Self::Dwarf,
+        Self::Eladrin,
+        Self::EladrinChaosmancer,
+        Self::Elf,
+        Self::Gnome,
+        Self::HalfElf,
+        Self::Halfling,
+        Self::HalfOrc,
+        Self::Human,
+        Self::Morninglord,
+        Self::PurpleDragonKnight,
+        Self::RazorclawShifter,
+        Self::ShadarKai,
+        Self::Shifter,
+        Self::Tabaxi,
+        Self::TabaxiTrailblazer,
+        Self::Tiefling,
+        Self::TieflingScoundrel,
+        Self::Warforged,
+        Self::WoodElf,
+    ];
+}
+
 impl std::str::FromStr for Race {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "Aasimar" => Ok(Self::Aasimar),
-            "Aasimar Scourge" => Ok(Self::AasimarScourge),
-            "Bladeforged" => Ok(Self::Bladeforged),
-            "Deep Gnome" => Ok(Self::DeepGnome),
-            "Dragonborn" => Ok(Self::Dragonborn),
-            "Drow" => Ok(Self::Drow),
-            "Dwarf" => Ok(Self::Dwarf),
-            "Elf" => Ok(Self::Elf),
-            "Gnome" => Ok(Self::Gnome),
-            "Half-Elf" => Ok(Self::HalfElf),
-            "Halfing" => Ok(Self::Halfing),
-            "Half-Orc" => Ok(Self::HalfOrc),
-            "Human" => Ok(Self::Human),
-            "Morninglord" => Ok(Self::Morninglord),
-            "Purple Dragon Knight" => Ok(Self::PurpleDragonKnight),
-            "Shadar-kai" => Ok(Self::ShadarKai),
-            "Tiefling" => Ok(Self::Tiefling),
-            "Tiefling Scoundrel" => Ok(Self::TieflingScoundrel),
-            "Warforged" => Ok(Self::Warforged),
-            "Wood Elf" => Ok(Self::WoodElf),
-            _ => Err(()),
-        }
+        content::registry().race_by_name(s).ok_or(())
     }
 }
 
 impl fmt::Display for Race {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        match self {
-            Self::Aasimar => f.write_str("Aasimar"),
-            Self::AasimarScourge => f.write_str("Aasimar Scourge"),
-            Self::Bladeforged => f.write_str("Bladeforged"),
-            Self::DeepGnome => f.write_str("Deep Gnome"),
-            Self::Dragonborn => f.write_str("Dragonborn"),
-            Self::Drow => f.write_str("Drow"),
-            Self::Dwarf => f.write_str("Dwarf"),
-            Self::Elf => f.write_str("Elf"),
-            Self::Gnome => f.write_str("Gnome"),
-            Self::HalfElf => f.write_str("Half-Elf"),
-            Self::Halfing => f.write_str("Halfing"),
-            Self::HalfOrc => f.write_str("Half-Orc"),
-            Self::Human => f.write_str("Human"),
-            Self::Morninglord => f.write_str("Morninglord"),
-            Self::PurpleDragonKnight => f.write_str("Purple Dragon Knight"),
-            Self::ShadarKai => f.write_str("Shadar-kai"),
-            Self::Tiefling => f.write_str("Tiefling"),
-            Self::TieflingScoundrel => f.write_str("Tiefling Scoundrel"),
-            Self::Warforged => f.write_str("Warforged"),
-            Self::WoodElf => f.write_str("Wood Elf"),
+        match content::registry().race(*self) {
+            Some(info) => f.write_str(&info.name),
+            None => write!(f, "{:?}", self),
         }
     }
 }
 
+impl Alignment {
+    pub const ALL: [Self; 6] = [
+        Self::LawfulGood,
+        Self::LawfulNeutral,
+        Self::NeutralGood,
+        Self::TrueNeutral,
+        Self::ChaoticGood,
+        Self::ChaoticNeutral,
+    ];
+}
+
 impl std::str::FromStr for Alignment {
     type Err = ();
 
@@ -438,47 +639,54 @@ impl fmt::Display for Alignment {
     }
 }
 
+impl Class {
+    pub const ALL: [Self; 15] = [
+        Self::Alchemist,
+        Self::Artificer,
+        Self::Barbarian,
+        Self::Bard,
+        Self::Cleric,
+        Self::Druid,
+        Self::FavoredSoul,
+        Self::Fighter,
+        Self::Monk,
+        Self::Paladin,
+        Self::Ranger,
+        Self::Rogue,
+        Self::Sorcerer,
+        Self::Warlock,
+        Self::Wizard,
+    ];
+}
+
 impl std::str::FromStr for Class {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "Artificer" => Ok(Self::Artificer),
-            "Barbarian" => Ok(Self::Barbarian),
-            "Bard" => Ok(Self::Bard),
-            "Cleric" => Ok(Self::Cleric),
-            "Druid" => Ok(Self::Druid),
-            "Favored Soul" => Ok(Self::FavoredSoul),
-            "Fighter" => Ok(Self::Fighter),
-            "Monk" => Ok(Self::Monk),
-            "Paladin" => Ok(Self::Paladin),
-            "Ranger" => Ok(Self::Ranger),
-            "Rogue" => Ok(Self::Rogue),
-            "Sorcerer" => Ok(Self::Sorcerer),
-            "Warlock" => Ok(Self::Warlock),
-            "Wizard" => Ok(Self::Wizard),
-            _ => Err(()),
-        }
+        content::registry().class_by_name(s).ok_or(())
     }
 }
 
 impl fmt::Display for Class {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match content::registry().class(*self) {
+            Some(info) => f.write_str(&info.name),
+            None => write!(f, "{:?}", self),
+        }
+    }
+}
+
+impl BuildType {
+    pub const ALL: [Self; 4] =
+        [Self::Adventurer, Self::Champion, Self::Hero, Self::Legend];
+
+    /// The build points that a build of this type can spend on abilities.
+    pub fn build_points(self) -> u16 {
         match self {
-            Self::Artificer => f.write_str("Artificer"),
-            Self::Barbarian => f.write_str("Barbarian"),
-            Self::Bard => f.write_str("Bard"),
-            Self::Cleric => f.write_str("Cleric"),
-            Self::Druid => f.write_str("Druid"),
-            Self::FavoredSoul => f.write_str("Favored Soul"),
-            Self::Fighter => f.write_str("Fighter"),
-            Self::Monk => f.write_str("Monk"),
-            Self::Paladin => f.write_str("Paladin"),
-            Self::Ranger => f.write_str("Ranger"),
-            Self::Rogue => f.write_str("Rogue"),
-            Self::Sorcerer => f.write_str("Sorcerer"),
-            Self::Warlock => f.write_str("Warlock"),
-            Self::Wizard => f.write_str("Wizard"),
+            Self::Adventurer => 28,
+            Self::Champion => 32,
+            Self::Hero => 34,
+            Self::Legend => 36,
         }
     }
 }
@@ -497,6 +705,40 @@ impl std::str::FromStr for BuildType {
     }
 }
 
+impl fmt::Display for BuildType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Self::Adventurer => f.write_str("Adventurer"),
+            Self::Champion => f.write_str("Champion"),
+            Self::Hero => f.write_str("Hero"),
+            Self::Legend => f.write_str("Legend"),
+        }
+    }
+}
+
+impl Ability {
+    pub const ALL: [Self; 6] = [
+        Self::Str,
+        Self::Dex,
+        Self::Con,
+        Self::Int,
+        Self::Wis,
+        Self::Cha,
+    ];
+
+    /// The three-letter abbreviation of the ability, e.g. `"STR"`.
+    pub fn abbreviation(self) -> &'static str {
+        match self {
+            Self::Str => "STR",
+            Self::Dex => "DEX",
+            Self::Con => "CON",
+            Self::Int => "INT",
+            Self::Wis => "WIS",
+            Self::Cha => "CHA",
+        }
+    }
+}
+
 impl std::str::FromStr for Ability {
     type Err = ();
 
@@ -513,6 +755,19 @@ impl std::str::FromStr for Ability {
     }
 }
 
+impl fmt::Display for Ability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Self::Str => f.write_str("Strength"),
+            Self::Dex => f.write_str("Dexterity"),
+            Self::Con => f.write_str("Constitution"),
+            Self::Int => f.write_str("Intelligence"),
+            Self::Wis => f.write_str("Wisdom"),
+            Self::Cha => f.write_str("Charisma"),
+        }
+    }
+}
+
 impl std::ops::Index<Ability> for Stats {
     type Output = u8;
 
@@ -542,6 +797,30 @@ impl std::ops::IndexMut<Ability> for Stats {
 }
 
 impl Skill {
+    pub const ALL: [Self; 21] = [
+        Self::Balance,
+        Self::Bluff,
+        Self::Concentration,
+        Self::Diplomacy,
+        Self::DisableDevice,
+        Self::Haggle,
+        Self::Heal,
+        Self::Hide,
+        Self::Intimidate,
+        Self::Jump,
+        Self::Listen,
+        Self::MoveSilently,
+        Self::OpenLock,
+        Self::Perform,
+        Self::Repair,
+        Self::Search,
+        Self::Spellcraft,
+        Self::Spot,
+        Self::Swim,
+        Self::Tumble,
+        Self::UseMagicDevice,
+    ];
+
     fn from_7_chars(chars: &str) -> Option<Self> {
         match &chars[..3] {
             "Bal" => Some(Self::Balance),
@@ -568,13 +847,103 @@ impl Skill {
             _ => None,
         }
     }
+
+    /// The inverse of `from_7_chars()`: the name of the skill as it is written
+    /// in the skills grid of a build file.
+    fn to_7_chars(self) -> &'static str {
+        match self {
+            Self::Balance => "Balance",
+            Self::Bluff => "Bluff  ",
+            Self::Concentration => "Concent",
+            Self::Diplomacy => "Diplo  ",
+            Self::DisableDevice => "Disable",
+            Self::Haggle => "Haggle ",
+            Self::Heal => "Heal   ",
+            Self::Hide => "Hide   ",
+            Self::Intimidate => "Intim  ",
+            Self::Jump => "Jump   ",
+            Self::Listen => "Listen ",
+            Self::MoveSilently => "Move Si",
+            Self::OpenLock => "Open Lo",
+            Self::Perform => "Perform",
+            Self::Repair => "Repair ",
+            Self::Search => "Search ",
+            Self::Spellcraft => "Spellcr",
+            Self::Spot => "Spot   ",
+            Self::Swim => "Swim   ",
+            Self::Tumble => "Tumble ",
+            Self::UseMagicDevice => "UMD    ",
+        }
+    }
+}
+
+impl std::str::FromStr for Skill {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Balance" => Ok(Self::Balance),
+            "Bluff" => Ok(Self::Bluff),
+            "Concentration" => Ok(Self::Concentration),
+            "Diplomacy" => Ok(Self::Diplomacy),
+            "Disable Device" => Ok(Self::DisableDevice),
+            "Haggle" => Ok(Self::Haggle),
+            "Heal" => Ok(Self::Heal),
+            "Hide" => Ok(Self::Hide),
+            "Intimidate" => Ok(Self::Intimidate),
+            "Jump" => Ok(Self::Jump),
+            "Listen" => Ok(Self::Listen),
+            "Move Silently" => Ok(Self::MoveSilently),
+            "Open Lock" => Ok(Self::OpenLock),
+            "Perform" => Ok(Self::Perform),
+            "Repair" => Ok(Self::Repair),
+            "Search" => Ok(Self::Search),
+            "Spellcraft" => Ok(Self::Spellcraft),
+            "Spot" => Ok(Self::Spot),
+            "Swim" => Ok(Self::Swim),
+            "Tumble" => Ok(Self::Tumble),
+            "Use Magic Device" => Ok(Self::UseMagicDevice),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Skill {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Self::Balance => f.write_str("Balance"),
+            Self::Bluff => f.write_str("Bluff"),
+            Self::Concentration => f.write_str("Concentration"),
+            Self::Diplomacy => f.write_str("Diplomacy"),
+            Self::DisableDevice => f.write_str("Disable Device"),
+            Self::Haggle => f.write_str("Haggle"),
+            Self::Heal => f.write_str("Heal"),
+            Self::Hide => f.write_str("Hide"),
+            Self::Intimidate => f.write_str("Intimidate"),
+            Self::Jump => f.write_str("Jump"),
+            Self::Listen => f.write_str("Listen"),
+            Self::MoveSilently => f.write_str("Move Silently"),
+            Self::OpenLock => f.write_str("Open Lock"),
+            Self::Perform => f.write_str("Perform"),
+            Self::Repair => f.write_str("Repair"),
+            Self::Search => f.write_str("Search"),
+            Self::Spellcraft => f.write_str("Spellcraft"),
+            Self::Spot => f.write_str("Spot"),
+            Self::Swim => f.write_str("Swim"),
+            Self::Tumble => f.write_str("Tumble"),
+            Self::UseMagicDevice => f.write_str("Use Magic Device"),
+        }
+    }
 }
 
 impl Skills {
     /// Returns an array of points spent in the given skill for levels 1
     /// through 20. A return value of `None` means that no points were spent at
     /// any level.
-    pub fn points_in_skill(&self, skill: Skill) -> Option<&[u8; 20]> {
+    pub fn points_in_skill(
+        &self,
+        skill: Skill,
+    ) -> Option<&[u8; HEROIC_LEVEL_CAP as usize]> {
         self.skill_table.get(&skill)
     }
 
@@ -629,6 +998,7 @@ impl Spells {
     ///
     /// This method uses debug-only assertions to check that `1 <= level <= 9`
     /// and that `name` is **not** `Some(s)` where `s.is_empty()`.
+    #[allow(clippy::result_unit_err)]
     pub fn insert(
         &mut self,
         class: Class,
@@ -648,7 +1018,7 @@ impl Spells {
                     true
                 }
             })
-            .and_then(|msb| {
+            .map(|msb| {
                 let sb = msb.get_or_insert_with(|| Spellbook {
                     class,
                     spells_by_level: Vec::with_capacity(5),
@@ -658,8 +1028,6 @@ impl Spells {
                     sb.spells_by_level.resize_with(level, Vec::new);
                 }
                 sb.spells_by_level[level - 1].push(name);
-
-                Some(())
             })
             .ok_or(())
     }
@@ -668,99 +1036,133 @@ impl Spells {
 impl std::str::FromStr for EnhancementTreeName {
     type Err = ();
 
-    /// **NOTE:** if `s == "Vanguard"`, then this function returns
-    /// `Ok(Self::Class(ClassEnhancementTree::VanguardFighter))`. The caller
-    /// must disambiguate on their own if this is the value that they receive.
+    /// **NOTE:** if `s` is shared by several trees (e.g. `"Arcane Archer"`),
+    /// then this function returns the first of them. The caller must
+    /// disambiguate on their own, e.g. with
+    /// `content::Registry::resolve_tree()`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        content::registry().tree_by_name(s).ok_or(())
+    }
+}
+
+impl ClassEnhancementTree {
+    pub const ALL: [Self; 51] = [
+        Self::Apothecary,
+        Self::Bombardier,
+        Self::VileChemist,
+        Self::Arcanotechnician,
+        Self::BattleEngineer,
+        Self::RenegadeMastermaker,
+        Self::FrenziedBerserker,
+        Self::OccultSlayer,
+        Self::Ravager,
+        Self::Swashbuckler,
+        Self::Spellsinger,
+        Self::Stormsinger,
+        Self::Warchanter,
+        Self::DarkApostate,
+        Self::DivineDisciple,
+        Self::RadiantServant,
+        Self::Warpriest,
+        Self::Blightcaster,
+        Self::NaturesWarrior,
+        Self::NaturesProtector,
+        Self::SeasonsHerald,
+        Self::AngelOfVengeance,
+        Self::BeaconOfHope,
+        Self::WarSoul,
+        Self::Kensei,
+        Self::StalwartDefender,
+        Self::Vanguard,
+        Self::HenshinMystic,
+        Self::NinjaSpy,
+        Self::Shintao,
+        Self::KnightOfTheChalice,
+        Self::SacredDefender,
+        Self::ArcaneArcher,
+        Self::DarkHunter,
+        Self::DeepwoodStalker,
+        Self::Tempest,
+        Self::Assassin,
+        Self::Mechanic,
+        Self::ThiefAcrobat,
+        Self::AirSavant,
+        Self::EarthSavant,
+        Self::EldritchKnight,
+        Self::FireSavant,
+        Self::WaterSavant,
+        Self::WildMage,
+        Self::AcolyteOfTheSkin,
+        Self::EnlightenedSpirit,
+        Self::SoulEater,
+        Self::TaintedScholar,
+        Self::Archmage,
+        Self::PaleMaster,
+    ];
+}
+
+impl fmt::Display for ClassEnhancementTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        EnhancementTreeName::Class(*self).fmt(f)
+    }
+}
+
+impl GlobalEnhancementTree {
+    pub const ALL: [Self; 5] = [
+        Self::HarperAgent,
+        Self::Falconry,
+        Self::VistaniKnifeFighter,
+        Self::Inquisitive,
+        Self::FeydarkIllusionist,
+    ];
+}
+
+impl fmt::Display for GlobalEnhancementTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        EnhancementTreeName::Global(*self).fmt(f)
+    }
+}
+
+impl RaceClassEnhancementTree {
+    pub const ALL: [Self; 1] = [Self::ElfArcaneArcher];
+}
+
+impl fmt::Display for RaceClassEnhancementTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        EnhancementTreeName::RaceClass(*self).fmt(f)
+    }
+}
+
+impl EnhancementTreeName {
+    pub fn tree_type(self) -> EnhancementTreeType {
+        match self {
+            Self::Class(_) => EnhancementTreeType::Class,
+            Self::Race(_) => EnhancementTreeType::Race,
+            Self::Global(_) => EnhancementTreeType::Global,
+            Self::RaceClass(_) => EnhancementTreeType::RaceClass,
+        }
+    }
+}
+
+impl fmt::Display for EnhancementTreeName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match content::registry().tree(*self) {
+            Some(info) => f.write_str(&info.name),
+            None => write!(f, "{:?}", self),
+        }
+    }
+}
+
+impl std::str::FromStr for EnhancementTreeType {
+    type Err = ();
+
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            "Arcanotechnician" =>
-                Ok(Self::Class(ClassEnhancementTree::Arcanotechnician)),
-            "Battle Engineer" =>
-                Ok(Self::Class(ClassEnhancementTree::BattleEngineer)),
-            "Renegade Mastermaker" =>
-                Ok(Self::Class(ClassEnhancementTree::RenegadeMastermaker)),
-            "Frenzied Berserker" =>
-                Ok(Self::Class(ClassEnhancementTree::FrenziedBerserker)),
-            "Occult Slayer" =>
-                Ok(Self::Class(ClassEnhancementTree::OccultSlayer)),
-            "Ravager" => Ok(Self::Class(ClassEnhancementTree::Ravager)),
-            "Swashbuckler" =>
-                Ok(Self::Class(ClassEnhancementTree::Swashbuckler)),
-            "Spellsinger" =>
-                Ok(Self::Class(ClassEnhancementTree::Spellsinger)),
-            "Warchanter" => Ok(Self::Class(ClassEnhancementTree::Warchanter)),
-            "Divine Disciple" =>
-                Ok(Self::Class(ClassEnhancementTree::DivineDisciple)),
-            "Radiant Servant" =>
-                Ok(Self::Class(ClassEnhancementTree::RadiantServant)),
-            "Warpriest" => Ok(Self::Class(ClassEnhancementTree::Warpriest)),
-            "Nature's Warrior" =>
-                Ok(Self::Class(ClassEnhancementTree::NaturesWarrior)),
-            "Nature's Protector" =>
-                Ok(Self::Class(ClassEnhancementTree::NaturesProtector)),
-            "Season's Herald" =>
-                Ok(Self::Class(ClassEnhancementTree::SeasonsHerald)),
-            "Angel of Vengeance" =>
-                Ok(Self::Class(ClassEnhancementTree::AngelOfVengeance)),
-            "Beacon of Hope" =>
-                Ok(Self::Class(ClassEnhancementTree::BeaconOfHope)),
-            "War Soul" => Ok(Self::Class(ClassEnhancementTree::WarSoul)),
-            "Kensei" => Ok(Self::Class(ClassEnhancementTree::Kensei)),
-            "Stalwart Defender" =>
-                Ok(Self::Class(ClassEnhancementTree::StalwartDefender)),
-            "Vanguard" | "Vanguard (Fighter)" =>
-                Ok(Self::Class(ClassEnhancementTree::VanguardFighter)),
-            "Henshin Mystic" =>
-                Ok(Self::Class(ClassEnhancementTree::HenshinMystic)),
-            "Ninja Spy" => Ok(Self::Class(ClassEnhancementTree::NinjaSpy)),
-            "Shintao" => Ok(Self::Class(ClassEnhancementTree::Shintao)),
-            "Knight of the Chalice" =>
-                Ok(Self::Class(ClassEnhancementTree::KnightOfTheChalice)),
-            "Sacred Defender" =>
-                Ok(Self::Class(ClassEnhancementTree::SacredDefender)),
-            "Vanguard (Paladin)" =>
-                Ok(Self::Class(ClassEnhancementTree::VanguardPaladin)),
-            "Arcane Archer" =>
-                Ok(Self::Class(ClassEnhancementTree::ArcaneArcher)),
-            "Deepwood Stalker" =>
-                Ok(Self::Class(ClassEnhancementTree::DeepwoodStalker)),
-            "Tempest" => Ok(Self::Class(ClassEnhancementTree::Tempest)),
-            "Assassin" => Ok(Self::Class(ClassEnhancementTree::Assassin)),
-            "Mechanic" => Ok(Self::Class(ClassEnhancementTree::Mechanic)),
-            "Thief-Acrobat" =>
-                Ok(Self::Class(ClassEnhancementTree::ThiefAcrobat)),
-            "Air Savant" => Ok(Self::Class(ClassEnhancementTree::AirSavant)),
-            "Earth Savant" =>
-                Ok(Self::Class(ClassEnhancementTree::EarthSavant)),
-            "Eldritch Knight (Sorcerer)" =>
-                Ok(Self::Class(ClassEnhancementTree::EldritchKnightSorcerer)),
-            "Fire Savant" => Ok(Self::Class(ClassEnhancementTree::FireSavant)),
-            "Water Savant" =>
-                Ok(Self::Class(ClassEnhancementTree::WaterSavant)),
-            "Enlightened Spirit" =>
-                Ok(Self::Class(ClassEnhancementTree::EnlightenedSpirit)),
-            "Soul Eater" => Ok(Self::Class(ClassEnhancementTree::SoulEater)),
-            "Tainted Scholar" =>
-                Ok(Self::Class(ClassEnhancementTree::TaintedScholar)),
-            "Archmage" => Ok(Self::Class(ClassEnhancementTree::Archmage)),
-            "Eldritch Knight (Wizard)" =>
-                Ok(Self::Class(ClassEnhancementTree::EldritchKnightWizard)),
-            "Pale Master" => Ok(Self::Class(ClassEnhancementTree::PaleMaster)),
-            "Harper Agent" =>
-                Ok(Self::Global(GlobalEnhancementTree::HarperAgent)),
-            "Falconry" => Ok(Self::Global(GlobalEnhancementTree::Falconry)),
-            "Vistani Knife Fighter" =>
-                Ok(Self::Global(GlobalEnhancementTree::VistaniKnifeFighter)),
-            "Inquisitive" =>
-                Ok(Self::Global(GlobalEnhancementTree::Inquisitive)),
-            "Elf-Arcane Archer" =>
-                Ok(Self::RaceClass(RaceClassEnhancementTree::ElfArcaneArcher)),
-            _ =>
-                if let Ok(r) = s.parse() {
-                    Ok(Self::Race(r))
-                } else {
-                    Err(())
-                },
+            "Race" => Ok(Self::Race),
+            "Class" => Ok(Self::Class),
+            "Global" => Ok(Self::Global),
+            "RaceClass" => Ok(Self::RaceClass),
+            _ => Err(()),
         }
     }
 }
@@ -779,22 +1181,106 @@ impl Enhancement {
     }
 }
 
+impl EnhancementTree {
+    pub fn new(name: EnhancementTreeName) -> Self {
+        Self {
+            name,
+            source: None,
+            class_levels: None,
+            tiers: Default::default(),
+        }
+    }
+
+    /// Whether any enhancements were taken in the tree.
+    pub fn is_empty(&self) -> bool {
+        self.tiers.iter().all(Vec::is_empty)
+    }
+
+    /// The action points spent in the tree, using the game content of
+    /// `content::registry()`.
+    pub fn action_points(&self) -> u16 {
+        self.action_points_with(&content::registry())
+    }
+
+    /// The action points spent in the tree, according to `content`.
+    /// Enhancements that aren't catalogued cost 1 point per rank.
+    pub fn action_points_with(&self, content: &Registry) -> u16 {
+        self.tiers
+            .iter()
+            .enumerate()
+            .flat_map(|(tier, es)| es.iter().map(move |e| (tier, e)))
+            .map(|(tier, e)| {
+                let cost = content
+                    .enhancement(self.name, tier, &e.name)
+                    .map_or(1, |ei| ei.cost);
+                let ranks = e.rank.map_or(1, |r| r.get());
+
+                u16::from(cost) * u16::from(ranks)
+            })
+            .sum()
+    }
+}
+
 impl Enhancements {
-    /// `tier <= 5`, checked by debug-only assertion.
+    pub fn get(
+        &self,
+        tree_name: EnhancementTreeName,
+    ) -> Option<&EnhancementTree> {
+        self.trees.iter().find(|et| et.name == tree_name)
+    }
+
+    pub fn get_mut(
+        &mut self,
+        tree_name: EnhancementTreeName,
+    ) -> Option<&mut EnhancementTree> {
+        self.trees.iter_mut().find(|et| et.name == tree_name)
+    }
+
+    pub fn contains(&self, tree_name: EnhancementTreeName) -> bool {
+        self.get(tree_name).is_some()
+    }
+
+    /// Returns the given tree, adding an empty one after the existing trees
+    /// if it hasn't been declared yet.
+    pub fn declare(
+        &mut self,
+        tree_name: EnhancementTreeName,
+    ) -> &mut EnhancementTree {
+        let i = match self.trees.iter().position(|et| et.name == tree_name) {
+            Some(i) => i,
+            None => {
+                self.trees.push(EnhancementTree::new(tree_name));
+                self.trees.len() - 1
+            },
+        };
+
+        &mut self.trees[i]
+    }
+
+    /// `tier <= 5`, checked by debug-only assertion. Returns the tree that
+    /// the enhancement was inserted into.
     pub fn insert(
         &mut self,
         tree_name: EnhancementTreeName,
         tier: usize,
         enhancement: Enhancement,
-    ) {
+    ) -> &mut EnhancementTree {
         debug_assert!(tier <= 5);
 
-        if let Some(et) = self.trees.get_mut(&tree_name) {
-            et.tiers[tier].push(enhancement);
-        } else {
-            let mut et = EnhancementTree::default();
-            et.tiers[tier].push(enhancement);
-            self.trees.insert(tree_name, et);
+        let et = self.declare(tree_name);
+        et.tiers[tier].push(enhancement);
+
+        et
+    }
+
+    /// Adds a tree that was read from a build. If the tree was already
+    /// declared, its enhancements are added to the existing entry.
+    fn merge(&mut self, tree: EnhancementTree) {
+        let et = self.declare(tree.name);
+        et.source = tree.source.or(et.source);
+        et.class_levels = tree.class_levels.or(et.class_levels);
+        for (tier, enhancements) in tree.tiers.iter().enumerate() {
+            et.tiers[tier].extend(enhancements.iter().cloned());
         }
     }
 }
@@ -836,14 +1322,22 @@ impl fmt::Display for ParseError {
                 write!(f, "Unknown secondary feat type: {}", s),
             Self::InvalidFeatLevel(l) =>
                 write!(f, "Invalid feat level: {}", l),
-            Self::MultipleLegendFeats => f.write_str("Multiple Legend feats"),
+            Self::TooManyLegendFeats => f.write_str(
+                "More legendary feats than there are legendary feat slots",
+            ),
+            Self::DuplicateLegendFeat(l) =>
+                write!(f, "Two legendary feats at level {}", l),
             Self::TooManySpellbooks => f.write_str(
                 "Too many spellbooks (a build can have -- at most -- three)",
             ),
+            Self::InvalidSpellLevel(c, l) =>
+                write!(f, "{} has no level {} spells", c, l),
             Self::UnknownEnhancementTree(e) =>
                 write!(f, "Unknown enhancement tree: {}", e),
             Self::WrongEnhancementTreeType =>
                 f.write_str("Wrong enhancement tree type"),
+            Self::WrongEnhancementTreeSource(t, c) =>
+                write!(f, "{} levels can't be spent in {}", c, t),
             Self::EnhancementTreeNotDeclared =>
                 f.write_str("Enhancement tree not declared"),
         }
@@ -858,7 +1352,46 @@ impl std::convert::From<io::Error> for ParseError {
     }
 }
 
+/// Parses a build using the game content of `content::registry()`.
 pub fn parse<R: BufRead>(input: &mut R) -> Result<CharacterBuild, ParseError> {
+    parse_with(input, &content::registry())
+}
+
+/// Parses a build, looking up the names of races, classes and enhancement
+/// trees in `content`.
+pub fn parse_with<R: BufRead>(
+    input: &mut R,
+    content: &Registry,
+) -> Result<CharacterBuild, ParseError> {
+    parse_recording(input, content, &mut Vec::new())
+}
+
+/// Does the work of `parse_with()`, noting every race, class and tree that was
+/// read by a former name in `migrations`.
+fn parse_recording<R: BufRead>(
+    input: &mut R,
+    content: &Registry,
+    migrations: &mut Vec<Migration>,
+) -> Result<CharacterBuild, ParseError> {
+    // Trees that share a name (e.g. "Arcane Archer") are told apart by their
+    // `Source:` rather than renamed, so the shared name isn't a former name.
+    // Nor are the names that CBL gives a tree for each of its sources.
+    let record_tree = |migrations: &mut Vec<Migration>, tree_str: &str| {
+        if let [tree] = content.trees_by_name(tree_str) {
+            let info = content.tree(*tree);
+            if info.is_some_and(|ti| {
+                ti.source_names.iter().all(|(_, n)| n != tree_str)
+            }) {
+                migrate::record(
+                    migrations,
+                    NameKind::EnhancementTree,
+                    tree_str,
+                    info.map(|ti| ti.name.as_str()),
+                );
+            }
+        }
+    };
+
     lazy_static! {
         // General regexps //
         static ref BLANK_RE: Regex = Regex::new(r"^\s*(;.*)?$").unwrap();
@@ -905,18 +1438,18 @@ pub fn parse<R: BufRead>(input: &mut R) -> Result<CharacterBuild, ParseError> {
         ).unwrap();
 
         // [Enhancements] regexps //
-        static ref TIER5_RE: Regex = Regex::new(r"^Tier5:\s+([A-Za-z '-]+)$")
+        static ref TIER5_RE: Regex = Regex::new(r"^Tier5:\s+([A-Za-z '()-]+)$")
             .unwrap();
-        static ref TREE_RE: Regex = Regex::new(r"^Tree:\s+([A-Za-z '-]+)$")
+        static ref TREE_RE: Regex = Regex::new(r"^Tree:\s+([A-Za-z '()-]+)$")
             .unwrap();
         static ref TREE_TYPE_RE: Regex =
             Regex::new(r"^Type:\s+(Race|Class|Global|RaceClass)$").unwrap();
         static ref SOURCE_RE: Regex = Regex::new(r"^Source:\s+([A-Za-z -]+)$")
             .unwrap();
         static ref CLASSLEVELS_RE: Regex =
-            Regex::new(r"^ClassLevels:\s+[1-9][0-9]?$").unwrap();
+            Regex::new(r"^ClassLevels:\s+([1-9][0-9]?)$").unwrap();
         static ref ENHANCEMENT_RE: Regex = Regex::new(
-            r"^Ability:\s+Tier\s+([0-5]):\s+([A-Za-z '-]+)(: ([A-Za-z '-]+))?( \(Rank ([1-3])\))?$"
+            r"^Ability:\s+Tier\s+([0-5]):\s+([A-Za-z '-]+)(: ([A-Za-z '-]+))?( \(Rank ([1-9])\))?$"
         ).unwrap();
     }
 
@@ -925,9 +1458,9 @@ pub fn parse<R: BufRead>(input: &mut R) -> Result<CharacterBuild, ParseError> {
     let mut name = String::new();
     let mut race: Option<Race> = None;
     let mut alignment: Option<Alignment> = None;
-    let mut max_levels = 20;
+    let mut max_levels = HEROIC_LEVEL_CAP;
     let mut classes: [Option<Class>; 3] = [None, None, None];
-    let mut levels = Vec::with_capacity(20);
+    let mut levels = Vec::with_capacity(HEROIC_LEVEL_CAP as usize);
 
     // [Stats]
     let mut preferred_build_type = BuildType::Adventurer;
@@ -936,7 +1469,7 @@ pub fn parse<R: BufRead>(input: &mut R) -> Result<CharacterBuild, ParseError> {
     let mut hero_stats = Some(Stats::default());
     let mut legend_stats = Some(Stats::default());
     let mut stat_tomes = Stats::default();
-    let mut stat_levelups = [None, None, None, None, None, None, None];
+    let mut stat_levelups = [None; LEVELUP_COUNT];
 
     // [Skills]
     let mut skills = Skills::default();
@@ -949,9 +1482,13 @@ pub fn parse<R: BufRead>(input: &mut R) -> Result<CharacterBuild, ParseError> {
     let mut spells = Spells::default();
 
     // [Enhancements]
-    let mut tier_five = None;
+    let mut tier_five_str: Option<String> = None;
     let mut enhancements = Enhancements::default();
-    let mut current_tree: Option<EnhancementTreeName> = None;
+    // The tree being read, which is only added to `enhancements` once all of
+    // it has been read, since its `Type:` and `Source:` can change which tree
+    // it is
+    let mut current_tree: Option<EnhancementTree> = None;
+    let mut current_tree_str = String::new();
     ////////////////////////////////////////////////////////////////
 
     let mut heading = Heading::None;
@@ -984,9 +1521,17 @@ pub fn parse<R: BufRead>(input: &mut R) -> Result<CharacterBuild, ParseError> {
                     name = name_caps[1].to_owned();
                 } else if let Some(race_caps) = RACE_RE.captures(&line) {
                     let race_str = &race_caps[1];
-                    race = Some(race_str.parse().map_err(|_| {
-                        ParseError::UnknownRace(race_str.to_owned())
-                    })?);
+                    let r =
+                        content.race_by_name(race_str).ok_or_else(|| {
+                            ParseError::UnknownRace(race_str.to_owned())
+                        })?;
+                    migrate::record(
+                        migrations,
+                        NameKind::Race,
+                        race_str,
+                        content.race(r).map(|ri| ri.name.as_str()),
+                    );
+                    race = Some(r);
                 } else if let Some(alignment_caps) =
                     ALIGNMENT_RE.captures(&line)
                 {
@@ -1000,14 +1545,21 @@ pub fn parse<R: BufRead>(input: &mut R) -> Result<CharacterBuild, ParseError> {
                     // Unwrapping the `.parse()` since the regular expression
                     // guarantees the result to be `Ok`
                     max_levels = max_levels_caps[1].parse().unwrap();
-                    if max_levels < 1 || max_levels > 30 {
+                    if !(1..=LEVEL_CAP).contains(&max_levels) {
                         return Err(ParseError::InvalidMaxLevel(max_levels));
                     }
                 } else if let Some(class_caps) = CLASS_RE.captures(&line) {
                     let class_str = &class_caps[1];
-                    let class = class_str.parse().map_err(|_| {
-                        ParseError::UnknownClass(class_str.to_owned())
-                    })?;
+                    let class =
+                        content.class_by_name(class_str).ok_or_else(|| {
+                            ParseError::UnknownClass(class_str.to_owned())
+                        })?;
+                    migrate::record(
+                        migrations,
+                        NameKind::Class,
+                        class_str,
+                        content.class(class).map(|ci| ci.name.as_str()),
+                    );
 
                     classes
                         .iter_mut()
@@ -1018,7 +1570,7 @@ pub fn parse<R: BufRead>(input: &mut R) -> Result<CharacterBuild, ParseError> {
                     // Unwrapping the `.parse()` since the regular expression
                     // guarantees the result to be `Ok`
                     let level_num = level_caps[1].parse().unwrap();
-                    if level_num < 1 || level_num > 20 {
+                    if !(1..=HEROIC_LEVEL_CAP).contains(&level_num) {
                         return Err(ParseError::InvalidLevelNum(level_num));
                     }
                     if level_num != (levels.len() + 1) as u8 {
@@ -1026,12 +1578,17 @@ pub fn parse<R: BufRead>(input: &mut R) -> Result<CharacterBuild, ParseError> {
                     }
 
                     let level_class_str = &level_caps[2];
-                    let level_class =
-                        level_class_str.parse().map_err(|_| {
-                            ParseError::UnknownClass(
-                                level_class_str.to_owned(),
-                            )
-                        })?;
+                    let level_class = content
+                        .class_by_name(level_class_str)
+                        .ok_or_else(|| {
+                        ParseError::UnknownClass(level_class_str.to_owned())
+                    })?;
+                    migrate::record(
+                        migrations,
+                        NameKind::Class,
+                        level_class_str,
+                        content.class(level_class).map(|ci| ci.name.as_str()),
+                    );
                     if classes
                         .iter()
                         .find(|&&c| c == Some(level_class))
@@ -1063,25 +1620,24 @@ pub fn parse<R: BufRead>(input: &mut R) -> Result<CharacterBuild, ParseError> {
                     // Unwrapping because the regexp ensures successful parse
                     let ability = ability_caps[1].parse().unwrap();
 
-                    (&mut [
+                    [
                         adventurer_stats.as_mut(),
                         champion_stats.as_mut(),
                         hero_stats.as_mut(),
                         legend_stats.as_mut(),
                         Some(&mut stat_tomes),
-                    ])
-                        .iter_mut()
-                        .zip(2..)
-                        .filter_map(|(maybe_stats, cg)| match maybe_stats {
-                            Some(s) => Some((s, cg)),
-                            _ => None,
-                        })
-                        .for_each(|(stats, cap_grp)| {
-                            stats[ability] = ability_caps[cap_grp]
-                                .trim_start()
-                                .parse()
-                                .unwrap_or(0); // CBL uses whitespace to mean 0
-                        });
+                    ]
+                    .iter_mut()
+                    .zip(2..)
+                    .filter_map(|(maybe_stats, cg)| {
+                        maybe_stats.as_mut().map(|s| (s, cg))
+                    })
+                    .for_each(|(stats, cap_grp)| {
+                        stats[ability] = ability_caps[cap_grp]
+                            .trim_start()
+                            .parse()
+                            .unwrap_or(0); // CBL uses whitespace to mean 0
+                    });
                 } else if let Some(levelup_caps) = LEVELUP_RE.captures(&line) {
                     // Unwrapping because the regexp ensures successful parse
                     let levelup_level =
@@ -1090,12 +1646,15 @@ pub fn parse<R: BufRead>(input: &mut R) -> Result<CharacterBuild, ParseError> {
                         // CBL stores "level 0" ability increases
                         continue;
                     }
-                    if levelup_level % 4 != 0 {
+                    if levelup_level % LEVELUP_INTERVAL as usize != 0
+                        || levelup_level > LEVEL_CAP as usize
+                    {
                         return Err(ParseError::BadLevelupLevel(
                             levelup_level,
                         ));
                     }
-                    let levelup_index = levelup_level / 4 - 1;
+                    let levelup_index =
+                        levelup_level / LEVELUP_INTERVAL as usize - 1;
 
                     let levelup_ability_str = &levelup_caps[2];
                     stat_levelups[levelup_index] =
@@ -1114,7 +1673,7 @@ pub fn parse<R: BufRead>(input: &mut R) -> Result<CharacterBuild, ParseError> {
                         })?;
 
                     let points_str = &skill_caps[2];
-                    let mut points_array = [0; 20];
+                    let mut points_array = [0; HEROIC_LEVEL_CAP as usize];
                     let mut array_is_empty = true;
                     for (i, bs) in
                         points_str.as_bytes().chunks_exact(4).enumerate()
@@ -1160,13 +1719,11 @@ pub fn parse<R: BufRead>(input: &mut R) -> Result<CharacterBuild, ParseError> {
                             } else {
                                 None
                             },
-                        BaseFeatType::Class => secondary_type_str
-                            .parse()
-                            .ok()
+                        BaseFeatType::Class => content
+                            .class_by_name(secondary_type_str)
                             .map(SecondaryFeatType::Class),
-                        BaseFeatType::Race => secondary_type_str
-                            .parse()
-                            .ok()
+                        BaseFeatType::Race => content
+                            .race_by_name(secondary_type_str)
                             .map(SecondaryFeatType::Race),
                     }
                     .ok_or_else(|| {
@@ -1174,12 +1731,30 @@ pub fn parse<R: BufRead>(input: &mut R) -> Result<CharacterBuild, ParseError> {
                             secondary_type_str.to_owned(),
                         )
                     })?;
+                    match secondary_type {
+                        SecondaryFeatType::Class(c) => migrate::record(
+                            migrations,
+                            NameKind::Class,
+                            secondary_type_str,
+                            content.class(c).map(|ci| ci.name.as_str()),
+                        ),
+                        SecondaryFeatType::Race(r) => migrate::record(
+                            migrations,
+                            NameKind::Race,
+                            secondary_type_str,
+                            content.race(r).map(|ri| ri.name.as_str()),
+                        ),
+                        _ => (),
+                    }
 
                     // Unwrapping parse since regexp ensures success
                     let feat_level = feat_caps[3].parse().unwrap();
-                    if feat_level < 1 || feat_level > 30 {
+                    if !(1..=LEVEL_CAP).contains(&feat_level) {
                         return Err(ParseError::InvalidFeatLevel(feat_level));
                     }
+                    if let BaseFeatType::Legend = base_type {
+                        check_legend_feat(&feats, feat_level, max_levels)?;
+                    }
 
                     let feat_name = feat_caps[4].to_owned();
 
@@ -1188,12 +1763,7 @@ pub fn parse<R: BufRead>(input: &mut R) -> Result<CharacterBuild, ParseError> {
                     match base_type {
                         BaseFeatType::Standard =>
                             feats.standard_feats.push(feat),
-                        BaseFeatType::Legend =>
-                            if feats.legend_feats.is_none() {
-                                feats.legend_feats = Some(feat);
-                            } else {
-                                return Err(ParseError::MultipleLegendFeats);
-                            },
+                        BaseFeatType::Legend => feats.legend_feats.push(feat),
                         BaseFeatType::Class => feats.class_feats.push(feat),
                         BaseFeatType::Race => feats.race_feats.push(feat),
                     }
@@ -1201,12 +1771,28 @@ pub fn parse<R: BufRead>(input: &mut R) -> Result<CharacterBuild, ParseError> {
             Heading::Spells =>
                 if let Some(spell_caps) = SPELL_RE.captures(&line) {
                     let class_str = &spell_caps[1];
-                    let class: Class = class_str.parse().map_err(|_| {
-                        ParseError::UnknownClass(class_str.to_owned())
-                    })?;
+                    let class =
+                        content.class_by_name(class_str).ok_or_else(|| {
+                            ParseError::UnknownClass(class_str.to_owned())
+                        })?;
+                    migrate::record(
+                        migrations,
+                        NameKind::Class,
+                        class_str,
+                        content.class(class).map(|ci| ci.name.as_str()),
+                    );
 
                     // Unwrapping parse since regexp guarantees success
                     let spell_level = spell_caps[2].parse().unwrap();
+                    let max_spell_level = content
+                        .class(class)
+                        .map_or(0, |ci| ci.max_spell_level);
+                    if spell_level > max_spell_level {
+                        return Err(ParseError::InvalidSpellLevel(
+                            class,
+                            spell_level,
+                        ));
+                    }
 
                     let spell_name = &spell_caps[3];
                     let spell_name = if spell_name.is_empty() {
@@ -1221,71 +1807,93 @@ pub fn parse<R: BufRead>(input: &mut R) -> Result<CharacterBuild, ParseError> {
                 },
             Heading::Enhancements =>
                 if let Some(tier5_caps) = TIER5_RE.captures(&line) {
+                    // Resolved once the trees are known, as the name may be
+                    // shared by several of them
                     let tier5_str = &tier5_caps[1];
-                    tier_five = Some(tier5_str.parse().map_err(|_| {
-                        ParseError::UnknownEnhancementTree(
+                    if content.tree_by_name(tier5_str).is_none() {
+                        return Err(ParseError::UnknownEnhancementTree(
                             tier5_str.to_owned(),
-                        )
-                    })?);
+                        ));
+                    }
+                    record_tree(migrations, tier5_str);
+                    tier_five_str = Some(tier5_str.to_owned());
                 } else if let Some(tree_caps) = TREE_RE.captures(&line) {
                     let tree_str = &tree_caps[1];
-                    current_tree = Some(tree_str.parse().map_err(|_| {
-                        ParseError::UnknownEnhancementTree(tree_str.to_owned())
-                    })?);
+                    let tree_name =
+                        content.tree_by_name(tree_str).ok_or_else(|| {
+                            ParseError::UnknownEnhancementTree(
+                                tree_str.to_owned(),
+                            )
+                        })?;
+                    record_tree(migrations, tree_str);
+
+                    if let Some(tree) = current_tree.take() {
+                        enhancements.merge(tree);
+                    }
+                    current_tree = Some(EnhancementTree::new(tree_name));
+                    current_tree_str = tree_str.to_owned();
                 } else if let Some(tree_type_caps) =
                     TREE_TYPE_RE.captures(&line)
                 {
-                    match &tree_type_caps[1] {
-                        "Race" =>
-                            if let Some(EnhancementTreeName::Race(_)) =
-                                current_tree
-                            {
-                                Ok(())
-                            } else {
-                                Err(ParseError::WrongEnhancementTreeType)
-                            },
-                        "Class" =>
-                            if let Some(EnhancementTreeName::Class(_)) =
-                                current_tree
-                            {
-                                Ok(())
-                            } else {
-                                Err(ParseError::WrongEnhancementTreeType)
-                            },
-                        "Global" =>
-                            if let Some(EnhancementTreeName::Global(_)) =
-                                current_tree
-                            {
-                                Ok(())
-                            } else {
-                                Err(ParseError::WrongEnhancementTreeType)
-                            },
-                        "RaceClass" =>
-                            if let Some(EnhancementTreeName::RaceClass(_)) =
-                                current_tree
-                            {
-                                Ok(())
-                            } else {
-                                Err(ParseError::WrongEnhancementTreeType)
-                            },
-                        _ => unreachable!(), // Unreachable due to regexp
-                    }?;
+                    // Unwrapping parse since regexp guarantees success
+                    let tree_type = tree_type_caps[1].parse().unwrap();
+                    let tree = current_tree
+                        .as_mut()
+                        .ok_or(ParseError::WrongEnhancementTreeType)?;
+                    tree.name = content
+                        .resolve_tree(
+                            &current_tree_str,
+                            Some(tree_type),
+                            tree.source,
+                        )
+                        .ok_or(ParseError::WrongEnhancementTreeType)?;
                 } else if let Some(source_caps) = SOURCE_RE.captures(&line) {
-                    if &source_caps[1] == "Paladin"
-                        && current_tree
-                            == Some(EnhancementTreeName::Class(
-                                ClassEnhancementTree::VanguardFighter,
-                            ))
-                    {
-                        current_tree = Some(EnhancementTreeName::Class(
-                            ClassEnhancementTree::VanguardPaladin,
+                    let source_str = &source_caps[1];
+                    let source =
+                        content.class_by_name(source_str).ok_or_else(
+                            || ParseError::UnknownClass(source_str.to_owned()),
+                        )?;
+                    migrate::record(
+                        migrations,
+                        NameKind::Class,
+                        source_str,
+                        content.class(source).map(|ci| ci.name.as_str()),
+                    );
+
+                    let tree = current_tree
+                        .as_mut()
+                        .ok_or(ParseError::EnhancementTreeNotDeclared)?;
+                    // Unwrapping since the tree's current name is one of the
+                    // trees that go by `current_tree_str`
+                    let tree_name = content
+                        .resolve_tree(
+                            &current_tree_str,
+                            Some(tree.name.tree_type()),
+                            Some(source),
+                        )
+                        .unwrap();
+                    if content.tree(tree_name).is_some_and(|ti| {
+                        !ti.sources.is_empty() && !ti.sources.contains(&source)
+                    }) {
+                        return Err(ParseError::WrongEnhancementTreeSource(
+                            tree_name, source,
                         ));
                     }
-                } else if CLASSLEVELS_RE.is_match(&line) {
-                    /* Redundant info that I don't want to bother handling */
+
+                    tree.name = tree_name;
+                    tree.source = Some(source);
+                } else if let Some(class_levels_caps) =
+                    CLASSLEVELS_RE.captures(&line)
+                {
+                    // Unwrapping parse since regexp guarantees success
+                    current_tree
+                        .as_mut()
+                        .ok_or(ParseError::EnhancementTreeNotDeclared)?
+                        .class_levels =
+                        Some(class_levels_caps[1].parse().unwrap());
                 } else if let Some(enh_caps) = ENHANCEMENT_RE.captures(&line) {
                     // Unwrapping this parse, as the regexp guarantees success
-                    let tier = enh_caps[1].parse().unwrap();
+                    let tier: usize = enh_caps[1].parse().unwrap();
 
                     let name = enh_caps[2].to_owned();
                     let subenhancement =
@@ -1293,33 +1901,46 @@ pub fn parse<R: BufRead>(input: &mut R) -> Result<CharacterBuild, ParseError> {
                     let rank =
                         enh_caps.get(6).and_then(|c| c.as_str().parse().ok());
 
-                    enhancements.insert(
-                        current_tree
-                            .ok_or(ParseError::EnhancementTreeNotDeclared)?,
-                        tier,
-                        Enhancement::new(name, subenhancement, rank),
-                    );
+                    current_tree
+                        .as_mut()
+                        .ok_or(ParseError::EnhancementTreeNotDeclared)?
+                        .tiers[tier]
+                        .push(Enhancement::new(name, subenhancement, rank));
                 },
         }
     }
 
+    if let Some(tree) = current_tree.take() {
+        enhancements.merge(tree);
+    }
+
+    let tier_five = tier_five_str.and_then(|tier5_str| {
+        let trees = content.trees_by_name(&tier5_str);
+
+        trees
+            .iter()
+            .find(|&&t| enhancements.contains(t))
+            .or_else(|| trees.first())
+            .copied()
+    });
+
     Ok(CharacterBuild {
         // [Overview]
         name,
         race: race.ok_or(ParseError::NoRace)?,
         alignment: alignment.ok_or(ParseError::NoAlignment)?,
         max_levels,
-        levels: if levels.len() == max_levels.min(20) as usize {
+        levels: if levels.len() == max_levels.min(HEROIC_LEVEL_CAP) as usize {
             levels
         } else if levels.is_empty()
             && classes.iter().filter(|c| c.is_some()).count() == 1
         {
             let single_class = classes[0].unwrap();
 
-            vec![single_class; max_levels as usize]
+            vec![single_class; max_levels.min(HEROIC_LEVEL_CAP) as usize]
         } else {
             return Err(ParseError::WrongLevelNumber(
-                max_levels.min(20),
+                max_levels.min(HEROIC_LEVEL_CAP),
                 levels.len(),
             ));
         },
@@ -1347,3 +1968,28 @@ pub fn parse<R: BufRead>(input: &mut R) -> Result<CharacterBuild, ParseError> {
         enhancements,
     })
 }
+
+/// Checks that a legendary feat taken at `level` fills a legendary feat slot
+/// of a build with `max_levels` levels that none of `feats` fills yet.
+fn check_legend_feat(
+    feats: &Feats,
+    level: u8,
+    max_levels: u8,
+) -> Result<(), ParseError> {
+    let slots: Vec<u8> = LEGEND_FEAT_LEVELS
+        .iter()
+        .copied()
+        .filter(|&l| l <= max_levels)
+        .collect();
+    if feats.legend_feats.len() >= slots.len() {
+        return Err(ParseError::TooManyLegendFeats);
+    }
+    if !slots.contains(&level) {
+        return Err(ParseError::InvalidFeatLevel(level));
+    }
+    if feats.legend_feats.iter().any(|f| f.level == level) {
+        return Err(ParseError::DuplicateLegendFeat(level));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,133 @@
+//! Upgrading builds that use names which the game (or CBL) has since changed.
+//!
+//! Races, classes and enhancement trees are upgraded by the parser itself,
+//! since their former names are aliases in the content registry. Feats and
+//! enhancements are stored by name, so they are renamed by `migrate()`.
+//! `parse_and_migrate()` does both, and reports every name that it changed.
+
+use crate::{
+    content::Registry,
+    parse_recording,
+    CharacterBuild,
+    EnhancementTreeName,
+    ParseError,
+};
+use std::{fmt, io::prelude::*};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NameKind {
+    Race,
+    Class,
+    EnhancementTree,
+    Feat,
+    /// An enhancement in the given tree.
+    Enhancement(EnhancementTreeName),
+}
+
+/// A name that was read from a build and replaced by its current form.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Migration {
+    pub kind: NameKind,
+    pub from: String,
+    pub to:   String,
+}
+
+impl fmt::Display for NameKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Self::Race => f.write_str("Race"),
+            Self::Class => f.write_str("Class"),
+            Self::EnhancementTree => f.write_str("Enhancement tree"),
+            Self::Feat => f.write_str("Feat"),
+            Self::Enhancement(t) => write!(f, "Enhancement ({})", t),
+        }
+    }
+}
+
+impl fmt::Display for Migration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{}: {} -> {}", self.kind, self.from, self.to)
+    }
+}
+
+/// Parses a build like `parse_with()`, then renames its feats and
+/// enhancements with `migrate()`. Returns the upgraded build along with every
+/// name that was changed, each listed once, in the order that they were
+/// found.
+pub fn parse_and_migrate<R: BufRead>(
+    input: &mut R,
+    content: &Registry,
+) -> Result<(CharacterBuild, Vec<Migration>), ParseError> {
+    let mut migrations = Vec::new();
+    let mut build = parse_recording(input, content, &mut migrations)?;
+    for m in migrate(&mut build, content) {
+        record(&mut migrations, m.kind, &m.from, Some(&m.to));
+    }
+
+    Ok((build, migrations))
+}
+
+/// Renames every feat and enhancement of `build` that goes by a former name
+/// in `content`. Returns the changes that were made, each listed once.
+pub fn migrate(
+    build: &mut CharacterBuild,
+    content: &Registry,
+) -> Vec<Migration> {
+    let mut migrations = Vec::new();
+
+    let feats = &mut build.feats;
+    for feat in feats
+        .standard_feats
+        .iter_mut()
+        .chain(feats.legend_feats.iter_mut())
+        .chain(feats.class_feats.iter_mut())
+        .chain(feats.race_feats.iter_mut())
+    {
+        if let Some(current) = content.current_feat_name(&feat.name) {
+            record(&mut migrations, NameKind::Feat, &feat.name, Some(current));
+            feat.name = current.to_owned();
+        }
+    }
+
+    for tree in build.enhancements.trees.iter_mut() {
+        let tree_name = tree.name;
+        for enhancement in tree.tiers.iter_mut().flatten() {
+            if let Some(current) =
+                content.current_enhancement_name(tree_name, &enhancement.name)
+            {
+                record(
+                    &mut migrations,
+                    NameKind::Enhancement(tree_name),
+                    &enhancement.name,
+                    Some(current),
+                );
+                enhancement.name = current.to_owned();
+            }
+        }
+    }
+
+    migrations
+}
+
+/// Notes that `from` was read as `to`, unless they are the same name or the
+/// change has already been noted.
+pub(crate) fn record(
+    migrations: &mut Vec<Migration>,
+    kind: NameKind,
+    from: &str,
+    to: Option<&str>,
+) {
+    let to = match to {
+        Some(to) if to != from => to,
+        _ => return,
+    };
+    if migrations.iter().any(|m| m.kind == kind && m.from == from) {
+        return;
+    }
+
+    migrations.push(Migration {
+        kind,
+        from: from.to_owned(),
+        to: to.to_owned(),
+    });
+}
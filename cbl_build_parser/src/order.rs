@@ -0,0 +1,435 @@
+//! Choosing the order to take a build's class levels in.
+//!
+//! Given a class split, e.g. 15 Bard / 4 Fighter / 1 Rogue, and milestones
+//! that the build must reach, e.g. Fighter 4 by level 12 for its bonus feat,
+//! the valid orderings of `CharacterBuild::levels` are listed from best to
+//! worst. Orderings are ranked first by the sum of the character levels that
+//! the milestones are reached at, then by how early the levels of the main
+//! class (the one with the most levels) come, so that its class features
+//! and spells arrive as early as they can.
+
+use crate::{
+    content::{self, Registry},
+    validate::TIER_LEVELS,
+    Class,
+    HEROIC_LEVEL_CAP,
+};
+use fxhash::FxHashMap as HashMap;
+use regex::Regex;
+use std::{cmp::Reverse, collections::BinaryHeap, fmt, str::FromStr};
+
+/// How much reaching a milestone one level earlier is worth, compared to
+/// taking one level of the main class one level earlier.
+const MILESTONE_WEIGHT: u32 = 1_000;
+
+/// Something that a build must have by some character level.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Milestone {
+    /// A class level, e.g. for a class feat or class feature.
+    ClassLevel {
+        class:       Class,
+        class_level: u8,
+        by_level:    u8,
+    },
+    /// The first spells of a spell level.
+    SpellLevel {
+        class:       Class,
+        spell_level: usize,
+        by_level:    u8,
+    },
+    /// Tier 5 of one of the class's enhancement trees.
+    TierFive { class: Class, by_level: u8 },
+    /// The class taken at level 1, e.g. Rogue for trapping skills.
+    FirstLevel(Class),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OrderError {
+    InvalidSplit(String),
+    InvalidMilestone(String),
+    TooManyClasses,
+    /// The split has no levels, or more than `HEROIC_LEVEL_CAP`.
+    InvalidLevelCount(u16),
+    NoSuchSpellLevel(Class, usize),
+    /// The split doesn't have the class levels that the milestone needs.
+    Unreachable(Milestone),
+    /// No ordering reaches every milestone in time.
+    Unsatisfiable,
+}
+
+impl fmt::Display for Milestone {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Self::ClassLevel {
+                class,
+                class_level,
+                by_level,
+            } => write!(f, "{} {} by {}", class, class_level, by_level),
+            Self::SpellLevel {
+                class,
+                spell_level,
+                by_level,
+            } => write!(f, "{} spells {} by {}", class, spell_level, by_level),
+            Self::TierFive { class, by_level } =>
+                write!(f, "{} tier 5 by {}", class, by_level),
+            Self::FirstLevel(class) => write!(f, "{} first", class),
+        }
+    }
+}
+
+impl FromStr for Milestone {
+    type Err = OrderError;
+
+    /// Parses milestones written as "Fighter 4 by 12", "Bard spells 3 by 10",
+    /// "Bard tier 5 by 12" or "Rogue first".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        lazy_static! {
+            static ref SPELL_LEVEL_RE: Regex =
+                Regex::new(r"^(.+?)\s+spells\s+([1-9])\s+by\s+(\d+)$")
+                    .unwrap();
+            static ref TIER_FIVE_RE: Regex =
+                Regex::new(r"^(.+?)\s+tier\s+5\s+by\s+(\d+)$").unwrap();
+            static ref CLASS_LEVEL_RE: Regex =
+                Regex::new(r"^(.+?)\s+(\d+)\s+by\s+(\d+)$").unwrap();
+            static ref FIRST_LEVEL_RE: Regex =
+                Regex::new(r"^(.+?)\s+first$").unwrap();
+        }
+
+        let invalid = || OrderError::InvalidMilestone(s.to_owned());
+        let class = |name: &str| name.parse::<Class>().map_err(|_| invalid());
+        let number = |n: &str| n.parse::<u8>().map_err(|_| invalid());
+        let s = s.trim();
+
+        if let Some(caps) = SPELL_LEVEL_RE.captures(s) {
+            Ok(Self::SpellLevel {
+                class:       class(&caps[1])?,
+                spell_level: caps[2].parse().unwrap(),
+                by_level:    number(&caps[3])?,
+            })
+        } else if let Some(caps) = TIER_FIVE_RE.captures(s) {
+            Ok(Self::TierFive {
+                class:    class(&caps[1])?,
+                by_level: number(&caps[2])?,
+            })
+        } else if let Some(caps) = CLASS_LEVEL_RE.captures(s) {
+            Ok(Self::ClassLevel {
+                class:       class(&caps[1])?,
+                class_level: number(&caps[2])?,
+                by_level:    number(&caps[3])?,
+            })
+        } else if let Some(caps) = FIRST_LEVEL_RE.captures(s) {
+            Ok(Self::FirstLevel(class(&caps[1])?))
+        } else {
+            Err(invalid())
+        }
+    }
+}
+
+impl fmt::Display for OrderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Self::InvalidSplit(s) => write!(f, "Invalid class split: {}", s),
+            Self::InvalidMilestone(s) => write!(f, "Invalid milestone: {}", s),
+            Self::TooManyClasses =>
+                f.write_str("More than three classes specified"),
+            Self::InvalidLevelCount(n) => write!(
+                f,
+                "A class split must have 1 to {} levels, not {}",
+                HEROIC_LEVEL_CAP, n,
+            ),
+            Self::NoSuchSpellLevel(c, l) =>
+                write!(f, "{} never learns level {} spells", c, l),
+            Self::Unreachable(m) =>
+                write!(f, "The class split can't reach {}", m),
+            Self::Unsatisfiable => f.write_str(
+                "No order of the class levels reaches every milestone in time",
+            ),
+        }
+    }
+}
+
+impl std::error::Error for OrderError {}
+
+/// Parses a class split written as "15 Bard/4 Fighter/1 Rogue".
+pub fn parse_split(s: &str) -> Result<Vec<(Class, u8)>, OrderError> {
+    lazy_static! {
+        static ref PART_RE: Regex = Regex::new(r"^(\d+)\s+(.+)$").unwrap();
+    }
+
+    let mut split: Vec<(Class, u8)> = Vec::with_capacity(3);
+    for part in s.split('/') {
+        let invalid = || OrderError::InvalidSplit(part.trim().to_owned());
+        let caps = PART_RE.captures(part.trim()).ok_or_else(invalid)?;
+        let levels = caps[1].parse::<u8>().map_err(|_| invalid())?;
+        let class = caps[2].parse::<Class>().map_err(|_| invalid())?;
+
+        match split.iter_mut().find(|(c, _)| *c == class) {
+            Some((_, n)) => *n = n.saturating_add(levels),
+            None => split.push((class, levels)),
+        }
+    }
+
+    Ok(split)
+}
+
+/// Writes a class split as "15 Bard/4 Fighter/1 Rogue", as read by
+/// `parse_split()`.
+pub fn format_split(split: &[(Class, u8)]) -> String {
+    split
+        .iter()
+        .map(|(class, n)| format!("{} {}", n, class))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// One ordering of a class split's levels.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RankedOrder {
+    /// The class of each level, as in `CharacterBuild::levels`.
+    pub levels:           Vec<Class>,
+    /// The character level that each milestone is reached at, in the order
+    /// that the milestones were given.
+    pub milestone_levels: Vec<u8>,
+    /// Lower is better.
+    pub score:            u32,
+}
+
+/// The valid orderings of a class split, from best to worst; see
+/// `orderings()`.
+#[derive(Clone, Debug)]
+pub struct Orderings {
+    classes:     Vec<Class>,
+    split:       Vec<u8>,
+    /// The milestones, as (index into `classes`, class level, by level).
+    targets:     Vec<(usize, u8, u8)>,
+    main_class:  usize,
+    /// The best score that the rest of an ordering can add, for the levels
+    /// taken so far in each class. `None` if the milestones can't be met.
+    completions: HashMap<Vec<u8>, Option<u32>>,
+    /// Partial orderings, as (best final score, class indices, score so far).
+    queue:       BinaryHeap<Reverse<(u32, Vec<usize>, u32)>>,
+}
+
+/// Lists the orderings of `split` that reach every milestone in time, using
+/// the game content of `content::registry()`.
+pub fn orderings(
+    split: &[(Class, u8)],
+    milestones: &[Milestone],
+) -> Result<Orderings, OrderError> {
+    orderings_with(split, milestones, &content::registry())
+}
+
+/// Lists the orderings of `split` that reach every milestone in time,
+/// according to `content`. Fails if the split or milestones are invalid, but
+/// not if no ordering is valid; the iterator is just empty then.
+pub fn orderings_with(
+    split: &[(Class, u8)],
+    milestones: &[Milestone],
+    content: &Registry,
+) -> Result<Orderings, OrderError> {
+    let mut classes: Vec<Class> = Vec::with_capacity(3);
+    let mut counts: Vec<u8> = Vec::with_capacity(3);
+    for &(class, levels) in split.iter().filter(|(_, n)| *n > 0) {
+        match classes.iter().position(|&c| c == class) {
+            Some(i) => counts[i] = counts[i].saturating_add(levels),
+            None => {
+                classes.push(class);
+                counts.push(levels);
+            },
+        }
+    }
+    if classes.len() > 3 {
+        return Err(OrderError::TooManyClasses);
+    }
+    let total: u16 = counts.iter().map(|&n| u16::from(n)).sum();
+    if total == 0 || total > u16::from(HEROIC_LEVEL_CAP) {
+        return Err(OrderError::InvalidLevelCount(total));
+    }
+
+    let mut targets = Vec::with_capacity(milestones.len());
+    for &milestone in milestones.iter() {
+        let (class, class_level, by_level) = match milestone {
+            Milestone::ClassLevel {
+                class,
+                class_level,
+                by_level,
+            } => (class, class_level, by_level),
+            Milestone::SpellLevel {
+                class,
+                spell_level,
+                by_level,
+            } => {
+                let unlock = spell_level
+                    .checked_sub(1)
+                    .and_then(|i| content.class(class)?.spell_unlocks.get(i))
+                    .ok_or(OrderError::NoSuchSpellLevel(class, spell_level))?;
+
+                (class, *unlock, by_level)
+            },
+            Milestone::TierFive { class, by_level } =>
+                (class, TIER_LEVELS[5], by_level),
+            Milestone::FirstLevel(class) => (class, 1, 1),
+        };
+
+        let i = classes
+            .iter()
+            .position(|&c| c == class)
+            .filter(|&i| class_level >= 1 && class_level <= counts[i])
+            .ok_or(OrderError::Unreachable(milestone))?;
+        targets.push((i, class_level, by_level));
+    }
+
+    // The first of the classes with the most levels.
+    let main_class =
+        (0..classes.len()).rev().max_by_key(|&i| counts[i]).unwrap();
+
+    let mut orderings = Orderings {
+        classes,
+        split: counts,
+        targets,
+        main_class,
+        completions: HashMap::default(),
+        queue: BinaryHeap::new(),
+    };
+    let start = vec![0; orderings.split.len()];
+    if let Some(best) = orderings.completion(&start) {
+        orderings.queue.push(Reverse((best, Vec::new(), 0)));
+    }
+
+    Ok(orderings)
+}
+
+/// Returns the best ordering of `split` that reaches every milestone in
+/// time, using the game content of `content::registry()`.
+pub fn best_order(
+    split: &[(Class, u8)],
+    milestones: &[Milestone],
+) -> Result<RankedOrder, OrderError> {
+    orderings(split, milestones)?
+        .next()
+        .ok_or(OrderError::Unsatisfiable)
+}
+
+impl Orderings {
+    /// What taking a level of `classes[class]` adds to the score, after the
+    /// levels in `taken`, or `None` if it would miss a milestone.
+    fn step(&self, taken: &[u8], class: usize) -> Option<u32> {
+        let level = taken.iter().map(|&n| u32::from(n)).sum::<u32>() + 1;
+        let class_level = taken[class] + 1;
+
+        let mut score = 0;
+        for &(c, l, by_level) in self.targets.iter() {
+            if c == class && l == class_level {
+                if level > u32::from(by_level) {
+                    return None;
+                }
+                score += level * MILESTONE_WEIGHT;
+            }
+        }
+        if class == self.main_class {
+            score += level;
+        }
+
+        Some(score)
+    }
+
+    fn completion(&mut self, taken: &[u8]) -> Option<u32> {
+        if let Some(&best) = self.completions.get(taken) {
+            return best;
+        }
+
+        let mut best = None;
+        if taken == &self.split[..] {
+            best = Some(0);
+        } else {
+            let mut next = taken.to_vec();
+            for class in 0..self.split.len() {
+                if taken[class] == self.split[class] {
+                    continue;
+                }
+                let step = match self.step(taken, class) {
+                    Some(step) => step,
+                    None => continue,
+                };
+
+                next[class] += 1;
+                if let Some(rest) = self.completion(&next) {
+                    best = Some(
+                        best.map_or(step + rest, |b: u32| b.min(step + rest)),
+                    );
+                }
+                next[class] -= 1;
+            }
+        }
+
+        self.completions.insert(taken.to_vec(), best);
+        best
+    }
+
+    fn ranked_order(&self, order: &[usize], score: u32) -> RankedOrder {
+        let milestone_levels = self
+            .targets
+            .iter()
+            .map(|&(class, class_level, _)| {
+                let i = order
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, &c)| c == class)
+                    .nth(usize::from(class_level) - 1)
+                    .map(|(i, _)| i)
+                    .unwrap();
+
+                i as u8 + 1
+            })
+            .collect();
+
+        RankedOrder {
+            levels: order.iter().map(|&c| self.classes[c]).collect(),
+            milestone_levels,
+            score,
+        }
+    }
+}
+
+impl Iterator for Orderings {
+    type Item = RankedOrder;
+
+    fn next(&mut self) -> Option<RankedOrder> {
+        // The completions are exact, so partial orderings come off the queue
+        // in the order of their best final score, and the first complete one
+        // is the best one left.
+        while let Some(Reverse((_, order, score))) = self.queue.pop() {
+            let mut taken = vec![0; self.split.len()];
+            for &c in order.iter() {
+                taken[c] += 1;
+            }
+            if taken == self.split {
+                return Some(self.ranked_order(&order, score));
+            }
+
+            for class in 0..self.split.len() {
+                if taken[class] == self.split[class] {
+                    continue;
+                }
+                let step = match self.step(&taken, class) {
+                    Some(step) => step,
+                    None => continue,
+                };
+
+                taken[class] += 1;
+                if let Some(rest) = self.completion(&taken) {
+                    let mut next = order.clone();
+                    next.push(class);
+                    self.queue.push(Reverse((
+                        score + step + rest,
+                        next,
+                        score + step,
+                    )));
+                }
+                taken[class] -= 1;
+            }
+        }
+
+        None
+    }
+}
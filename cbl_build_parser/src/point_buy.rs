@@ -0,0 +1,287 @@
+//! Spending build points on abilities to meet a list of goals.
+
+use crate::{
+    content::{self, Registry},
+    snapshot::{BASE_ABILITY_SCORE, SCORE_COSTS},
+    Ability,
+    BuildType,
+    CharacterBuild,
+    Race,
+    Stats,
+    LEVELUP_COUNT,
+};
+use std::fmt;
+
+/// What the ability scores should look like, counting racial modifiers.
+/// Goals are met in the order that they are listed, except that every
+/// `AtLeast` goal is met before any `Maximize` goal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AbilityGoal {
+    /// As high as the build points allow.
+    Maximize(Ability),
+    /// At least the given score.
+    AtLeast(Ability, u8),
+    /// No build points at all.
+    Dump(Ability),
+}
+
+/// Why a point buy falls short of its goals, or what it would cost to do
+/// better.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Tradeoff {
+    /// An `AtLeast` goal couldn't be met, because of the build points or
+    /// because the score can't be bought that high.
+    TargetMissed {
+        ability: Ability,
+        target:  u8,
+        reached: u8,
+    },
+    /// A `Maximize` goal stopped short of the highest score that can be
+    /// bought; raising it to `score` would take `cost` more build points.
+    NextScore {
+        ability: Ability,
+        score:   u8,
+        cost:    u8,
+    },
+    /// Build points that nothing could be raised with.
+    Unspent(u16),
+}
+
+/// The build points to spend on each ability for one build type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PointBuy {
+    pub build_type: BuildType,
+    pub stats:      Stats,
+    pub tradeoffs:  Vec<Tradeoff>,
+}
+
+impl fmt::Display for Tradeoff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Self::TargetMissed {
+                ability,
+                target,
+                reached,
+            } => write!(
+                f,
+                "{} only reaches {}, short of the {} wanted",
+                ability, reached, target,
+            ),
+            Self::NextScore {
+                ability,
+                score,
+                cost,
+            } => write!(
+                f,
+                "{} {} would take {} more build points",
+                ability, score, cost,
+            ),
+            Self::Unspent(points) =>
+                write!(f, "{} build points are left unspent", points),
+        }
+    }
+}
+
+/// Spends the build points of `build_type` on the goals, for a character of
+/// `race`, using the game content of `content::registry()`.
+pub fn point_buy(
+    race: Race,
+    build_type: BuildType,
+    goals: &[AbilityGoal],
+) -> PointBuy {
+    point_buy_with(race, build_type, goals, &content::registry())
+}
+
+/// Spends the build points of `build_type` on the goals, for a character of
+/// `race`, according to `content`. Points left over after the goals go to
+/// the abilities without a goal, one score at a time, wherever the next
+/// score is cheapest (and lowest, when it costs the same).
+pub fn point_buy_with(
+    race: Race,
+    build_type: BuildType,
+    goals: &[AbilityGoal],
+    content: &Registry,
+) -> PointBuy {
+    let modifiers = content
+        .race(race)
+        .map_or(&[][..], |ri| &ri.ability_modifiers);
+    let modifier = |ability| -> i16 {
+        modifiers
+            .iter()
+            .filter(|(a, _)| *a == ability)
+            .map(|(_, m)| i16::from(*m))
+            .sum()
+    };
+    let score = |ability, raised: usize| {
+        (i16::from(BASE_ABILITY_SCORE) + raised as i16 + modifier(ability))
+            .clamp(0, i16::from(u8::MAX)) as u8
+    };
+    let max_raised = SCORE_COSTS.len() - 1;
+
+    // How far each ability is raised above `BASE_ABILITY_SCORE`, in the
+    // order of `Ability::ALL`.
+    let mut raised = [0usize; 6];
+    let index =
+        |ability| Ability::ALL.iter().position(|&a| a == ability).unwrap();
+    let mut budget = build_type.build_points();
+    let mut tradeoffs = Vec::new();
+
+    for goal in goals.iter() {
+        if let AbilityGoal::AtLeast(ability, target) = *goal {
+            let i = index(ability);
+            let needed = i16::from(target)
+                - i16::from(BASE_ABILITY_SCORE)
+                - modifier(ability);
+            let to = needed.clamp(0, max_raised as i16) as usize;
+            raise(&mut raised, &mut budget, i, to);
+
+            let reached = score(ability, raised[i]);
+            if reached < target {
+                tradeoffs.push(Tradeoff::TargetMissed {
+                    ability,
+                    target,
+                    reached,
+                });
+            }
+        }
+    }
+    for goal in goals.iter() {
+        if let AbilityGoal::Maximize(ability) = *goal {
+            let i = index(ability);
+            raise(&mut raised, &mut budget, i, max_raised);
+
+            if raised[i] < max_raised {
+                tradeoffs.push(Tradeoff::NextScore {
+                    ability,
+                    score: score(ability, raised[i] + 1),
+                    cost: (u16::from(SCORE_COSTS[raised[i] + 1])
+                        - u16::from(SCORE_COSTS[raised[i]])
+                        - budget) as u8,
+                });
+            }
+        }
+    }
+
+    let free: Vec<usize> = Ability::ALL
+        .iter()
+        .filter(|&&a| !goals.iter().any(|g| goal_ability(*g) == a))
+        .map(|&a| index(a))
+        .collect();
+    while let Some(i) = free
+        .iter()
+        .copied()
+        .filter(|&i| raised[i] < max_raised)
+        .min_by_key(|&i| {
+            (
+                SCORE_COSTS[raised[i] + 1] - SCORE_COSTS[raised[i]],
+                raised[i],
+            )
+        })
+    {
+        let before = raised[i];
+        raise(&mut raised, &mut budget, i, before + 1);
+        if raised[i] == before {
+            break;
+        }
+    }
+    if budget > 0 {
+        tradeoffs.push(Tradeoff::Unspent(budget));
+    }
+
+    let mut stats = Stats::default();
+    for (i, &ability) in Ability::ALL.iter().enumerate() {
+        stats[ability] = SCORE_COSTS[raised[i]];
+    }
+
+    PointBuy {
+        build_type,
+        stats,
+        tradeoffs,
+    }
+}
+
+/// Suggests where the ability score increases go: into the first ability to
+/// maximize or, if there is none, the first one with a target. `None` if no
+/// goal asks for more of an ability.
+pub fn suggested_levelups(
+    goals: &[AbilityGoal],
+) -> Option<[Option<Ability>; LEVELUP_COUNT]> {
+    let ability = goals
+        .iter()
+        .find_map(|g| match *g {
+            AbilityGoal::Maximize(a) => Some(a),
+            _ => None,
+        })
+        .or_else(|| {
+            goals.iter().find_map(|g| match *g {
+                AbilityGoal::AtLeast(a, _) => Some(a),
+                _ => None,
+            })
+        })?;
+
+    Some([Some(ability); LEVELUP_COUNT])
+}
+
+/// Raises `raised[i]` as far as `to`, or as far as the budget allows.
+fn raise(raised: &mut [usize; 6], budget: &mut u16, i: usize, to: usize) {
+    while raised[i] < to {
+        let step =
+            u16::from(SCORE_COSTS[raised[i] + 1] - SCORE_COSTS[raised[i]]);
+        if step > *budget {
+            break;
+        }
+        *budget -= step;
+        raised[i] += 1;
+    }
+}
+
+fn goal_ability(goal: AbilityGoal) -> Ability {
+    match goal {
+        AbilityGoal::Maximize(a)
+        | AbilityGoal::AtLeast(a, _)
+        | AbilityGoal::Dump(a) => a,
+    }
+}
+
+impl CharacterBuild {
+    /// Rebuys the stats of every enabled build type for the build's race,
+    /// e.g. after the race was changed, using the game content of
+    /// `content::registry()`.
+    pub fn regenerate_stats(
+        &mut self,
+        goals: &[AbilityGoal],
+    ) -> Vec<PointBuy> {
+        self.regenerate_stats_with(goals, &content::registry())
+    }
+
+    /// Rebuys the stats of every enabled build type for the build's race,
+    /// according to `content`, and puts the ability score increases where
+    /// `suggested_levelups()` suggests, if anywhere. Returns the point buy of
+    /// each enabled build type.
+    pub fn regenerate_stats_with(
+        &mut self,
+        goals: &[AbilityGoal],
+        content: &Registry,
+    ) -> Vec<PointBuy> {
+        let mut point_buys = Vec::with_capacity(BuildType::ALL.len());
+        for &build_type in BuildType::ALL.iter() {
+            let stats = match build_type {
+                BuildType::Adventurer => &mut self.adventurer_stats,
+                BuildType::Champion => &mut self.champion_stats,
+                BuildType::Hero => &mut self.hero_stats,
+                BuildType::Legend => &mut self.legend_stats,
+            };
+            if let Some(stats) = stats {
+                let point_buy =
+                    point_buy_with(self.race, build_type, goals, content);
+                *stats = point_buy.stats.clone();
+                point_buys.push(point_buy);
+            }
+        }
+        if let Some(levelups) = suggested_levelups(goals) {
+            self.stat_levelups = levelups;
+        }
+
+        point_buys
+    }
+}
@@ -0,0 +1,996 @@
+//! Share codes: a whole build packed into a short string that can be pasted
+//! into chat or a URL.
+//!
+//! A code is its bytes in unpadded URL-safe base64. The first byte is the
+//! version of the layout, so that a code keeps decoding after the layout
+//! changes; `encode()` always writes the latest version. After it comes a
+//! table of every free-form string in the build (its name and the names of
+//! its feats, spells and enhancements), each written once, and then the
+//! build itself, with races, classes, skills and trees written as their
+//! indices in the tables below and strings as their indices in the string
+//! table. Races, classes and trees that content files added (the `Other`
+//! variants) are written as their `Id` instead. Everything after the version
+//! is compressed with raw DEFLATE, which makes a code almost half as long.
+//!
+//! The tables below may only be appended to: a code holds indices into
+//! them, so reordering them would change the meaning of old codes.
+
+use crate::{
+    content::ContentId,
+    Ability,
+    Alignment,
+    BuildType,
+    CharacterBuild,
+    Class,
+    ClassEnhancementTree,
+    Enhancement,
+    EnhancementTree,
+    EnhancementTreeName,
+    Enhancements,
+    Feat,
+    Feats,
+    GlobalEnhancementTree,
+    Race,
+    RaceClassEnhancementTree,
+    SecondaryFeatType,
+    Skill,
+    Skills,
+    Spellbook,
+    Spells,
+    Stats,
+    LEVELUP_COUNT,
+};
+use fxhash::FxHashMap as HashMap;
+use miniz_oxide::{
+    deflate::compress_to_vec,
+    inflate::{decompress_to_vec_with_limit, TINFLStatus},
+};
+use std::{convert::TryFrom, fmt, num::NonZeroU8};
+
+/// The version of the layout that `encode()` writes.
+pub const VERSION: u8 = 1;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+const ALIGNMENTS: [Alignment; 6] = [
+    Alignment::LawfulGood,
+    Alignment::LawfulNeutral,
+    Alignment::NeutralGood,
+    Alignment::TrueNeutral,
+    Alignment::ChaoticGood,
+    Alignment::ChaoticNeutral,
+];
+const BUILD_TYPES: [BuildType; 4] = [
+    BuildType::Adventurer,
+    BuildType::Champion,
+    BuildType::Hero,
+    BuildType::Legend,
+];
+const ABILITIES: [Ability; 6] = [
+    Ability::Str,
+    Ability::Dex,
+    Ability::Con,
+    Ability::Int,
+    Ability::Wis,
+    Ability::Cha,
+];
+const RACES: [Race; 28] = [
+    Race::Aasimar,
+    Race::AasimarScourge,
+    Race::Bladeforged,
+    Race::DeepGnome,
+    Race::Dhampir,
+    Race::DhampirDarkBargainer,
+    Race::Dragonborn,
+    Race::Drow,
+    Race::Dwarf,
+    Race::Eladrin,
+    Race::EladrinChaosmancer,
+    Race::Elf,
+    Race::Gnome,
+    Race::HalfElf,
+    Race::Halfling,
+    Race::HalfOrc,
+    Race::Human,
+    Race::Morninglord,
+    Race::PurpleDragonKnight,
+    Race::RazorclawShifter,
+    Race::ShadarKai,
+    Race::Shifter,
+    Race::Tabaxi,
+    Race::TabaxiTrailblazer,
+    Race::Tiefling,
+    Race::TieflingScoundrel,
+    Race::Warforged,
+    Race::WoodElf,
+];
+const CLASSES: [Class; 15] = [
+    Class::Alchemist,
+    Class::Artificer,
+    Class::Barbarian,
+    Class::Bard,
+    Class::Cleric,
+    Class::Druid,
+    Class::FavoredSoul,
+    Class::Fighter,
+    Class::Monk,
+    Class::Paladin,
+    Class::Ranger,
+    Class::Rogue,
+    Class::Sorcerer,
+    Class::Warlock,
+    Class::Wizard,
+];
+const SKILLS: [Skill; 21] = [
+    Skill::Balance,
+    Skill::Bluff,
+    Skill::Concentration,
+    Skill::Diplomacy,
+    Skill::DisableDevice,
+    Skill::Haggle,
+    Skill::Heal,
+    Skill::Hide,
+    Skill::Intimidate,
+    Skill::Jump,
+    Skill::Listen,
+    Skill::MoveSilently,
+    Skill::OpenLock,
+    Skill::Perform,
+    Skill::Repair,
+    Skill::Search,
+    Skill::Spellcraft,
+    Skill::Spot,
+    Skill::Swim,
+    Skill::Tumble,
+    Skill::UseMagicDevice,
+];
+const CLASS_TREES: [ClassEnhancementTree; 51] = [
+    ClassEnhancementTree::Apothecary,
+    ClassEnhancementTree::Bombardier,
+    ClassEnhancementTree::VileChemist,
+    ClassEnhancementTree::Arcanotechnician,
+    ClassEnhancementTree::BattleEngineer,
+    ClassEnhancementTree::RenegadeMastermaker,
+    ClassEnhancementTree::FrenziedBerserker,
+    ClassEnhancementTree::OccultSlayer,
+    ClassEnhancementTree::Ravager,
+    ClassEnhancementTree::Swashbuckler,
+    ClassEnhancementTree::Spellsinger,
+    ClassEnhancementTree::Stormsinger,
+    ClassEnhancementTree::Warchanter,
+    ClassEnhancementTree::DarkApostate,
+    ClassEnhancementTree::DivineDisciple,
+    ClassEnhancementTree::RadiantServant,
+    ClassEnhancementTree::Warpriest,
+    ClassEnhancementTree::Blightcaster,
+    ClassEnhancementTree::NaturesWarrior,
+    ClassEnhancementTree::NaturesProtector,
+    ClassEnhancementTree::SeasonsHerald,
+    ClassEnhancementTree::AngelOfVengeance,
+    ClassEnhancementTree::BeaconOfHope,
+    ClassEnhancementTree::WarSoul,
+    ClassEnhancementTree::Kensei,
+    ClassEnhancementTree::StalwartDefender,
+    ClassEnhancementTree::Vanguard,
+    ClassEnhancementTree::HenshinMystic,
+    ClassEnhancementTree::NinjaSpy,
+    ClassEnhancementTree::Shintao,
+    ClassEnhancementTree::KnightOfTheChalice,
+    ClassEnhancementTree::SacredDefender,
+    ClassEnhancementTree::ArcaneArcher,
+    ClassEnhancementTree::DarkHunter,
+    ClassEnhancementTree::DeepwoodStalker,
+    ClassEnhancementTree::Tempest,
+    ClassEnhancementTree::Assassin,
+    ClassEnhancementTree::Mechanic,
+    ClassEnhancementTree::ThiefAcrobat,
+    ClassEnhancementTree::AirSavant,
+    ClassEnhancementTree::EarthSavant,
+    ClassEnhancementTree::EldritchKnight,
+    ClassEnhancementTree::FireSavant,
+    ClassEnhancementTree::WaterSavant,
+    ClassEnhancementTree::WildMage,
+    ClassEnhancementTree::AcolyteOfTheSkin,
+    ClassEnhancementTree::EnlightenedSpirit,
+    ClassEnhancementTree::SoulEater,
+    ClassEnhancementTree::TaintedScholar,
+    ClassEnhancementTree::Archmage,
+    ClassEnhancementTree::PaleMaster,
+];
+const GLOBAL_TREES: [GlobalEnhancementTree; 5] = [
+    GlobalEnhancementTree::HarperAgent,
+    GlobalEnhancementTree::Falconry,
+    GlobalEnhancementTree::VistaniKnifeFighter,
+    GlobalEnhancementTree::Inquisitive,
+    GlobalEnhancementTree::FeydarkIllusionist,
+];
+const RACE_CLASS_TREES: [RaceClassEnhancementTree; 1] =
+    [RaceClassEnhancementTree::ElfArcaneArcher];
+
+/// Why a share code couldn't be decoded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ShareCodeError {
+    /// A character that isn't in the URL-safe base64 alphabet.
+    InvalidCharacter(char),
+    /// A version that is newer than this crate knows.
+    UnknownVersion(u8),
+    /// The code ends before the build does, e.g. because it was cut short
+    /// when it was copied.
+    Truncated,
+    /// An index that is out of the range of its table, e.g. a race that
+    /// this crate doesn't know; what the index is of, and the index.
+    OutOfRange(&'static str, u32),
+    /// A string in the string table isn't UTF-8.
+    InvalidString,
+    /// The code goes on after the end of the build.
+    TrailingData,
+    /// The body of a compressed code isn't valid DEFLATE.
+    InvalidCompression,
+}
+
+/// Packs `build` into a share code.
+pub fn encode(build: &CharacterBuild) -> String {
+    let mut w = Writer::default();
+
+    w.string(&build.name);
+    w.content_index(&RACES, build.race);
+    w.index(&ALIGNMENTS, build.alignment);
+    w.byte(build.max_levels);
+    w.length(build.levels.len());
+    for &class in build.levels.iter() {
+        w.content_index(&CLASSES, class);
+    }
+
+    // [Stats]
+    w.index(&BUILD_TYPES, build.preferred_build_type);
+    let enabled = BUILD_TYPES
+        .iter()
+        .enumerate()
+        .filter(|&(_, &bt)| build.stats(bt).is_some())
+        .fold(0, |mask, (i, _)| mask | 1 << i);
+    w.byte(enabled);
+    for stats in BUILD_TYPES.iter().filter_map(|&bt| build.stats(bt)) {
+        w.stats(stats);
+    }
+    w.stats(&build.stat_tomes);
+    w.length(build.stat_levelups.len());
+    for levelup in build.stat_levelups.iter() {
+        w.optional_index(&ABILITIES, *levelup);
+    }
+
+    // [Skills]
+    let points = SKILLS
+        .iter()
+        .filter_map(|&s| Some((s, build.skills.points_in_skill(s)?)))
+        .collect::<Vec<_>>();
+    w.length(points.len());
+    for (skill, points) in points {
+        w.index(&SKILLS, skill);
+        w.bytes.extend_from_slice(points);
+    }
+    let tomes = SKILLS
+        .iter()
+        .filter_map(|s| Some((*s, *build.skills.skill_tomes.get(s)?)))
+        .collect::<Vec<_>>();
+    w.length(tomes.len());
+    for (skill, tome) in tomes {
+        w.index(&SKILLS, skill);
+        w.byte(tome);
+    }
+
+    // [Feats]
+    let feats = &build.feats;
+    for list in [
+        &feats.standard_feats,
+        &feats.legend_feats,
+        &feats.class_feats,
+        &feats.race_feats,
+    ] {
+        w.length(list.len());
+        for feat in list.iter() {
+            w.feat_type(feat.secondary_type);
+            w.byte(feat.level);
+            w.string(&feat.name);
+        }
+    }
+
+    // [Spells]
+    for spellbook in build.spells.spellbooks.iter() {
+        w.optional_content_index(
+            &CLASSES,
+            spellbook.as_ref().map(|sb| sb.class),
+        );
+        if let Some(sb) = spellbook {
+            w.length(sb.spells_by_level.len());
+            for spells in sb.spells_by_level.iter() {
+                w.length(spells.len());
+                for spell in spells.iter() {
+                    w.optional_string(spell.as_deref());
+                }
+            }
+        }
+    }
+
+    // [Enhancements]
+    match build.tier_five {
+        Some(tree) => {
+            w.byte(1);
+            w.tree(tree);
+        },
+        None => w.byte(0),
+    }
+    w.length(build.enhancements.trees.len());
+    for tree in build.enhancements.trees.iter() {
+        w.tree(tree.name);
+        w.optional_content_index(&CLASSES, tree.source);
+        w.varint(tree.class_levels.map_or(0, |n| u32::from(n) + 1));
+        for enhancements in tree.tiers.iter() {
+            w.length(enhancements.len());
+            for e in enhancements.iter() {
+                w.string(&e.name);
+                w.optional_string(e.subenhancement.as_deref());
+                w.byte(e.rank.map_or(0, NonZeroU8::get));
+            }
+        }
+    }
+
+    to_base64(&w.finish())
+}
+
+/// Unpacks a share code.
+pub fn decode(code: &str) -> Result<CharacterBuild, ShareCodeError> {
+    let bytes = from_base64(code.trim())?;
+    let (&version, body) =
+        bytes.split_first().ok_or(ShareCodeError::Truncated)?;
+
+    match version {
+        VERSION => decode_body(&inflate(body)?),
+        v => Err(ShareCodeError::UnknownVersion(v)),
+    }
+}
+
+/// Decompresses the body of a code.
+fn inflate(body: &[u8]) -> Result<Vec<u8>, ShareCodeError> {
+    // Far more than any build needs, so that a small code can't make a huge
+    // body
+    const MAX_BODY_LEN: usize = 1 << 20;
+
+    decompress_to_vec_with_limit(body, MAX_BODY_LEN).map_err(|e| {
+        match e.status {
+            TINFLStatus::FailedCannotMakeProgress => ShareCodeError::Truncated,
+            _ => ShareCodeError::InvalidCompression,
+        }
+    })
+}
+
+fn decode_body(body: &[u8]) -> Result<CharacterBuild, ShareCodeError> {
+    let mut r = Reader::new(body)?;
+
+    let name = r.string()?;
+    let race = r.content_index(&RACES, "race")?;
+    let alignment = r.index(&ALIGNMENTS, "alignment")?;
+    let max_levels = r.byte()?;
+    let levels = (0..r.varint()?)
+        .map(|_| r.content_index(&CLASSES, "class"))
+        .collect::<Result<_, _>>()?;
+
+    // [Stats]
+    let preferred_build_type = r.index(&BUILD_TYPES, "build type")?;
+    let enabled = r.byte()?;
+    let mut stats = [None, None, None, None];
+    for (i, s) in stats.iter_mut().enumerate() {
+        if enabled & 1 << i != 0 {
+            *s = Some(r.stats()?);
+        }
+    }
+    let [adventurer_stats, champion_stats, hero_stats, legend_stats] = stats;
+    let stat_tomes = r.stats()?;
+    let mut stat_levelups = [None; LEVELUP_COUNT];
+    for i in 0..r.varint()? as usize {
+        let levelup = r.optional_index(&ABILITIES, "ability")?;
+        // Levels beyond this crate's level cap are dropped
+        if let Some(l) = stat_levelups.get_mut(i) {
+            *l = levelup;
+        }
+    }
+
+    // [Skills]
+    let mut skills = Skills::default();
+    for _ in 0..r.varint()? {
+        let skill = r.index(&SKILLS, "skill")?;
+        let mut points = [0; crate::HEROIC_LEVEL_CAP as usize];
+        for p in points.iter_mut() {
+            *p = r.byte()?;
+        }
+        skills.skill_table.insert(skill, points);
+    }
+    for _ in 0..r.varint()? {
+        let skill = r.index(&SKILLS, "skill")?;
+        skills.skill_tomes.insert(skill, r.byte()?);
+    }
+
+    // [Feats]
+    let mut lists: [Vec<Feat>; 4] = Default::default();
+    for list in lists.iter_mut() {
+        for _ in 0..r.varint()? {
+            let secondary_type = r.feat_type()?;
+            let level = r.byte()?;
+            list.push(Feat::new(secondary_type, level, r.string()?));
+        }
+    }
+    let [standard_feats, legend_feats, class_feats, race_feats] = lists;
+
+    // [Spells]
+    let mut spells = Spells::default();
+    for spellbook in spells.spellbooks.iter_mut() {
+        if let Some(class) = r.optional_content_index(&CLASSES, "class")? {
+            let spells_by_level = (0..r.varint()?)
+                .map(|_| {
+                    (0..r.varint()?)
+                        .map(|_| r.optional_string())
+                        .collect::<Result<_, _>>()
+                })
+                .collect::<Result<_, _>>()?;
+            *spellbook = Some(Spellbook {
+                class,
+                spells_by_level,
+            });
+        }
+    }
+
+    // [Enhancements]
+    let tier_five = match r.byte()? {
+        0 => None,
+        _ => Some(r.tree()?),
+    };
+    let mut enhancements = Enhancements::default();
+    for _ in 0..r.varint()? {
+        let name = r.tree()?;
+        let source = r.optional_content_index(&CLASSES, "class")?;
+        let class_levels = match r.varint()? {
+            0 => None,
+            n => Some(u8::try_from(n - 1).map_err(|_| {
+                ShareCodeError::OutOfRange("class levels", n - 1)
+            })?),
+        };
+        let mut tiers: [Vec<Enhancement>; 6] = Default::default();
+        for tier in tiers.iter_mut() {
+            for _ in 0..r.varint()? {
+                let name = r.string()?;
+                let subenhancement = r.optional_string()?;
+                let rank = NonZeroU8::new(r.byte()?);
+                tier.push(Enhancement::new(name, subenhancement, rank));
+            }
+        }
+        enhancements.trees.push(EnhancementTree {
+            name,
+            source,
+            class_levels,
+            tiers,
+        });
+    }
+
+    if r.pos != r.bytes.len() {
+        return Err(ShareCodeError::TrailingData);
+    }
+
+    Ok(CharacterBuild {
+        name,
+        race,
+        alignment,
+        max_levels,
+        levels,
+        preferred_build_type,
+        adventurer_stats,
+        champion_stats,
+        hero_stats,
+        legend_stats,
+        stat_tomes,
+        stat_levelups,
+        skills,
+        feats: Feats {
+            standard_feats,
+            legend_feats,
+            class_feats,
+            race_feats,
+        },
+        spells,
+        tier_five,
+        enhancements,
+    })
+}
+
+/// A race, class or tree, which has no index in the tables if content files
+/// added it.
+trait ContentEnum: Copy + PartialEq + fmt::Debug {
+    /// The `Id` of an `Other` value.
+    fn other(self) -> Option<ContentId>;
+
+    fn from_other(id: ContentId) -> Self;
+}
+
+impl ContentEnum for Race {
+    fn other(self) -> Option<ContentId> {
+        match self {
+            Self::Other(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    fn from_other(id: ContentId) -> Self {
+        Self::Other(id)
+    }
+}
+
+impl ContentEnum for Class {
+    fn other(self) -> Option<ContentId> {
+        match self {
+            Self::Other(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    fn from_other(id: ContentId) -> Self {
+        Self::Other(id)
+    }
+}
+
+impl ContentEnum for ClassEnhancementTree {
+    fn other(self) -> Option<ContentId> {
+        match self {
+            Self::Other(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    fn from_other(id: ContentId) -> Self {
+        Self::Other(id)
+    }
+}
+
+impl ContentEnum for GlobalEnhancementTree {
+    fn other(self) -> Option<ContentId> {
+        match self {
+            Self::Other(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    fn from_other(id: ContentId) -> Self {
+        Self::Other(id)
+    }
+}
+
+impl ContentEnum for RaceClassEnhancementTree {
+    fn other(self) -> Option<ContentId> {
+        match self {
+            Self::Other(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    fn from_other(id: ContentId) -> Self {
+        Self::Other(id)
+    }
+}
+
+/// Writes the body of a code, collecting its strings as it goes.
+#[derive(Default)]
+struct Writer {
+    bytes:   Vec<u8>,
+    strings: Vec<String>,
+    indices: HashMap<String, u32>,
+}
+
+impl Writer {
+    fn byte(&mut self, b: u8) {
+        self.bytes.push(b);
+    }
+
+    /// An unsigned LEB128 number: 7 bits per byte, with the high bit set on
+    /// every byte but the last.
+    fn varint(&mut self, mut n: u32) {
+        while n >= 0x80 {
+            self.bytes.push(n as u8 | 0x80);
+            n >>= 7;
+        }
+        self.bytes.push(n as u8);
+    }
+
+    fn length(&mut self, len: usize) {
+        self.varint(len as u32);
+    }
+
+    fn index<T: PartialEq + fmt::Debug>(&mut self, table: &[T], value: T) {
+        self.byte(position(table, value));
+    }
+
+    /// `0` for `None`, or the index plus 1.
+    fn optional_index<T: PartialEq + fmt::Debug>(
+        &mut self,
+        table: &[T],
+        value: Option<T>,
+    ) {
+        self.byte(value.map_or(0, |v| position(table, v) + 1));
+    }
+
+    /// `0` followed by the `Id` for an `Other` value, or else the index plus
+    /// 1.
+    fn content_index<T: ContentEnum>(&mut self, table: &[T], value: T) {
+        match value.other() {
+            Some(id) => {
+                self.byte(0);
+                self.string(id.as_str());
+            },
+            None => self.byte(position(table, value) + 1),
+        }
+    }
+
+    /// `0` for `None`, `1` followed by the `Id` for an `Other` value, or
+    /// else the index plus 2.
+    fn optional_content_index<T: ContentEnum>(
+        &mut self,
+        table: &[T],
+        value: Option<T>,
+    ) {
+        match value {
+            None => self.byte(0),
+            Some(v) => match v.other() {
+                Some(id) => {
+                    self.byte(1);
+                    self.string(id.as_str());
+                },
+                None => self.byte(position(table, v) + 2),
+            },
+        }
+    }
+
+    fn string(&mut self, s: &str) {
+        let index = self.intern(s);
+        self.varint(index);
+    }
+
+    /// `0` for `None`, or the string's index plus 1.
+    fn optional_string(&mut self, s: Option<&str>) {
+        let index = s.map_or(0, |s| self.intern(s) + 1);
+        self.varint(index);
+    }
+
+    /// The index of `s` in the string table, adding it if it isn't there
+    /// yet.
+    fn intern(&mut self, s: &str) -> u32 {
+        if let Some(&i) = self.indices.get(s) {
+            return i;
+        }
+
+        let i = self.strings.len() as u32;
+        self.strings.push(s.to_owned());
+        self.indices.insert(s.to_owned(), i);
+
+        i
+    }
+
+    fn stats(&mut self, stats: &Stats) {
+        for &a in ABILITIES.iter() {
+            self.byte(stats[a]);
+        }
+    }
+
+    fn feat_type(&mut self, secondary_type: SecondaryFeatType) {
+        match secondary_type {
+            SecondaryFeatType::Heroic => self.byte(0),
+            SecondaryFeatType::Epic => self.byte(1),
+            SecondaryFeatType::Destiny => self.byte(2),
+            SecondaryFeatType::Legend => self.byte(3),
+            SecondaryFeatType::Class(c) => {
+                self.byte(4);
+                self.content_index(&CLASSES, c);
+            },
+            SecondaryFeatType::Race(r) => {
+                self.byte(5);
+                self.content_index(&RACES, r);
+            },
+        }
+    }
+
+    /// The tree's type, then its index in the table of that type.
+    fn tree(&mut self, tree: EnhancementTreeName) {
+        match tree {
+            EnhancementTreeName::Class(t) => {
+                self.byte(0);
+                self.content_index(&CLASS_TREES, t);
+            },
+            EnhancementTreeName::Race(r) => {
+                self.byte(1);
+                self.content_index(&RACES, r);
+            },
+            EnhancementTreeName::Global(t) => {
+                self.byte(2);
+                self.content_index(&GLOBAL_TREES, t);
+            },
+            EnhancementTreeName::RaceClass(t) => {
+                self.byte(3);
+                self.content_index(&RACE_CLASS_TREES, t);
+            },
+        }
+    }
+
+    /// The version, then the string table and the body, compressed.
+    fn finish(self) -> Vec<u8> {
+        let mut out = Writer::default();
+        out.length(self.strings.len());
+        for s in self.strings.iter() {
+            out.length(s.len());
+            out.bytes.extend_from_slice(s.as_bytes());
+        }
+        out.bytes.extend_from_slice(&self.bytes);
+
+        let mut code = vec![VERSION];
+        code.extend(compress_to_vec(&out.bytes, 10));
+
+        code
+    }
+}
+
+/// The index of `value` in `table`.
+///
+/// # Panics
+///
+/// If `value` is missing from `table`, i.e. a variant was added to an enum
+/// without being appended to its table.
+fn position<T: PartialEq + fmt::Debug>(table: &[T], value: T) -> u8 {
+    let i = table.iter().position(|t| *t == value).unwrap_or_else(|| {
+        panic!("{:?} is missing from the share code tables", value)
+    });
+
+    i as u8
+}
+
+/// Reads the body of a code, after its version.
+struct Reader<'a> {
+    bytes:   &'a [u8],
+    pos:     usize,
+    strings: Vec<String>,
+}
+
+impl<'a> Reader<'a> {
+    /// Reads the string table, leaving the reader at the start of the
+    /// build.
+    fn new(bytes: &'a [u8]) -> Result<Self, ShareCodeError> {
+        let mut r = Self {
+            bytes,
+            pos: 0,
+            strings: Vec::new(),
+        };
+        for _ in 0..r.varint()? {
+            let len = r.varint()? as usize;
+            let end = r
+                .pos
+                .checked_add(len)
+                .filter(|&end| end <= r.bytes.len())
+                .ok_or(ShareCodeError::Truncated)?;
+            let s = std::str::from_utf8(&r.bytes[r.pos..end])
+                .map_err(|_| ShareCodeError::InvalidString)?;
+            r.strings.push(s.to_owned());
+            r.pos = end;
+        }
+
+        Ok(r)
+    }
+
+    fn byte(&mut self) -> Result<u8, ShareCodeError> {
+        let b = *self.bytes.get(self.pos).ok_or(ShareCodeError::Truncated)?;
+        self.pos += 1;
+
+        Ok(b)
+    }
+
+    fn varint(&mut self) -> Result<u32, ShareCodeError> {
+        let mut n = 0u32;
+        for shift in (0..32).step_by(7) {
+            let b = self.byte()?;
+            n |= u32::from(b & 0x7f) << shift;
+            if b & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+
+        Err(ShareCodeError::OutOfRange("number", n))
+    }
+
+    fn index<T: Copy>(
+        &mut self,
+        table: &[T],
+        what: &'static str,
+    ) -> Result<T, ShareCodeError> {
+        let i = self.byte()?;
+        table
+            .get(usize::from(i))
+            .copied()
+            .ok_or(ShareCodeError::OutOfRange(what, u32::from(i)))
+    }
+
+    fn optional_index<T: Copy>(
+        &mut self,
+        table: &[T],
+        what: &'static str,
+    ) -> Result<Option<T>, ShareCodeError> {
+        match self.byte()? {
+            0 => Ok(None),
+            i => table
+                .get(usize::from(i - 1))
+                .copied()
+                .map(Some)
+                .ok_or(ShareCodeError::OutOfRange(what, u32::from(i - 1))),
+        }
+    }
+
+    /// See `Writer::content_index()`.
+    fn content_index<T: ContentEnum>(
+        &mut self,
+        table: &[T],
+        what: &'static str,
+    ) -> Result<T, ShareCodeError> {
+        match self.byte()? {
+            0 => Ok(T::from_other(ContentId::new(&self.string()?))),
+            i => table
+                .get(usize::from(i - 1))
+                .copied()
+                .ok_or(ShareCodeError::OutOfRange(what, u32::from(i - 1))),
+        }
+    }
+
+    /// See `Writer::optional_content_index()`.
+    fn optional_content_index<T: ContentEnum>(
+        &mut self,
+        table: &[T],
+        what: &'static str,
+    ) -> Result<Option<T>, ShareCodeError> {
+        match self.byte()? {
+            0 => Ok(None),
+            1 => Ok(Some(T::from_other(ContentId::new(&self.string()?)))),
+            i => table
+                .get(usize::from(i - 2))
+                .copied()
+                .map(Some)
+                .ok_or(ShareCodeError::OutOfRange(what, u32::from(i - 2))),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ShareCodeError> {
+        let i = self.varint()?;
+        self.strings
+            .get(i as usize)
+            .cloned()
+            .ok_or(ShareCodeError::OutOfRange("string", i))
+    }
+
+    fn optional_string(&mut self) -> Result<Option<String>, ShareCodeError> {
+        match self.varint()? {
+            0 => Ok(None),
+            i => self
+                .strings
+                .get(i as usize - 1)
+                .cloned()
+                .map(Some)
+                .ok_or(ShareCodeError::OutOfRange("string", i - 1)),
+        }
+    }
+
+    fn stats(&mut self) -> Result<Stats, ShareCodeError> {
+        let mut stats = Stats::default();
+        for &a in ABILITIES.iter() {
+            stats[a] = self.byte()?;
+        }
+
+        Ok(stats)
+    }
+
+    fn feat_type(&mut self) -> Result<SecondaryFeatType, ShareCodeError> {
+        match self.byte()? {
+            0 => Ok(SecondaryFeatType::Heroic),
+            1 => Ok(SecondaryFeatType::Epic),
+            2 => Ok(SecondaryFeatType::Destiny),
+            3 => Ok(SecondaryFeatType::Legend),
+            4 => Ok(SecondaryFeatType::Class(
+                self.content_index(&CLASSES, "class")?,
+            )),
+            5 => Ok(SecondaryFeatType::Race(
+                self.content_index(&RACES, "race")?,
+            )),
+            t => Err(ShareCodeError::OutOfRange("feat type", u32::from(t))),
+        }
+    }
+
+    fn tree(&mut self) -> Result<EnhancementTreeName, ShareCodeError> {
+        match self.byte()? {
+            0 => Ok(EnhancementTreeName::Class(
+                self.content_index(&CLASS_TREES, "tree")?,
+            )),
+            1 => Ok(EnhancementTreeName::Race(
+                self.content_index(&RACES, "race")?,
+            )),
+            2 => Ok(EnhancementTreeName::Global(
+                self.content_index(&GLOBAL_TREES, "tree")?,
+            )),
+            3 => Ok(EnhancementTreeName::RaceClass(
+                self.content_index(&RACE_CLASS_TREES, "tree")?,
+            )),
+            t => Err(ShareCodeError::OutOfRange("tree type", u32::from(t))),
+        }
+    }
+}
+
+impl fmt::Display for ShareCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Self::InvalidCharacter(c) =>
+                write!(f, "Not a character of a share code: {:?}", c),
+            Self::UnknownVersion(v) => write!(
+                f,
+                "Share code version {} is newer than this version ({}) knows",
+                v, VERSION,
+            ),
+            Self::Truncated => f.write_str("The share code is cut short"),
+            Self::OutOfRange(what, i) =>
+                write!(f, "Unknown {} in share code: {}", what, i),
+            Self::InvalidString =>
+                f.write_str("The share code has a string that isn't UTF-8"),
+            Self::TrailingData =>
+                f.write_str("The share code goes on after the build"),
+            Self::InvalidCompression =>
+                f.write_str("The share code's build can't be decompressed"),
+        }
+    }
+}
+
+impl std::error::Error for ShareCodeError {}
+
+/// Unpadded URL-safe base64.
+fn to_base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        // 3 bytes make 4 characters; fewer make one more than there are
+        for i in 0..=chunk.len() {
+            let sextet = (n >> (18 - 6 * i)) & 0x3f;
+            out.push(char::from(BASE64_ALPHABET[sextet as usize]));
+        }
+    }
+
+    out
+}
+
+fn from_base64(s: &str) -> Result<Vec<u8>, ShareCodeError> {
+    let sextets = s
+        .chars()
+        .map(|c| {
+            BASE64_ALPHABET
+                .iter()
+                .position(|&a| char::from(a) == c)
+                .map(|i| i as u32)
+                .ok_or(ShareCodeError::InvalidCharacter(c))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut out = Vec::with_capacity(sextets.len() * 3 / 4);
+    for chunk in sextets.chunks(4) {
+        // A lone character can't hold a whole byte
+        if chunk.len() == 1 {
+            return Err(ShareCodeError::Truncated);
+        }
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &s)| n | s << (18 - 6 * i));
+        for i in 0..chunk.len() - 1 {
+            out.push((n >> (16 - 8 * i)) as u8);
+        }
+    }
+
+    Ok(out)
+}
@@ -0,0 +1,229 @@
+//! What a character has at a given character level, e.g. to write leveling
+//! guides, or to see what a build is missing at level 12.
+
+use crate::{
+    class_levels,
+    content::{self, Registry},
+    validate::{levels_for_tree, TIER_LEVELS},
+    Ability,
+    CharacterBuild,
+    Class,
+    EnhancementTreeName,
+    Feat,
+    SecondaryFeatType,
+    Skill,
+    Stats,
+    HEROIC_LEVEL_CAP,
+};
+
+/// Every ability score starts here, before build points and racial
+/// modifiers.
+pub const BASE_ABILITY_SCORE: u8 = 8;
+/// The build points that it costs to raise an ability score from
+/// `BASE_ABILITY_SCORE` to `BASE_ABILITY_SCORE + i`.
+pub const SCORE_COSTS: [u8; 11] = [0, 1, 2, 3, 4, 5, 6, 8, 10, 13, 16];
+
+/// A build as it is at some character level; see
+/// `CharacterBuild::at_level()`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LevelSnapshot<'a> {
+    pub level:          u8,
+    /// The class of each heroic level up to `level`.
+    pub levels:         &'a [Class],
+    /// The number of levels in each class so far, in the order that the
+    /// classes were first taken.
+    pub class_levels:   Vec<(Class, u8)>,
+    /// The ability scores of the preferred build type (**not** the build
+    /// points, as in `CharacterBuild`), including racial modifiers, ability
+    /// score increases and tomes. `None` if every build type is disabled.
+    pub ability_scores: Option<Stats>,
+    /// The ranks in each skill that has any, in the order of `Skill::ALL`.
+    /// Points spent in cross-class skills are worth half a rank. Skill tomes
+    /// aren't counted.
+    pub skill_ranks:    Vec<(Skill, f32)>,
+    /// The feats taken so far, with the character level that each was taken
+    /// at (class feats are listed by class level in `CharacterBuild`).
+    pub feats:          Vec<(u8, &'a Feat)>,
+    /// The spells that have been learned so far, as (class, spell level,
+    /// name): those in slots that the class has learned by now (see
+    /// `content::ClassInfo::slot_unlock`). Unmemorized slots are left out.
+    pub spells:         Vec<(Class, usize, &'a str)>,
+    /// The highest tier that is unlocked in each declared tree that any
+    /// levels can be spent in, in the order that they were declared.
+    pub unlocked_tiers: Vec<(EnhancementTreeName, usize)>,
+}
+
+/// Returns the ability score bought with `points` build points, not counting
+/// racial modifiers.
+pub fn ability_score(points: u8) -> u8 {
+    let raised = SCORE_COSTS.iter().rposition(|&c| c <= points).unwrap_or(0);
+
+    BASE_ABILITY_SCORE + raised as u8
+}
+
+impl CharacterBuild {
+    /// Returns what the character has at character level `level`, using the
+    /// game content of `content::registry()`.
+    pub fn at_level(&self, level: u8) -> LevelSnapshot<'_> {
+        self.at_level_with(level, &content::registry())
+    }
+
+    /// Returns what the character has at character level `level`, according
+    /// to `content`. `level` is clamped to `1..=max_levels`.
+    pub fn at_level_with(
+        &self,
+        level: u8,
+        content: &Registry,
+    ) -> LevelSnapshot<'_> {
+        let level = level.clamp(1, self.max_levels.max(1));
+        let levels = &self.levels[..self.levels.len().min(level.into())];
+
+        let class_levels = class_levels(levels);
+        let levels_in = |class| {
+            class_levels
+                .iter()
+                .find(|(c, _)| *c == class)
+                .map_or(0, |(_, n)| *n)
+        };
+
+        LevelSnapshot {
+            level,
+            levels,
+            ability_scores: self.ability_scores_at(level, content),
+            skill_ranks: skill_ranks(self, levels, content),
+            feats: self.feats_at(level),
+            spells: self
+                .spells
+                .spellbooks
+                .iter()
+                .flatten()
+                .flat_map(|sb| {
+                    let info = content.class(sb.class);
+                    let class_level = levels_in(sb.class);
+
+                    sb.spells_by_level.iter().enumerate().flat_map(
+                        move |(i, spells)| {
+                            spells
+                                .iter()
+                                .enumerate()
+                                .filter(move |&(slot, _)| {
+                                    info.and_then(|ci| {
+                                        ci.slot_unlock(i + 1, slot)
+                                    })
+                                    .is_some_and(|u| u <= class_level)
+                                })
+                                .filter_map(move |(_, s)| {
+                                    Some((sb.class, i + 1, s.as_deref()?))
+                                })
+                        },
+                    )
+                })
+                .collect(),
+            unlocked_tiers: self
+                .enhancements
+                .trees
+                .iter()
+                .filter_map(|et| {
+                    let found = levels_for_tree(levels, content, et)?;
+                    let tier = (0..TIER_LEVELS.len())
+                        .filter(|&t| t < 5 || self.tier_five == Some(et.name))
+                        .take_while(|&t| TIER_LEVELS[t] <= found)
+                        .last()?;
+
+                    Some((et.name, tier))
+                })
+                .collect(),
+            class_levels,
+        }
+    }
+
+    fn ability_scores_at(
+        &self,
+        level: u8,
+        content: &Registry,
+    ) -> Option<Stats> {
+        let points = self.preferred_stats()?;
+        let modifiers = content
+            .race(self.race)
+            .map_or(&[][..], |ri| &ri.ability_modifiers);
+
+        let mut scores = Stats::default();
+        for &ability in Ability::ALL.iter() {
+            let modifier: i16 = modifiers
+                .iter()
+                .filter(|(a, _)| *a == ability)
+                .map(|(_, m)| i16::from(*m))
+                .sum();
+            let increases = (1..=level)
+                .filter(|&l| self.levelup_at(l) == Some(ability))
+                .count() as i16;
+
+            let score = i16::from(ability_score(points[ability]))
+                + modifier
+                + increases
+                + i16::from(self.stat_tomes[ability]);
+            scores[ability] = score.clamp(0, i16::from(u8::MAX)) as u8;
+        }
+
+        Some(scores)
+    }
+
+    fn feats_at(&self, level: u8) -> Vec<(u8, &Feat)> {
+        let feats = &self.feats;
+        let mut taken: Vec<(u8, &Feat)> = feats
+            .standard_feats
+            .iter()
+            .chain(feats.class_feats.iter())
+            .chain(feats.race_feats.iter())
+            .chain(feats.legend_feats.iter())
+            .filter_map(|feat| {
+                let character_level = match feat.secondary_type {
+                    SecondaryFeatType::Class(c) =>
+                        self.character_level_of(c, feat.level)?,
+                    _ => feat.level,
+                };
+
+                Some((character_level, feat))
+            })
+            .filter(|&(l, _)| l <= level)
+            .collect();
+        taken.sort_by_key(|&(l, _)| l);
+
+        taken
+    }
+}
+
+fn skill_ranks(
+    build: &CharacterBuild,
+    levels: &[Class],
+    content: &Registry,
+) -> Vec<(Skill, f32)> {
+    let heroic_levels = &levels[..levels.len().min(HEROIC_LEVEL_CAP.into())];
+
+    Skill::ALL
+        .iter()
+        .map(|&skill| {
+            let ranks = heroic_levels
+                .iter()
+                .enumerate()
+                .map(|(i, &class)| {
+                    let points = f32::from(
+                        build.skills.points_in_skill_at_level(skill, i),
+                    );
+                    let class_skill = content
+                        .class(class)
+                        .is_some_and(|ci| ci.class_skills.contains(&skill));
+
+                    if class_skill {
+                        points
+                    } else {
+                        points / 2.0
+                    }
+                })
+                .sum();
+
+            (skill, ranks)
+        })
+        .filter(|&(_, ranks)| ranks > 0.0)
+        .collect()
+}
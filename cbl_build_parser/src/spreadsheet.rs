@@ -0,0 +1,210 @@
+//! A build as tables for spreadsheets: the level plan, the skill points
+//! spent at each level, the build points of each build type, the feats and
+//! the enhancements. The column headers don't depend on the build, so that
+//! formulas written against one build's tables work for any other's.
+//!
+//! The tables can be written one per CSV file with `Table::write()`, or all
+//! together with `statistics::write_tables()`.
+
+use crate::{
+    content::{self, Registry},
+    statistics::{strings, Table},
+    Ability,
+    BuildType,
+    CharacterBuild,
+    SecondaryFeatType,
+    Skill,
+    HEROIC_LEVEL_CAP,
+};
+
+/// The tables of `build`, using the game content of `content::registry()`.
+pub fn tables(build: &CharacterBuild) -> Vec<Table> {
+    tables_with(build, &content::registry())
+}
+
+/// The tables of `build`, according to `content`, which gives the action
+/// points that each enhancement costs.
+pub fn tables_with(build: &CharacterBuild, content: &Registry) -> Vec<Table> {
+    vec![
+        levels(build),
+        skills(build),
+        stats(build),
+        feats(build),
+        enhancements(build, content),
+    ]
+}
+
+/// The class taken at each heroic level, with the level of the class that
+/// it makes.
+fn levels(build: &CharacterBuild) -> Table {
+    let mut rows = Vec::with_capacity(build.levels.len());
+    for (i, &class) in build.levels.iter().enumerate() {
+        let class_level =
+            build.levels[..=i].iter().filter(|&&c| c == class).count();
+        rows.push(vec![
+            (i + 1).to_string(),
+            class.to_string(),
+            class_level.to_string(),
+        ]);
+    }
+
+    Table {
+        name: "levels",
+        title: "Level plan",
+        header: strings(&["Level", "Class", "Class Level"]),
+        rows,
+    }
+}
+
+/// The skill points (**not** ranks) spent in every skill at every heroic
+/// level, then the skill's tome. There is a column for every heroic level,
+/// even those that the build doesn't reach.
+fn skills(build: &CharacterBuild) -> Table {
+    let mut header = vec!["Skill".to_owned()];
+    header.extend((1..=HEROIC_LEVEL_CAP).map(|l| l.to_string()));
+    header.push("Tome".to_owned());
+
+    let rows = Skill::ALL
+        .iter()
+        .map(|&skill| {
+            let mut row = vec![skill.to_string()];
+            row.extend((0..HEROIC_LEVEL_CAP as usize).map(|l| {
+                build.skills.points_in_skill_at_level(skill, l).to_string()
+            }));
+            row.push(build.skills.tome(skill).to_string());
+
+            row
+        })
+        .collect();
+
+    Table {
+        name: "skills",
+        title: "Skill points",
+        header,
+        rows,
+    }
+}
+
+/// The build points spent on each ability by each build type, then the
+/// ability's tome. The column of a disabled build type is left empty.
+fn stats(build: &CharacterBuild) -> Table {
+    let mut header = vec!["Ability".to_owned()];
+    header.extend(BuildType::ALL.iter().map(|bt| bt.to_string()));
+    header.push("Tome".to_owned());
+
+    let rows = Ability::ALL
+        .iter()
+        .map(|&ability| {
+            let mut row = vec![ability.to_string()];
+            row.extend(BuildType::ALL.iter().map(|&bt| {
+                build
+                    .stats(bt)
+                    .map_or_else(String::new, |s| s[ability].to_string())
+            }));
+            row.push(build.stat_tomes[ability].to_string());
+
+            row
+        })
+        .collect();
+
+    Table {
+        name: "stats",
+        title: "Build points",
+        header,
+        rows,
+    }
+}
+
+/// Every feat, as it is written in the build file, with the character level
+/// that it is taken at. The level of a class feat is a class level; its
+/// character level is left empty if the build doesn't reach it.
+fn feats(build: &CharacterBuild) -> Table {
+    let feats = &build.feats;
+    let lists = [
+        ("Standard", &feats.standard_feats),
+        ("Legend", &feats.legend_feats),
+        ("Class", &feats.class_feats),
+        ("Race", &feats.race_feats),
+    ];
+
+    let rows = lists
+        .iter()
+        .flat_map(|&(feat_type, list)| {
+            list.iter().map(move |f| (feat_type, f))
+        })
+        .map(|(feat_type, feat)| {
+            let (slot, character_level) = match feat.secondary_type {
+                SecondaryFeatType::Heroic =>
+                    ("Heroic".to_owned(), Some(feat.level)),
+                SecondaryFeatType::Epic =>
+                    ("Epic".to_owned(), Some(feat.level)),
+                SecondaryFeatType::Destiny =>
+                    ("Destiny".to_owned(), Some(feat.level)),
+                SecondaryFeatType::Legend =>
+                    ("Legend".to_owned(), Some(feat.level)),
+                SecondaryFeatType::Class(c) =>
+                    (c.to_string(), build.character_level_of(c, feat.level)),
+                SecondaryFeatType::Race(r) =>
+                    (r.to_string(), Some(feat.level)),
+            };
+
+            vec![
+                feat_type.to_owned(),
+                slot,
+                feat.level.to_string(),
+                character_level.map_or_else(String::new, |l| l.to_string()),
+                feat.name.clone(),
+            ]
+        })
+        .collect();
+
+    Table {
+        name: "feats",
+        title: "Feats",
+        header: strings(&["Type", "Slot", "Level", "Character Level", "Feat"]),
+        rows,
+    }
+}
+
+/// Every enhancement, in the order that the trees were declared, with the
+/// action points that it costs at its rank. `Tier 5` marks the enhancements
+/// of the tier 5 tree.
+fn enhancements(build: &CharacterBuild, content: &Registry) -> Table {
+    let mut rows = Vec::new();
+    for tree in build.enhancements.trees.iter() {
+        let tier_five = build.tier_five == Some(tree.name);
+        for (tier, es) in tree.tiers.iter().enumerate() {
+            for e in es.iter() {
+                let cost = content
+                    .enhancement(tree.name, tier, &e.name)
+                    .map_or(1, |ei| ei.cost);
+                let ranks = e.rank.map_or(1, |r| r.get());
+
+                rows.push(vec![
+                    tree.name.to_string(),
+                    if tier_five { "Yes" } else { "" }.to_owned(),
+                    tier.to_string(),
+                    e.name.clone(),
+                    e.subenhancement.clone().unwrap_or_default(),
+                    e.rank.map_or_else(String::new, |r| r.to_string()),
+                    (u16::from(cost) * u16::from(ranks)).to_string(),
+                ]);
+            }
+        }
+    }
+
+    Table {
+        name: "enhancements",
+        title: "Enhancements",
+        header: strings(&[
+            "Tree",
+            "Tier 5",
+            "Tier",
+            "Enhancement",
+            "Subenhancement",
+            "Rank",
+            "AP",
+        ]),
+        rows,
+    }
+}
@@ -0,0 +1,619 @@
+//! Statistics over a collection of builds, e.g. a guild's archive: which
+//! feats each class split takes and when, the ability scores that each race
+//! starts with, which trees are popular, and where skill points go. The
+//! statistics can be written as Markdown or CSV tables.
+
+use crate::{
+    content::{self, Registry},
+    order::format_split,
+    snapshot::ability_score,
+    Ability,
+    BuildType,
+    CharacterBuild,
+    Class,
+    ClassEnhancementTree,
+    EnhancementTreeName,
+    Race,
+    SecondaryFeatType,
+    Skill,
+    Stats,
+};
+use std::{cmp::Reverse, fmt::Write};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TableFormat {
+    Markdown,
+    Csv,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Statistics {
+    pub build_count:       usize,
+    /// Sorted by the number of builds, most first.
+    pub split_feats:       Vec<SplitFeats>,
+    /// Sorted by the number of builds that take the feat, most first.
+    pub feat_timings:      Vec<FeatTiming>,
+    /// Sorted by race, then build type.
+    pub stat_spreads:      Vec<StatSpread>,
+    /// The class trees that any build spends action points in, in the order
+    /// that the registry defines them.
+    pub tree_popularity:   Vec<TreePopularity>,
+    /// Every tier 5 tree chosen, with the number of builds that chose it,
+    /// most chosen first.
+    pub tier_five_choices: Vec<(EnhancementTreeName, usize)>,
+    /// The skills that any build spends points in, in the order of
+    /// `Skill::ALL`.
+    pub skill_allocation:  Vec<SkillAllocation>,
+}
+
+/// The feats taken by the builds of one class split.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SplitFeats {
+    /// Most levels first; see `CharacterBuild::class_split()`.
+    pub split:  Vec<(Class, u8)>,
+    pub builds: usize,
+    /// Each feat with the number of builds that take it, most first.
+    pub feats:  Vec<(String, usize)>,
+}
+
+/// The character levels that a feat is taken at.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FeatTiming {
+    pub feat:   String,
+    /// Each character level with the number of times that the feat is taken
+    /// at it, sorted by level.
+    pub levels: Vec<(u8, usize)>,
+}
+
+/// The starting ability scores of the builds of one race and preferred
+/// build type.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatSpread {
+    pub race:        Race,
+    pub build_type:  BuildType,
+    pub builds:      usize,
+    /// The average score of each ability, in the order of `Ability::ALL`.
+    pub average:     [f32; 6],
+    /// The scores (**not** build points) that most of the builds start with.
+    pub most_common: Stats,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TreePopularity {
+    pub tree:       ClassEnhancementTree,
+    /// The number of builds that spend action points in the tree.
+    pub builds:     usize,
+    /// The average action points spent by those builds.
+    pub average_ap: f32,
+    /// The number of builds that chose the tree for tier 5.
+    pub tier_five:  usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SkillAllocation {
+    pub skill:          Skill,
+    /// The number of builds that spend points in the skill.
+    pub builds:         usize,
+    /// The average points spent over the heroic levels, by every build.
+    pub average_points: f32,
+}
+
+/// A table of statistics, ready to be written.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Table {
+    /// A name for the table that can be used as a filename, e.g.
+    /// `"feat_timings"`.
+    pub name:   &'static str,
+    pub title:  &'static str,
+    pub header: Vec<String>,
+    pub rows:   Vec<Vec<String>>,
+}
+
+/// Gathers statistics over `builds`, using the game content of
+/// `content::registry()`.
+pub fn statistics(builds: &[CharacterBuild]) -> Statistics {
+    statistics_with(builds, &content::registry())
+}
+
+pub fn statistics_with(
+    builds: &[CharacterBuild],
+    content: &Registry,
+) -> Statistics {
+    Statistics {
+        build_count:       builds.len(),
+        split_feats:       split_feats(builds),
+        feat_timings:      feat_timings(builds),
+        stat_spreads:      stat_spreads(builds, content),
+        tree_popularity:   tree_popularity(builds, content),
+        tier_five_choices: tier_five_choices(builds),
+        skill_allocation:  skill_allocation(builds),
+    }
+}
+
+impl FeatTiming {
+    /// The number of times that the feat is taken.
+    pub fn count(&self) -> usize {
+        self.levels.iter().map(|(_, n)| n).sum()
+    }
+
+    pub fn earliest(&self) -> Option<u8> {
+        self.levels.first().map(|(l, _)| *l)
+    }
+
+    /// The lower median of the levels that the feat is taken at.
+    pub fn median(&self) -> Option<u8> {
+        let middle = self.count().checked_sub(1)? / 2;
+        let mut seen = 0;
+
+        self.levels.iter().find_map(|&(l, n)| {
+            seen += n;
+            Some(l).filter(|_| seen > middle)
+        })
+    }
+
+    pub fn latest(&self) -> Option<u8> {
+        self.levels.last().map(|(l, _)| *l)
+    }
+}
+
+impl Statistics {
+    /// The statistics as tables, in the order of the fields.
+    pub fn tables(&self) -> Vec<Table> {
+        let split_feats = Table {
+            name:   "split_feats",
+            title:  "Feats by class split",
+            header: strings(&["Class split", "Builds", "Feat", "Taken by"]),
+            rows:   self
+                .split_feats
+                .iter()
+                .flat_map(|sf| {
+                    let split = format_split(&sf.split);
+                    sf.feats.iter().map(move |(feat, n)| {
+                        vec![
+                            split.clone(),
+                            sf.builds.to_string(),
+                            feat.clone(),
+                            n.to_string(),
+                        ]
+                    })
+                })
+                .collect(),
+        };
+
+        let feat_timings = Table {
+            name:   "feat_timings",
+            title:  "Feat timings",
+            header: strings(&[
+                "Feat", "Taken", "Earliest", "Median", "Latest", "Levels",
+            ]),
+            rows:   self
+                .feat_timings
+                .iter()
+                .map(|ft| {
+                    let level = |l: Option<u8>| {
+                        l.map_or_else(String::new, |l| l.to_string())
+                    };
+                    let levels = ft
+                        .levels
+                        .iter()
+                        .map(|(l, n)| format!("{}: {}", l, n))
+                        .collect::<Vec<_>>();
+
+                    vec![
+                        ft.feat.clone(),
+                        ft.count().to_string(),
+                        level(ft.earliest()),
+                        level(ft.median()),
+                        level(ft.latest()),
+                        levels.join(", "),
+                    ]
+                })
+                .collect(),
+        };
+
+        let mut spread_header = strings(&["Race", "Build type", "Builds"]);
+        spread_header
+            .extend(Ability::ALL.iter().map(|a| a.abbreviation().to_owned()));
+        spread_header.push("Most common".to_owned());
+        let stat_spreads = Table {
+            name:   "stat_spreads",
+            title:  "Starting ability scores",
+            header: spread_header,
+            rows:   self
+                .stat_spreads
+                .iter()
+                .map(|ss| {
+                    let mut row = vec![
+                        ss.race.to_string(),
+                        ss.build_type.to_string(),
+                        ss.builds.to_string(),
+                    ];
+                    row.extend(ss.average.iter().map(|a| format!("{:.1}", a)));
+                    let most_common = Ability::ALL
+                        .iter()
+                        .map(|&a| ss.most_common[a].to_string())
+                        .collect::<Vec<_>>();
+                    row.push(most_common.join("/"));
+
+                    row
+                })
+                .collect(),
+        };
+
+        let tree_popularity = Table {
+            name:   "tree_popularity",
+            title:  "Class enhancement trees",
+            header: strings(&["Tree", "Builds", "Average AP", "Tier 5"]),
+            rows:   self
+                .tree_popularity
+                .iter()
+                .map(|tp| {
+                    vec![
+                        tp.tree.to_string(),
+                        tp.builds.to_string(),
+                        format!("{:.1}", tp.average_ap),
+                        tp.tier_five.to_string(),
+                    ]
+                })
+                .collect(),
+        };
+
+        let tier_five_choices = Table {
+            name:   "tier_five_choices",
+            title:  "Tier 5 trees",
+            header: strings(&["Tree", "Builds", "Share"]),
+            rows:   self
+                .tier_five_choices
+                .iter()
+                .map(|&(tree, n)| {
+                    vec![
+                        tree.to_string(),
+                        n.to_string(),
+                        percentage(n, self.build_count),
+                    ]
+                })
+                .collect(),
+        };
+
+        let skill_allocation = Table {
+            name:   "skill_allocation",
+            title:  "Skill points",
+            header: strings(&["Skill", "Builds", "Average points"]),
+            rows:   self
+                .skill_allocation
+                .iter()
+                .map(|sa| {
+                    vec![
+                        sa.skill.to_string(),
+                        sa.builds.to_string(),
+                        format!("{:.1}", sa.average_points),
+                    ]
+                })
+                .collect(),
+        };
+
+        vec![
+            split_feats,
+            feat_timings,
+            stat_spreads,
+            tree_popularity,
+            tier_five_choices,
+            skill_allocation,
+        ]
+    }
+
+    /// Writes every table, as `write_tables()` does, under a heading in
+    /// Markdown.
+    pub fn report(&self, format: TableFormat) -> String {
+        let mut out = String::with_capacity(4_096);
+
+        if format == TableFormat::Markdown {
+            writeln!(out, "# Statistics of {} builds\n", self.build_count)
+                .unwrap();
+        }
+        out.push_str(&write_tables(&self.tables(), format));
+
+        out
+    }
+}
+
+impl Table {
+    /// Writes the header and rows of the table.
+    pub fn write(&self, format: TableFormat) -> String {
+        let mut out = String::with_capacity(1_024);
+        let rows = std::iter::once(&self.header).chain(self.rows.iter());
+
+        match format {
+            TableFormat::Markdown =>
+                for (i, row) in rows.enumerate() {
+                    let cells = row
+                        .iter()
+                        .map(|c| c.replace('|', "\\|"))
+                        .collect::<Vec<_>>();
+                    writeln!(out, "| {} |", cells.join(" | ")).unwrap();
+                    if i == 0 {
+                        let rule = vec!["---"; row.len()];
+                        writeln!(out, "| {} |", rule.join(" | ")).unwrap();
+                    }
+                },
+            TableFormat::Csv =>
+                for row in rows {
+                    let fields =
+                        row.iter().map(|f| csv_field(f)).collect::<Vec<_>>();
+                    writeln!(out, "{}", fields.join(",")).unwrap();
+                },
+        }
+
+        out
+    }
+}
+
+/// Writes each table under its title: a `##` heading in Markdown, or a
+/// line of its own in CSV. Tables are separated by blank lines; see
+/// `Table::write()` to write them one per file instead.
+pub fn write_tables(tables: &[Table], format: TableFormat) -> String {
+    let mut out = String::with_capacity(4_096);
+
+    for table in tables.iter() {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        match format {
+            TableFormat::Markdown =>
+                writeln!(out, "## {}\n", table.title).unwrap(),
+            TableFormat::Csv =>
+                writeln!(out, "{}", csv_field(table.title)).unwrap(),
+        }
+        out.push_str(&table.write(format));
+    }
+
+    out
+}
+
+fn split_feats(builds: &[CharacterBuild]) -> Vec<SplitFeats> {
+    let mut by_split: Vec<SplitFeats> = Vec::new();
+    for build in builds.iter() {
+        let split = build.class_split();
+        let i = match by_split.iter().position(|sf| sf.split == split) {
+            Some(i) => i,
+            None => {
+                by_split.push(SplitFeats {
+                    split,
+                    builds: 0,
+                    feats: Vec::new(),
+                });
+                by_split.len() - 1
+            },
+        };
+        let sf = &mut by_split[i];
+        sf.builds += 1;
+
+        // A feat taken twice (e.g. at two class levels) counts once
+        let mut names = all_feats(build).map(|(_, n)| n).collect::<Vec<_>>();
+        names.sort_unstable();
+        names.dedup();
+        for name in names {
+            match sf.feats.iter_mut().find(|(f, _)| f == name) {
+                Some((_, n)) => *n += 1,
+                None => sf.feats.push((name.to_owned(), 1)),
+            }
+        }
+    }
+
+    for sf in by_split.iter_mut() {
+        sf.feats
+            .sort_by(|(fa, a), (fb, b)| b.cmp(a).then_with(|| fa.cmp(fb)));
+    }
+    by_split.sort_by_key(|sf| Reverse(sf.builds));
+
+    by_split
+}
+
+fn feat_timings(builds: &[CharacterBuild]) -> Vec<FeatTiming> {
+    let mut timings: Vec<FeatTiming> = Vec::new();
+    for (level, name) in builds.iter().flat_map(all_feats) {
+        let i = match timings.iter().position(|ft| ft.feat == name) {
+            Some(i) => i,
+            None => {
+                timings.push(FeatTiming {
+                    feat:   name.to_owned(),
+                    levels: Vec::new(),
+                });
+                timings.len() - 1
+            },
+        };
+        let levels = &mut timings[i].levels;
+        match levels.binary_search_by_key(&level, |&(l, _)| l) {
+            Ok(j) => levels[j].1 += 1,
+            Err(j) => levels.insert(j, (level, 1)),
+        }
+    }
+
+    timings.sort_by(|a, b| {
+        b.count().cmp(&a.count()).then_with(|| a.feat.cmp(&b.feat))
+    });
+
+    timings
+}
+
+fn stat_spreads(
+    builds: &[CharacterBuild],
+    content: &Registry,
+) -> Vec<StatSpread> {
+    let mut groups: Vec<(Race, BuildType, Vec<Stats>)> = Vec::new();
+    for build in builds.iter() {
+        let points = match build.stats(build.preferred_build_type) {
+            Some(points) => points,
+            None => continue,
+        };
+        let modifiers = content
+            .race(build.race)
+            .map_or(&[][..], |ri| &ri.ability_modifiers);
+        let mut scores = Stats::default();
+        for &ability in Ability::ALL.iter() {
+            let modifier: i16 = modifiers
+                .iter()
+                .filter(|(a, _)| *a == ability)
+                .map(|(_, m)| i16::from(*m))
+                .sum();
+            let score = i16::from(ability_score(points[ability])) + modifier;
+            scores[ability] = score.clamp(0, i16::from(u8::MAX)) as u8;
+        }
+
+        let key = (build.race, build.preferred_build_type);
+        match groups.iter_mut().find(|(r, bt, _)| (*r, *bt) == key) {
+            Some((_, _, spreads)) => spreads.push(scores),
+            None => groups.push((key.0, key.1, vec![scores])),
+        }
+    }
+
+    let race_index = |r| content.races().position(|(x, _)| x == r);
+    let build_type_index = |bt| BuildType::ALL.iter().position(|&x| x == bt);
+    groups.sort_by_key(|&(r, bt, _)| (race_index(r), build_type_index(bt)));
+
+    groups
+        .into_iter()
+        .map(|(race, build_type, spreads)| {
+            let mut average = [0.0; 6];
+            for (avg, &ability) in average.iter_mut().zip(Ability::ALL.iter())
+            {
+                let total: u32 =
+                    spreads.iter().map(|s| u32::from(s[ability])).sum();
+                *avg = total as f32 / spreads.len() as f32;
+            }
+            // The first of the spreads that are shared by the most builds
+            let most_common = spreads
+                .iter()
+                .rev()
+                .max_by_key(|s| spreads.iter().filter(|o| o == s).count())
+                .cloned()
+                .unwrap_or_default();
+
+            StatSpread {
+                race,
+                build_type,
+                builds: spreads.len(),
+                average,
+                most_common,
+            }
+        })
+        .collect()
+}
+
+fn tree_popularity(
+    builds: &[CharacterBuild],
+    content: &Registry,
+) -> Vec<TreePopularity> {
+    content
+        .trees()
+        .filter_map(|(name, _)| match name {
+            EnhancementTreeName::Class(tree) => Some((name, tree)),
+            _ => None,
+        })
+        .filter_map(|(name, tree)| {
+            let spent = builds
+                .iter()
+                .filter_map(|b| b.enhancements.get(name))
+                .map(|et| et.action_points_with(content))
+                .filter(|&ap| ap > 0)
+                .collect::<Vec<_>>();
+            if spent.is_empty() {
+                return None;
+            }
+            let total: u32 = spent.iter().map(|&ap| u32::from(ap)).sum();
+
+            Some(TreePopularity {
+                tree,
+                builds: spent.len(),
+                average_ap: total as f32 / spent.len() as f32,
+                tier_five: builds
+                    .iter()
+                    .filter(|b| b.tier_five == Some(name))
+                    .count(),
+            })
+        })
+        .collect()
+}
+
+fn tier_five_choices(
+    builds: &[CharacterBuild],
+) -> Vec<(EnhancementTreeName, usize)> {
+    let mut choices: Vec<(EnhancementTreeName, usize)> = Vec::new();
+    for tree in builds.iter().filter_map(|b| b.tier_five) {
+        match choices.iter_mut().find(|(t, _)| *t == tree) {
+            Some((_, n)) => *n += 1,
+            None => choices.push((tree, 1)),
+        }
+    }
+    // A stable sort, so ties stay in the order that they were first seen
+    choices.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+    choices
+}
+
+fn skill_allocation(builds: &[CharacterBuild]) -> Vec<SkillAllocation> {
+    Skill::ALL
+        .iter()
+        .filter_map(|&skill| {
+            let points = builds
+                .iter()
+                .filter_map(|b| b.skills.points_in_skill(skill))
+                .map(|ps| ps.iter().map(|&p| u32::from(p)).sum::<u32>())
+                .filter(|&p| p > 0)
+                .collect::<Vec<_>>();
+            if points.is_empty() {
+                return None;
+            }
+
+            Some(SkillAllocation {
+                skill,
+                builds: points.len(),
+                average_points: points.iter().sum::<u32>() as f32
+                    / builds.len() as f32,
+            })
+        })
+        .collect()
+}
+
+/// Every feat that the build takes, with the character level that it's
+/// taken at. Class feats at class levels that the build doesn't have are
+/// left out.
+fn all_feats(build: &CharacterBuild) -> impl Iterator<Item = (u8, &str)> + '_ {
+    let feats = &build.feats;
+
+    feats
+        .standard_feats
+        .iter()
+        .chain(feats.legend_feats.iter())
+        .chain(feats.class_feats.iter())
+        .chain(feats.race_feats.iter())
+        .filter_map(move |f| {
+            let level = match f.secondary_type {
+                SecondaryFeatType::Class(c) =>
+                    build.character_level_of(c, f.level)?,
+                _ => f.level,
+            };
+
+            Some((level, f.name.as_str()))
+        })
+}
+
+pub(crate) fn strings(ss: &[&str]) -> Vec<String> {
+    ss.iter().map(|&s| s.to_owned()).collect()
+}
+
+fn percentage(n: usize, of: usize) -> String {
+    if of == 0 {
+        return String::new();
+    }
+
+    format!("{:.0}%", n as f32 * 100.0 / of as f32)
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
@@ -1,5 +1,13 @@
 use super::*;
 
+/// The bundled content, with the catalogs of `test/catalog.txt`.
+fn catalog() -> content::Registry {
+    let mut content = content::Registry::bundled();
+    content.patch_file("./test/catalog.txt").unwrap();
+
+    content
+}
+
 #[test]
 fn stack_size() {
     println!(
@@ -47,6 +55,9 @@ fn wartrapper_test() {
     );
 
     let mut enhancements = Enhancements::default();
+    enhancements
+        .declare(EnhancementTreeName::Race(Race::Drow))
+        .class_levels = Some(20);
     let rank3 = Some(NonZeroU8::new(3).unwrap());
     enhancements.insert(
         EnhancementTreeName::Class(ClassEnhancementTree::Warchanter),
@@ -258,6 +269,20 @@ fn wartrapper_test() {
             Some(NonZeroU8::new(2).unwrap()),
         ),
     );
+    for (tree, source, class_levels) in [
+        (ClassEnhancementTree::Warchanter, Class::Bard, 15),
+        (ClassEnhancementTree::StalwartDefender, Class::Fighter, 4),
+        (ClassEnhancementTree::Swashbuckler, Class::Bard, 15),
+        (ClassEnhancementTree::Spellsinger, Class::Bard, 15),
+    ]
+    .iter()
+    {
+        let et = enhancements
+            .get_mut(EnhancementTreeName::Class(*tree))
+            .unwrap();
+        et.source = Some(*source);
+        et.class_levels = Some(*class_levels);
+    }
 
     let c = CharacterBuild {
         name: "Wartrapper".to_owned(),
@@ -307,6 +332,7 @@ fn wartrapper_test() {
             Some(Ability::Str),
             Some(Ability::Str),
             Some(Ability::Str),
+            None,
         ],
         skills,
         feats: Feats {
@@ -347,7 +373,7 @@ fn wartrapper_test() {
                     "Improved Bardic Music".to_owned(),
                 ),
             ],
-            legend_feats:   None,
+            legend_feats:   Vec::new(),
             class_feats:    vec![
                 Feat::new(
                     SecondaryFeatType::Class(Class::Fighter),
@@ -417,3 +443,1869 @@ fn wartrapper_test() {
 
     assert_eq!(parsed, c);
 }
+
+#[test]
+fn level_cap_test() {
+    let file = std::fs::File::open("./test/test4.build").unwrap();
+    let mut buf_reader = std::io::BufReader::new(file);
+
+    let parsed = parse(&mut buf_reader).unwrap();
+
+    assert_eq!(parsed.max_levels, LEVEL_CAP);
+    assert_eq!(parsed.heroic_levels(), HEROIC_LEVEL_CAP);
+    assert_eq!(parsed.levels, vec![Class::Fighter; 20]);
+    assert_eq!(parsed.levelup_levels().last(), Some(32));
+    assert_eq!(parsed.levelup_at(28), Some(Ability::Int));
+    assert_eq!(parsed.levelup_at(32), Some(Ability::Str));
+    assert_eq!(parsed.levelup_at(33), None);
+    assert_eq!(parsed.feats.legend_feats.len(), 2);
+    assert_eq!(parsed.feats.standard_feats.last().unwrap().level, 33);
+
+    let over_cap = format!(
+        "[Overview]\nRace: Human\nAlignment: True Neutral\nMaxLevels: {}\n",
+        LEVEL_CAP + 1,
+    );
+    match parse(&mut over_cap.as_bytes()) {
+        Err(ParseError::InvalidMaxLevel(m)) => assert_eq!(m, LEVEL_CAP + 1),
+        r => panic!("expected InvalidMaxLevel, got {:?}", r),
+    }
+
+    // Legendary feats must each fill a slot of their own, up to MaxLevels
+    let build = std::fs::read_to_string("./test/test4.build").unwrap();
+    let legend_feats = |max_levels: u8, feats: &str| {
+        let build = build
+            .replace("MaxLevels: 34", &format!("MaxLevels: {}", max_levels))
+            .replace(
+                "Legend: Legend 30\tScion of: Ethereal Plane\r\nLegend: \
+                 Legend 32\tEpic Strike\r\n",
+                feats,
+            );
+        parse(&mut build.as_bytes()).map(|b| b.feats.legend_feats.len())
+    };
+    let feat =
+        |level: u8| format!("Legend: Legend {}\tEpic Strike\r\n", level);
+    let feats =
+        |levels: &[u8]| levels.iter().map(|&l| feat(l)).collect::<String>();
+    assert_eq!(legend_feats(34, &feats(&[30, 32, 34])).unwrap(), 3);
+    assert_eq!(legend_feats(31, &feats(&[30])).unwrap(), 1);
+    assert!(matches!(
+        legend_feats(31, &feats(&[30, 32])),
+        Err(ParseError::TooManyLegendFeats),
+    ));
+    assert!(matches!(
+        legend_feats(34, &feats(&[30, 32, 34, 34])),
+        Err(ParseError::TooManyLegendFeats),
+    ));
+    assert!(matches!(
+        legend_feats(34, &feats(&[31])),
+        Err(ParseError::InvalidFeatLevel(31)),
+    ));
+    assert!(matches!(
+        legend_feats(32, &feats(&[32, 32])),
+        Err(ParseError::DuplicateLegendFeat(32)),
+    ));
+}
+
+#[test]
+fn names_round_trip() {
+    for &race in Race::ALL.iter() {
+        assert_eq!(race.to_string().parse(), Ok(race));
+    }
+    for &class in Class::ALL.iter() {
+        assert_eq!(class.to_string().parse(), Ok(class));
+    }
+    for &tree in ClassEnhancementTree::ALL.iter() {
+        let tree = EnhancementTreeName::Class(tree);
+        assert_eq!(tree.to_string().parse(), Ok(tree));
+    }
+    for &tree in GlobalEnhancementTree::ALL.iter() {
+        let tree = EnhancementTreeName::Global(tree);
+        assert_eq!(tree.to_string().parse(), Ok(tree));
+    }
+}
+
+#[test]
+fn new_content_test() {
+    let expected = [
+        (
+            "./test/test5.build",
+            Race::Tabaxi,
+            Class::Alchemist,
+            EnhancementTreeName::Class(ClassEnhancementTree::Bombardier),
+            vec![
+                EnhancementTreeName::Class(ClassEnhancementTree::Apothecary),
+                EnhancementTreeName::Class(ClassEnhancementTree::VileChemist),
+                EnhancementTreeName::Global(
+                    GlobalEnhancementTree::FeydarkIllusionist,
+                ),
+            ],
+        ),
+        (
+            "./test/test6.build",
+            Race::Shifter,
+            Class::Druid,
+            EnhancementTreeName::Class(ClassEnhancementTree::Blightcaster),
+            vec![
+                EnhancementTreeName::Race(Race::Shifter),
+                EnhancementTreeName::Class(ClassEnhancementTree::DarkApostate),
+            ],
+        ),
+        (
+            "./test/test7.build",
+            Race::EladrinChaosmancer,
+            Class::Sorcerer,
+            EnhancementTreeName::Class(ClassEnhancementTree::WildMage),
+            vec![EnhancementTreeName::Race(Race::EladrinChaosmancer)],
+        ),
+        (
+            "./test/test8.build",
+            Race::DhampirDarkBargainer,
+            Class::Warlock,
+            EnhancementTreeName::Class(ClassEnhancementTree::AcolyteOfTheSkin),
+            vec![
+                EnhancementTreeName::Race(Race::DhampirDarkBargainer),
+                EnhancementTreeName::Class(ClassEnhancementTree::DarkHunter),
+            ],
+        ),
+        (
+            "./test/test9.build",
+            Race::RazorclawShifter,
+            Class::Barbarian,
+            EnhancementTreeName::Class(ClassEnhancementTree::Stormsinger),
+            vec![EnhancementTreeName::Race(Race::RazorclawShifter)],
+        ),
+    ];
+
+    for (path, race, class, tier_five, other_trees) in expected.iter() {
+        let file = std::fs::File::open(path).unwrap();
+        let mut buf_reader = std::io::BufReader::new(file);
+
+        let parsed = parse(&mut buf_reader).unwrap();
+
+        assert_eq!(parsed.race, *race);
+        assert_eq!(parsed.levels[0], *class);
+        assert_eq!(parsed.tier_five, Some(*tier_five));
+        assert!(parsed.enhancements.contains(*tier_five));
+        for tree in other_trees.iter() {
+            assert!(parsed.enhancements.contains(*tree));
+        }
+    }
+
+    let file = std::fs::File::open("./test/test5.build").unwrap();
+    let mut buf_reader = std::io::BufReader::new(file);
+    let alchemist = parse(&mut buf_reader).unwrap();
+    let spellbook = alchemist.spells.spellbooks[0].as_ref().unwrap();
+    assert_eq!(spellbook.class, Class::Alchemist);
+    assert_eq!(spellbook.spells_by_level.len(), 6);
+    assert_eq!(spellbook.spells_by_level[1][2], None);
+    assert_eq!(
+        alchemist.feats.race_feats[0].secondary_type,
+        SecondaryFeatType::Race(Race::Tabaxi),
+    );
+    assert_eq!(
+        alchemist.feats.class_feats[3].secondary_type,
+        SecondaryFeatType::Class(Class::Alchemist),
+    );
+
+    let fighter_spell = "[Overview]\nRace: Human\nAlignment: True \
+                         Neutral\nClass: Fighter\n[Spells]\nSpell: Fighter \
+                         1\tHaste\n";
+    match parse(&mut fighter_spell.as_bytes()) {
+        Err(ParseError::InvalidSpellLevel(Class::Fighter, 1)) => (),
+        r => panic!("expected InvalidSpellLevel, got {:?}", r),
+    }
+}
+
+#[test]
+fn bundled_content_test() {
+    let content = content::Registry::bundled();
+
+    assert!(content.missing().is_empty(), "{:?}", content.missing());
+
+    let bard = content.class(Class::Bard).unwrap();
+    assert_eq!(bard.skill_points, 6);
+    assert!(bard.class_skills.contains(&Skill::UseMagicDevice));
+    assert_eq!(content.class(Class::Fighter).unwrap().max_spell_level, 0);
+    assert_eq!(bard.slot_unlock(1, 4), Some(16));
+    assert_eq!(bard.slot_unlock(6, 3), Some(19));
+    assert_eq!(bard.slot_unlock(6, 4), None);
+    let wizard = content.class(Class::Wizard).unwrap();
+    assert_eq!(wizard.slot_unlock(9, 7), Some(17));
+    assert_eq!(wizard.slot_unlock(10, 0), None);
+
+    let vanguard = content.tree_by_name("Vanguard").unwrap();
+    assert_eq!(
+        content.tree(vanguard).unwrap().sources,
+        vec![Class::Fighter, Class::Paladin],
+    );
+    assert_eq!(
+        content.resolve_tree(
+            "Arcane Archer",
+            Some(EnhancementTreeType::RaceClass),
+            None,
+        ),
+        Some(EnhancementTreeName::RaceClass(
+            RaceClassEnhancementTree::ElfArcaneArcher
+        )),
+    );
+    assert_eq!(
+        content.resolve_tree("Arcane Archer", None, Some(Class::Ranger)),
+        Some(EnhancementTreeName::Class(
+            ClassEnhancementTree::ArcaneArcher
+        )),
+    );
+    assert_eq!(
+        content
+            .tree(EnhancementTreeName::Race(Race::Drow))
+            .unwrap()
+            .race,
+        Some(Race::Drow),
+    );
+
+    // Every entry can be found by its abbreviation again
+    for &race in Race::ALL.iter() {
+        let abbreviation = &content.race(race).unwrap().abbreviation;
+        assert_eq!(content.race_by_abbreviation(abbreviation), Some(race));
+    }
+    for &class in Class::ALL.iter() {
+        let abbreviation = &content.class(class).unwrap().abbreviation;
+        assert_eq!(content.class_by_abbreviation(abbreviation), Some(class));
+    }
+    for (tree, info) in content.trees() {
+        assert_eq!(
+            content.tree_by_abbreviation(&info.abbreviation),
+            Some(tree),
+        );
+    }
+}
+
+#[test]
+fn content_patch_test() {
+    let mut content = content::Registry::bundled();
+    let patch = "[Races]\nRace: Human (Updated)\nId: Human\nAlias: \
+                 Human\n[Trees]\nTree: Song of the Warchanter\nId: \
+                 Warchanter\nType: Class\nAlias: Warchanter\n";
+    content.patch(&mut patch.as_bytes()).unwrap();
+
+    assert_eq!(content.race_by_name("Human (Updated)"), Some(Race::Human));
+    assert_eq!(
+        content
+            .tree(EnhancementTreeName::Race(Race::Human))
+            .unwrap()
+            .name,
+        "Human (Updated)",
+    );
+    assert_eq!(content.race(Race::Human).unwrap().abbreviation, "hum");
+    let warchanter = content
+        .tree(EnhancementTreeName::Class(ClassEnhancementTree::Warchanter));
+    assert_eq!(warchanter.unwrap().sources, vec![Class::Bard]);
+
+    let file = std::fs::File::open("./test/test0.build").unwrap();
+    let mut buf_reader = std::io::BufReader::new(file);
+    let parsed = parse_with(&mut buf_reader, &content).unwrap();
+    assert_eq!(parsed.race, Race::Human);
+
+    // New entries can be added, and are then used like the bundled ones
+    let new_patch = "[Classes]\nClass: Gunslinger\nId: Gunslinger\nHitDie: \
+                     8\nSkillPoints: 4\nBaseAttack: Full\nGoodSaves: \
+                     Reflex\nClassSkills: Spot, Tumble\nAlignments: Chaotic \
+                     Good, Chaotic Neutral\n[Trees]\nTree: Deadeye\nId: \
+                     Deadeye\nType: Class\nSource: Gunslinger\n";
+    content.patch(&mut new_patch.as_bytes()).unwrap();
+    let gunslinger = Class::Other(content::ContentId::new("Gunslinger"));
+    let deadeye = EnhancementTreeName::Class(ClassEnhancementTree::Other(
+        content::ContentId::new("Deadeye"),
+    ));
+    assert_eq!(content.class_by_name("Gunslinger"), Some(gunslinger));
+    assert_eq!(
+        content.class(gunslinger).unwrap().abbreviation,
+        "Gunslinger"
+    );
+    assert_eq!(content.tree_by_name("Deadeye"), Some(deadeye));
+    assert_eq!(content.tree(deadeye).unwrap().sources, vec![gunslinger]);
+
+    let build = "[Overview]\nRace: Human\nAlignment: Chaotic \
+                 Good\nMaxLevels: 2\nClass: Gunslinger\nLevel: \
+                 1\tGunslinger\nLevel: 2\tGunslinger\n[Enhancements]\nTree: \
+                 Deadeye\nType: Class\nSource: Gunslinger\nClassLevels: 2\n";
+    let parsed = parse_with(&mut build.as_bytes(), &content).unwrap();
+    assert_eq!(parsed.levels, vec![gunslinger, gunslinger]);
+    assert_eq!(parsed.enhancements.trees[0].name, deadeye);
+    assert_eq!(parsed.enhancements.trees[0].source, Some(gunslinger));
+    assert_eq!(validate::validate_with(&parsed, &content), Vec::new());
+    assert_eq!(share::decode(&share::encode(&parsed)), Ok(parsed));
+
+    let lawful = build.replace("Chaotic Good", "Lawful Good");
+    let parsed = parse_with(&mut lawful.as_bytes(), &content).unwrap();
+    assert_eq!(
+        validate::validate_with(&parsed, &content),
+        vec![validate::Violation::AlignmentForbidsClass(
+            gunslinger,
+            Alignment::LawfulGood,
+        )],
+    );
+
+    // The former `Id` of the Halfling
+    let old_patch = "[Races]\nRace: Halfling\nId: Halfing\nAlias: Hobbit\n";
+    content.patch(&mut old_patch.as_bytes()).unwrap();
+    assert_eq!(content.race_by_name("Hobbit"), Some(Race::Halfling));
+    #[allow(deprecated)]
+    let halfing = Race::Halfing;
+    assert_eq!(halfing, Race::Halfling);
+
+    let bad_patch = "[Classes]\nClass: Gunslinger\nId: gun slinger\n";
+    match content.patch(&mut bad_patch.as_bytes()) {
+        Err(content::ContentError::InvalidId(id)) =>
+            assert_eq!(id, "gun slinger"),
+        r => panic!("expected InvalidId, got {:?}", r),
+    }
+
+    let bad_patch = "[Races]\nRace: Drow\nId: Drow\nAbbreviation: dr_w\n";
+    match content.patch(&mut bad_patch.as_bytes()) {
+        Err(content::ContentError::InvalidAbbreviation(a)) =>
+            assert_eq!(a, "dr_w"),
+        r => panic!("expected InvalidAbbreviation, got {:?}", r),
+    }
+
+    // A patch with an error leaves nothing of itself behind, not even the
+    // records before the error
+    let half_patch = "[Races]\nRace: Sea Elf\nId: SeaElf\nSkillPoints: \
+                      0\n[Classes]\nClass: Bad\nId: Bad\nHitDie: d6\n";
+    assert!(content.patch(&mut half_patch.as_bytes()).is_err());
+    assert_eq!(content.race_by_name("Sea Elf"), None);
+    assert_eq!(content.race_by_name("Drow"), Some(Race::Drow));
+}
+
+#[test]
+fn shared_tree_test() {
+    let vanguard = EnhancementTreeName::Class(ClassEnhancementTree::Vanguard);
+    let build = "[Overview]\nRace: Human\nAlignment: Lawful Good\nMaxLevels: \
+                 20\nClass: Paladin\n[Enhancements]\nTier5: Vanguard\nTree: \
+                 Vanguard\nType: Class\nSource: Paladin\nClassLevels: \
+                 20\nAbility: Tier 1: Shield Specialization\n";
+    let parsed = parse(&mut build.as_bytes()).unwrap();
+
+    assert_eq!(parsed.tier_five, Some(vanguard));
+    let tree = parsed.enhancements.get(vanguard).unwrap();
+    assert_eq!(tree.source, Some(Class::Paladin));
+    assert_eq!(tree.class_levels, Some(20));
+
+    let file = std::fs::File::open("./test/test2.build").unwrap();
+    let mut buf_reader = std::io::BufReader::new(file);
+    let parsed = parse(&mut buf_reader).unwrap();
+    for tree in parsed.enhancements.trees.iter() {
+        let source = match tree.source {
+            Some(source) => source,
+            None => continue,
+        };
+        assert_eq!(
+            tree.class_levels,
+            Some(parsed.levels.iter().filter(|&&c| c == source).count() as u8),
+        );
+    }
+
+    let elf = "[Overview]\nRace: Elf\nAlignment: True Neutral\nClass: \
+               Ranger\n[Enhancements]\nTree: Arcane Archer\nType: \
+               RaceClass\nAbility: Tier 0: Arcane Archer\nTree: Arcane \
+               Archer\nType: Class\nSource: Ranger\nAbility: Tier 0: Arcane \
+               Archer\n";
+    let parsed = parse(&mut elf.as_bytes()).unwrap();
+    assert_eq!(parsed.enhancements.trees.len(), 2);
+
+    let wrong_source = "[Overview]\nRace: Human\nAlignment: True \
+                        Neutral\nClass: Rogue\n[Enhancements]\nTree: \
+                        Eldritch Knight\nType: Class\nSource: Rogue\n";
+    match parse(&mut wrong_source.as_bytes()) {
+        Err(ParseError::WrongEnhancementTreeSource(
+            EnhancementTreeName::Class(ClassEnhancementTree::EldritchKnight),
+            Class::Rogue,
+        )) => (),
+        r => panic!("expected WrongEnhancementTreeSource, got {:?}", r),
+    }
+}
+
+#[test]
+fn legacy_names_test() {
+    let content = content::Registry::bundled();
+    let file = std::fs::File::open("./test/test10.build").unwrap();
+    let mut buf_reader = std::io::BufReader::new(file);
+    let (build, migrations) =
+        migrate::parse_and_migrate(&mut buf_reader, &content).unwrap();
+
+    assert_eq!(build.race, Race::Halfling);
+    assert_eq!(build.levels[4], Class::FavoredSoul);
+    assert_eq!(
+        build.feats.standard_feats[0].name,
+        "Exotic Weapon: Shuriken",
+    );
+    assert_eq!(build.feats.standard_feats[1].name, "Two Handed Fighting");
+    let stalwart =
+        EnhancementTreeName::Class(ClassEnhancementTree::StalwartDefender);
+    assert_eq!(
+        build.enhancements.get(stalwart).unwrap().tiers[0][0].name,
+        "Stalwart Defense",
+    );
+
+    let migration = |kind, from: &str, to: &str| migrate::Migration {
+        kind,
+        from: from.to_owned(),
+        to: to.to_owned(),
+    };
+    assert_eq!(
+        migrations,
+        vec![
+            migration(migrate::NameKind::Race, "Halfing", "Halfling"),
+            migration(
+                migrate::NameKind::Class,
+                "Favoured Soul",
+                "Favored Soul",
+            ),
+            migration(
+                migrate::NameKind::EnhancementTree,
+                "Halfing",
+                "Halfling",
+            ),
+            migration(
+                migrate::NameKind::Feat,
+                "Exotic Weapon Proficiency: Shuriken",
+                "Exotic Weapon: Shuriken",
+            ),
+            migration(
+                migrate::NameKind::Feat,
+                "Two-Handed Fighting",
+                "Two Handed Fighting",
+            ),
+            migration(
+                migrate::NameKind::Feat,
+                "Improved Two-Handed Fighting",
+                "Improved Two Handed Fighting",
+            ),
+            migration(
+                migrate::NameKind::Enhancement(stalwart),
+                "Stalwart Defensive Stance",
+                "Stalwart Defense",
+            ),
+        ],
+    );
+
+    // The upgraded build is written with current names only, and reads back
+    // without anything left to migrate.
+    let mut upgraded = Vec::new();
+    write_with(&build, &content, &mut upgraded).unwrap();
+    let (reparsed, remigrations) =
+        migrate::parse_and_migrate(&mut upgraded.as_slice(), &content)
+            .unwrap();
+    assert_eq!(reparsed, build);
+    assert!(remigrations.is_empty(), "{:?}", remigrations);
+}
+
+#[test]
+fn write_round_trip_test() {
+    let paths = [
+        "./test/brd15_ftr4_rog1_-_drw_-_trapper_-_WC_StD_-_THFs_none_BPlat_-_Wartrapper.build",
+        "./test/test0.build",
+        "./test/test1.build",
+        "./test/test2.build",
+        "./test/test3.build",
+        "./test/test4.build",
+        "./test/test5.build",
+        "./test/test6.build",
+        "./test/test7.build",
+        "./test/test8.build",
+        "./test/test9.build",
+    ];
+
+    for path in paths.iter() {
+        let original = std::fs::read_to_string(path).unwrap();
+        let parsed = parse(&mut original.as_bytes()).unwrap();
+
+        let mut written = Vec::new();
+        write(&parsed, &mut written).unwrap();
+        let written = String::from_utf8(written).unwrap();
+        assert_eq!(
+            parse(&mut written.as_bytes()).unwrap(),
+            parsed,
+            "{}",
+            path
+        );
+
+        // Trees are written in the order they were declared, empty or not,
+        // so writing is repeatable.
+        let tree_lines = |build: &str| -> Vec<String> {
+            build
+                .lines()
+                .filter(|l| l.starts_with("Tree:"))
+                .map(str::to_owned)
+                .collect()
+        };
+        assert_eq!(tree_lines(&written), tree_lines(&original), "{}", path);
+        let mut rewritten = Vec::new();
+        write(&parse(&mut written.as_bytes()).unwrap(), &mut rewritten)
+            .unwrap();
+        assert_eq!(String::from_utf8(rewritten).unwrap(), written, "{}", path);
+
+        // The skills grid is laid out exactly as CBL does it.
+        for line in original.lines().filter(|l| {
+            l.starts_with("Balance:")
+                || l.starts_with("Disable:")
+                || l.starts_with("Heal   :")
+        }) {
+            assert!(written.contains(line), "{}: {:?}", path, line);
+        }
+    }
+}
+
+#[test]
+fn iconic_start_test() {
+    use validate::{validate, Violation};
+
+    // Razorclaw Shifters start as Barbarians
+    let build = "[Overview]\nRace: Razorclaw Shifter\nAlignment: Chaotic \
+                 Good\nMaxLevels: 2\nClass: Bard\nClass: Barbarian\nLevel: \
+                 1\tBard\nLevel: 2\tBarbarian\n";
+    let parsed = parse(&mut build.as_bytes()).unwrap();
+    assert_eq!(
+        validate(&parsed),
+        vec![Violation::WrongStartingClass {
+            race:     Race::RazorclawShifter,
+            expected: Class::Barbarian,
+            found:    Class::Bard,
+        }],
+    );
+
+    let build = build.replace(
+        "1\tBard\nLevel: 2\tBarbarian",
+        "1\tBarbarian\nLevel: 2\tBard",
+    );
+    assert_eq!(validate(&parse(&mut build.as_bytes()).unwrap()), Vec::new());
+}
+
+#[test]
+fn validation_test() {
+    use validate::{
+        unchecked_trees,
+        unchecked_trees_with,
+        validate,
+        validate_with,
+        Violation,
+    };
+
+    for entry in std::fs::read_dir("./test").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().map_or(true, |e| e != "build") {
+            continue;
+        }
+        let file = std::fs::File::open(&path).unwrap();
+        let parsed = parse(&mut std::io::BufReader::new(file)).unwrap();
+        let violations = validate(&parsed);
+
+        let expected = if path.ends_with("test10.build") {
+            vec![Violation::EmptyTierFive(EnhancementTreeName::Class(
+                ClassEnhancementTree::AngelOfVengeance,
+            ))]
+        } else {
+            Vec::new()
+        };
+        assert_eq!(violations, expected, "{}", path.display());
+    }
+
+    let build = "[Overview]\nRace: Bladeforged\nAlignment: Chaotic \
+                 Neutral\nMaxLevels: 4\nClass: Monk\nClass: Paladin\nClass: \
+                 Druid\nLevel: 1\tMonk\nLevel: 2\tPaladin\nLevel: \
+                 3\tDruid\nLevel: 4\tMonk\n[Enhancements]\nTree: Elf\nType: \
+                 Race\nTree: Arcane Archer\nType: RaceClass\nTree: \
+                 Bladeforged\nType: Race\n";
+    let parsed = parse(&mut build.as_bytes()).unwrap();
+    assert_eq!(
+        validate(&parsed),
+        vec![
+            Violation::WrongStartingClass {
+                race:     Race::Bladeforged,
+                expected: Class::Paladin,
+                found:    Class::Monk,
+            },
+            Violation::AlignmentForbidsClass(
+                Class::Monk,
+                Alignment::ChaoticNeutral,
+            ),
+            Violation::AlignmentForbidsClass(
+                Class::Paladin,
+                Alignment::ChaoticNeutral,
+            ),
+            Violation::TreeOfOtherRace(
+                EnhancementTreeName::Race(Race::Elf),
+                Race::Elf
+            ),
+            Violation::TreeOfOtherRace(
+                EnhancementTreeName::RaceClass(
+                    RaceClassEnhancementTree::ElfArcaneArcher,
+                ),
+                Race::Elf,
+            ),
+        ]
+    );
+
+    let kensei = EnhancementTreeName::Class(ClassEnhancementTree::Kensei);
+    let warchanter =
+        EnhancementTreeName::Class(ClassEnhancementTree::Warchanter);
+    let build = "[Overview]\nRace: Human\nAlignment: True \
+                 Neutral\nMaxLevels: 4\nClass: Fighter\nClass: Bard\nLevel: \
+                 1\tFighter\nLevel: 2\tFighter\nLevel: 3\tBard\nLevel: \
+                 4\tFighter\n[Enhancements]\nTier5: Kensei\nTree: \
+                 Kensei\nType: Class\nAbility: Tier 3: Weapon Group \
+                 Specialization\nAbility: Tier 4: Exotic Weapon \
+                 Mastery\nAbility: Tier 5: One with the Blade\nTree: \
+                 Warchanter\nType: Class\nAbility: Tier 5: Howl of the \
+                 North\nTree: Archmage\nType: Class\n";
+    let parsed = parse(&mut build.as_bytes()).unwrap();
+    let catalog = catalog();
+    assert_eq!(
+        validate_with(&parsed, &catalog),
+        vec![
+            Violation::TierLocked {
+                tree:        kensei,
+                tier:        4,
+                enhancement: "Exotic Weapon Mastery".to_owned(),
+                needed:      4,
+                found:       3,
+            },
+            Violation::TierLocked {
+                tree:        kensei,
+                tier:        5,
+                enhancement: "One with the Blade".to_owned(),
+                needed:      12,
+                found:       3,
+            },
+            Violation::TierFiveOutsideTree(
+                warchanter,
+                "Howl of the North".to_owned(),
+            ),
+            Violation::TierLocked {
+                tree:        warchanter,
+                tier:        5,
+                enhancement: "Howl of the North".to_owned(),
+                needed:      12,
+                found:       1,
+            },
+            Violation::NoLevelsForTree(EnhancementTreeName::Class(
+                ClassEnhancementTree::Archmage,
+            )),
+        ]
+    );
+    // Warchanter is catalogued, and Archmage has no enhancements taken
+    assert_eq!(unchecked_trees_with(&parsed, &catalog), vec![kensei]);
+    // The bundled content catalogs neither
+    assert_eq!(unchecked_trees(&parsed), vec![kensei, warchanter]);
+
+    let build = "[Overview]\nRace: Human\nAlignment: True Neutral\nClass: \
+                 Fighter\n[Enhancements]\nTree: Stalwart Defender\nType: \
+                 Class\nAbility: Tier 0: Stalwart Defensive Stance\nAbility: \
+                 Tier 1: Improved Stalwart Defense: Hardy Defense\nAbility: \
+                 Tier 1: Stalwart Defensive Mastery (Rank 4)\nAbility: Tier \
+                 2: Shield Mastery\nAbility: Tier 3: Greater Stalwart \
+                 Defense\nAbility: Tier 4: Greater Stalwart Defense: Hardy \
+                 Defense\n";
+    let parsed = parse(&mut build.as_bytes()).unwrap();
+    let stalwart =
+        EnhancementTreeName::Class(ClassEnhancementTree::StalwartDefender);
+    assert_eq!(validate(&parsed), Vec::new());
+    assert_eq!(
+        validate_with(&parsed, &catalog),
+        vec![
+            Violation::InvalidChoice {
+                tree:        stalwart,
+                enhancement: "Improved Stalwart Defense".to_owned(),
+                choice:      Some("Hardy Defense".to_owned()),
+            },
+            Violation::TooManyRanks {
+                tree:        stalwart,
+                enhancement: "Stalwart Defensive Mastery".to_owned(),
+                ranks:       4,
+                max:         3,
+            },
+            Violation::UnknownEnhancement {
+                tree:        stalwart,
+                tier:        2,
+                enhancement: "Shield Mastery".to_owned(),
+            },
+            Violation::InvalidChoice {
+                tree:        stalwart,
+                enhancement: "Greater Stalwart Defense".to_owned(),
+                choice:      None,
+            },
+        ]
+    );
+
+    let build = "[Overview]\nRace: Human\nAlignment: True Neutral\nClass: \
+                 Bard\n[Enhancements]\nTree: Warchanter\nType: \
+                 Class\nAbility: Tier 0: Skaldic: Constitution\nAbility: \
+                 Tier 2: Iced Edges (Rank 2)\n";
+    let parsed = parse(&mut build.as_bytes()).unwrap();
+    assert_eq!(
+        validate_with(&parsed, &catalog),
+        vec![Violation::MissingPrerequisite {
+            tree:        EnhancementTreeName::Class(
+                ClassEnhancementTree::Warchanter,
+            ),
+            enhancement: "Iced Edges".to_owned(),
+            requires:    "Enchant Weapon".to_owned(),
+        }]
+    );
+    assert_eq!(unchecked_trees_with(&parsed, &catalog), Vec::new());
+}
+
+#[test]
+fn level_snapshot_test() {
+    let file = std::fs::File::open(
+        "./test/brd15_ftr4_rog1_-_drw_-_trapper_-_WC_StD_-_THFs_none_BPlat_-_Wartrapper.build"
+    ).unwrap();
+    let parsed = parse(&mut std::io::BufReader::new(file)).unwrap();
+    let snapshot = parsed.at_level(12);
+
+    assert_eq!(snapshot.level, 12);
+    assert_eq!(snapshot.levels.len(), 12);
+    assert_eq!(
+        snapshot.class_levels,
+        vec![(Class::Rogue, 1), (Class::Bard, 8), (Class::Fighter, 3),]
+    );
+
+    // 10 points buy 16 Strength, which is increased at levels 4, 8 and 12.
+    // Drow have -2 Constitution.
+    let scores = snapshot.ability_scores.unwrap();
+    assert_eq!(scores[Ability::Str], 19);
+    assert_eq!(scores[Ability::Dex], 10);
+    assert_eq!(scores[Ability::Con], 12);
+    assert_eq!(scores[Ability::Int], 16);
+
+    // Of the three classes, Spot is only a class skill of Rogues, so the 20
+    // points after level 1 are worth half a rank each.
+    let spot = snapshot.skill_ranks.iter().find(|(s, _)| *s == Skill::Spot);
+    assert_eq!(spot, Some(&(Skill::Spot, 4.0 + 10.0)));
+
+    let feats: Vec<(u8, &str)> = snapshot
+        .feats
+        .iter()
+        .map(|(l, f)| (*l, f.name.as_str()))
+        .collect();
+    assert_eq!(
+        feats,
+        vec![
+            (1, "Power Attack"),
+            (3, "Force of Personality"),
+            (3, "Cleave"),
+            (6, "Great Cleave"),
+            (9, "Extend Spell"),
+            (11, "Improved Critical: Slashing"),
+            (12, "Quicken Spell"),
+        ]
+    );
+
+    // Bards learn 4th level spells at level 10, and by level 8 have four
+    // slots of 1st and 2nd level spells and three of 3rd level spells.
+    assert_eq!(snapshot.spells.len(), 11);
+    assert!(snapshot
+        .spells
+        .iter()
+        .all(|&(c, l, _)| c == Class::Bard && l <= 3));
+    let spells = &snapshot.spells;
+    let at_spell_level =
+        |n| spells.iter().filter(|&&(_, l, _)| l == n).count();
+    assert_eq!(
+        (at_spell_level(1), at_spell_level(2), at_spell_level(3)),
+        (4, 4, 3)
+    );
+
+    assert_eq!(
+        snapshot.unlocked_tiers,
+        vec![
+            (EnhancementTreeName::Race(Race::Drow), 4),
+            (
+                EnhancementTreeName::Class(ClassEnhancementTree::Warchanter),
+                4
+            ),
+            (
+                EnhancementTreeName::Class(
+                    ClassEnhancementTree::StalwartDefender
+                ),
+                3,
+            ),
+            (
+                EnhancementTreeName::Class(ClassEnhancementTree::Swashbuckler),
+                4
+            ),
+            (
+                EnhancementTreeName::Class(ClassEnhancementTree::Spellsinger),
+                4
+            ),
+        ]
+    );
+
+    let last = parsed.at_level(LEVEL_CAP);
+    assert_eq!(last.level, 20);
+    assert_eq!(last.feats.len(), 10);
+    assert!(last.unlocked_tiers.contains(&(
+        EnhancementTreeName::Class(ClassEnhancementTree::Warchanter),
+        5,
+    )));
+}
+
+#[test]
+fn leveling_guide_test() {
+    use guide::{guide_levels, leveling_guide, GuideFormat};
+
+    let file = std::fs::File::open(
+        "./test/brd15_ftr4_rog1_-_drw_-_trapper_-_WC_StD_-_THFs_none_BPlat_-_Wartrapper.build"
+    ).unwrap();
+    let parsed = parse(&mut std::io::BufReader::new(file)).unwrap();
+    let levels = guide_levels(&parsed, &content::registry());
+    assert_eq!(levels.len(), 20);
+
+    let level3 = &levels[2];
+    assert_eq!(level3.class, Some((Class::Fighter, 1)));
+    assert_eq!(level3.ability_increase, None);
+    assert_eq!(
+        level3.skill_points,
+        vec![
+            (Skill::DisableDevice, 2),
+            (Skill::Search, 2),
+            (Skill::Spot, 1),
+        ]
+    );
+    let feats: Vec<&str> =
+        level3.feats.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(feats, vec!["Force of Personality", "Cleave"]);
+    assert_eq!(
+        level3.tiers,
+        vec![
+            (EnhancementTreeName::Race(Race::Drow), 3),
+            (
+                EnhancementTreeName::Class(
+                    ClassEnhancementTree::StalwartDefender
+                ),
+                0,
+            ),
+            (
+                EnhancementTreeName::Class(
+                    ClassEnhancementTree::StalwartDefender
+                ),
+                1,
+            ),
+        ]
+    );
+
+    // Every spell is learned exactly once, and Strength is increased at
+    // every fourth level.
+    let spells: usize = levels.iter().map(|l| l.spells.len()).sum();
+    assert_eq!(spells, 19);
+
+    // Level 6 is Bard level 4, which learns two slots of 2nd level spells;
+    // the other two come at Bard levels 5 and 7.
+    let spell_levels = |l: &guide::GuideLevel| -> Vec<usize> {
+        l.spells.iter().map(|&(_, sl, _)| sl).collect()
+    };
+    assert_eq!(spell_levels(&levels[5]), vec![2, 2]);
+    assert_eq!(spell_levels(&levels[6]), vec![1, 2]);
+    assert_eq!(spell_levels(&levels[7]), vec![]);
+    assert_eq!(spell_levels(&levels[8]), vec![2, 3, 3]);
+    let increases: Vec<u8> = levels
+        .iter()
+        .filter(|l| l.ability_increase == Some(Ability::Str))
+        .map(|l| l.level)
+        .collect();
+    assert_eq!(increases, vec![4, 8, 12, 16, 20]);
+
+    let markdown = leveling_guide(&parsed, GuideFormat::Markdown);
+    assert!(markdown.starts_with("## Leveling Guide\n"));
+    assert!(markdown.contains(
+        "\n### Level 3: Fighter 1\n\n- **Skills:** Disable Device +2, Search \
+         +2, Spot +1\n- **Feats:** Force of Personality, Cleave (Fighter \
+         bonus feat)\n"
+    ));
+    let text = leveling_guide(&parsed, GuideFormat::PlainText);
+    assert!(
+        text.contains("\nLevel 4: Bard 2\n    Ability increase: Strength\n")
+    );
+}
+
+#[test]
+fn edit_test() {
+    use edit::EditError;
+    use validate::Violation;
+
+    let file = std::fs::File::open(
+        "./test/brd15_ftr4_rog1_-_drw_-_trapper_-_WC_StD_-_THFs_none_BPlat_-_Wartrapper.build"
+    ).unwrap();
+    let original = parse(&mut std::io::BufReader::new(file)).unwrap();
+    let mut build = original.clone();
+
+    build.swap_levels(2, 3).unwrap();
+    assert_eq!(&build.levels[1..3], &[Class::Fighter, Class::Bard]);
+    for &skill in Skill::ALL.iter() {
+        assert_eq!(
+            build.skills.points_in_skill_at_level(skill, 2),
+            original.skills.points_in_skill_at_level(skill, 1),
+        );
+    }
+    build.swap_levels(3, 2).unwrap();
+    assert_eq!(build, original);
+    assert_eq!(build.swap_levels(0, 1), Err(EditError::InvalidLevel(0)));
+    assert_eq!(build.swap_levels(1, 21), Err(EditError::InvalidLevel(21)));
+
+    assert_eq!(
+        build.set_class_at(2, Class::Wizard),
+        Err(EditError::TooManyClasses),
+    );
+    assert_eq!(
+        build.set_class_at(15, Class::Bard),
+        Err(EditError::ClassFeatOutOfRange {
+            feat:        "Improved Two Handed Fighting".to_owned(),
+            class:       Class::Fighter,
+            class_level: 4,
+        }),
+    );
+    assert_eq!(build, original);
+    // Fighters get fewer skill points than Rogues.
+    assert_eq!(
+        build.set_class_at(1, Class::Fighter),
+        Err(EditError::Violation(Violation::TooManySkillPoints {
+            level:     1,
+            spent:     44,
+            available: 20,
+        })),
+    );
+    assert_eq!(
+        build.set_class_at(1, Class::Monk),
+        Err(EditError::Violation(Violation::AlignmentForbidsClass(
+            Class::Monk,
+            Alignment::TrueNeutral,
+        ))),
+    );
+    // Without the Rogue level first, Disable Device is a cross-class skill
+    // until level 20.
+    assert_eq!(
+        build.swap_levels(1, 20),
+        Err(EditError::Violation(Violation::TooManySkillRanks {
+            skill: Skill::DisableDevice,
+            level: 4,
+            ranks: 4.0,
+            max:   3.5,
+        })),
+    );
+    assert_eq!(build, original);
+    let warchanter =
+        EnhancementTreeName::Class(ClassEnhancementTree::Warchanter);
+
+    build.move_feat("Power Attack", 3).unwrap();
+    let standard: Vec<(u8, &str)> = build
+        .feats
+        .standard_feats
+        .iter()
+        .map(|f| (f.level, f.name.as_str()))
+        .take(2)
+        .collect();
+    assert_eq!(
+        standard,
+        vec![(3, "Power Attack"), (1, "Force of Personality")]
+    );
+    assert_eq!(
+        build.move_feat("Cleave", 5),
+        Err(EditError::InvalidLevel(5))
+    );
+    assert_eq!(
+        build.move_feat("Power Attack", 4),
+        Err(EditError::NoFeatSlot {
+            feat:  "Power Attack".to_owned(),
+            level: 4,
+        }),
+    );
+    assert_eq!(
+        build.move_feat("Cleave", 3),
+        Err(EditError::NoFeatSlot {
+            feat:  "Cleave".to_owned(),
+            level: 3,
+        }),
+    );
+    assert_eq!(
+        build.move_feat("Cleaves", 1),
+        Err(EditError::NoSuchFeat("Cleaves".to_owned())),
+    );
+
+    assert_eq!(
+        build.set_stat_points(BuildType::Champion, Ability::Str, 10),
+        Err(EditError::BuildTypeDisabled(BuildType::Champion)),
+    );
+    assert_eq!(
+        build.set_stat_points(BuildType::Adventurer, Ability::Dex, 1),
+        Err(EditError::TooManyStatPoints {
+            build_type: BuildType::Adventurer,
+            total:      29,
+            max:        28,
+        }),
+    );
+    build
+        .set_stat_points(BuildType::Adventurer, Ability::Str, 8)
+        .unwrap();
+    build
+        .set_stat_points(BuildType::Adventurer, Ability::Dex, 2)
+        .unwrap();
+    assert_eq!(build.adventurer_stats.as_ref().unwrap()[Ability::Dex], 2);
+
+    // Spot is a class skill of Rogues, so level 1's 4 points are the most.
+    assert_eq!(
+        build.set_skill_points(Skill::Spot, 1, 5),
+        Err(EditError::Violation(
+            validate::Violation::TooManySkillRanks {
+                skill: Skill::Spot,
+                level: 1,
+                ranks: 5.0,
+                max:   4.0,
+            }
+        )),
+    );
+    assert_eq!(build.skills, original.skills);
+    // Level 20 already spends all 9 of its points.
+    assert_eq!(
+        build.set_skill_points(Skill::Swim, 20, 2),
+        Err(EditError::Violation(Violation::TooManySkillPoints {
+            level:     20,
+            spent:     11,
+            available: 9,
+        })),
+    );
+    assert_eq!(build.skills, original.skills);
+
+    let catalog = catalog();
+    build
+        .set_enhancement_rank_with(warchanter, 3, "High Spirits", 3, &catalog)
+        .unwrap();
+    assert_eq!(
+        build.set_enhancement_rank_with(
+            warchanter,
+            3,
+            "High Spirits",
+            4,
+            &catalog,
+        ),
+        Err(EditError::InvalidRank {
+            name: "High Spirits".to_owned(),
+            rank: 4,
+            max:  3,
+        }),
+    );
+    // Without a catalog, only the most ranks of any enhancement are checked
+    build
+        .set_enhancement_rank(warchanter, 3, "High Spirits", 4)
+        .unwrap();
+    assert!(build
+        .set_enhancement_rank(warchanter, 2, "High Spirits", 1)
+        .is_err());
+
+    let bard = "[Overview]\nRace: Human\nAlignment: True Neutral\nMaxLevels: \
+                4\nClass: Bard\nClass: Fighter\nLevel: 1\tBard\nLevel: \
+                2\tBard\nLevel: 3\tBard\nLevel: 4\tBard\n[Spells]\nSpell: \
+                Bard 2\tBlur\n[Enhancements]\nTree: Warchanter\nType: \
+                Class\nSource: Bard\nClassLevels: 4\nAbility: Tier 1: \
+                Enchant Weapon\nAbility: Tier 2: Iced Edges\n";
+    let mut build = parse(&mut bard.as_bytes()).unwrap();
+    assert_eq!(validate::validate(&build), vec![]);
+    let original = build.clone();
+    assert_eq!(
+        build.set_class_at(4, Class::Fighter),
+        Err(EditError::Violation(Violation::SpellLocked {
+            class:       Class::Bard,
+            spell_level: 2,
+            needed:      4,
+            found:       3,
+        })),
+    );
+    build.spells = Default::default();
+    build.set_class_at(4, Class::Fighter).unwrap();
+    assert_eq!(
+        build.enhancements.get(warchanter).unwrap().class_levels,
+        Some(3),
+    );
+    build.set_class_at(3, Class::Fighter).unwrap();
+    assert_eq!(
+        build.set_class_at(2, Class::Fighter),
+        Err(EditError::Violation(Violation::TierLocked {
+            tree:        warchanter,
+            tier:        2,
+            enhancement: "Iced Edges".to_owned(),
+            needed:      2,
+            found:       1,
+        })),
+    );
+    assert_eq!(&build.levels[..2], &original.levels[..2]);
+}
+
+#[test]
+fn level_order_test() {
+    use order::{Milestone, OrderError};
+
+    let split = order::parse_split("15 Bard/4 Fighter/1 Rogue").unwrap();
+    assert_eq!(
+        split,
+        vec![(Class::Bard, 15), (Class::Fighter, 4), (Class::Rogue, 1)],
+    );
+    let milestones: Vec<Milestone> =
+        ["Rogue first", "Fighter 4 by 12", "Bard spells 5 by 18"]
+            .iter()
+            .map(|m| m.parse().unwrap())
+            .collect();
+    assert_eq!(
+        milestones[1],
+        Milestone::ClassLevel {
+            class:       Class::Fighter,
+            class_level: 4,
+            by_level:    12,
+        }
+    );
+    assert_eq!(milestones[2].to_string(), "Bard spells 5 by 18");
+
+    let ranked: Vec<_> = order::orderings(&split, &milestones)
+        .unwrap()
+        .take(50)
+        .collect();
+    let mut expected = vec![Class::Rogue];
+    expected.extend_from_slice(&[Class::Fighter; 4]);
+    expected.extend_from_slice(&[Class::Bard; 15]);
+    assert_eq!(ranked[0].levels, expected);
+    assert_eq!(ranked[0].milestone_levels, vec![1, 5, 18]);
+    assert_eq!(ranked[1].milestone_levels, vec![1, 6, 18]);
+    assert!(ranked.windows(2).all(|w| w[0].score <= w[1].score));
+    assert!(ranked.iter().all(|o| o.levels[0] == Class::Rogue));
+
+    // Bard 13 by 17 leaves room for only three Fighter levels by then.
+    let late = "Bard spells 5 by 17".parse().unwrap();
+    assert_eq!(
+        order::best_order(&split, &[milestones[0], milestones[1], late]),
+        Err(OrderError::Unsatisfiable),
+    );
+    let fifth = "Fighter 5 by 12".parse().unwrap();
+    assert_eq!(
+        order::best_order(&split, &[fifth]),
+        Err(OrderError::Unreachable(fifth)),
+    );
+    assert_eq!(
+        order::best_order(&split, &["Bard spells 7 by 20".parse().unwrap()]),
+        Err(OrderError::NoSuchSpellLevel(Class::Bard, 7)),
+    );
+    assert_eq!(
+        "Rogue eventually".parse::<Milestone>(),
+        Err(OrderError::InvalidMilestone("Rogue eventually".to_owned())),
+    );
+    assert!(order::parse_split("15 Bard/4 Fighter/1 Wizard/1 Rogue")
+        .and_then(|s| order::best_order(&s, &[]))
+        .is_err());
+
+    let file = std::fs::File::open(
+        "./test/brd15_ftr4_rog1_-_drw_-_trapper_-_WC_StD_-_THFs_none_BPlat_-_Wartrapper.build"
+    ).unwrap();
+    let original = parse(&mut std::io::BufReader::new(file)).unwrap();
+    let mut build = original.clone();
+    build.reorder_levels(&ranked[0].levels).unwrap();
+    assert_eq!(build.levels, ranked[0].levels);
+    // The first Fighter level is now level 2, with its skill points.
+    let fighter = original
+        .levels
+        .iter()
+        .position(|&c| c == Class::Fighter)
+        .unwrap();
+    for &skill in Skill::ALL.iter() {
+        assert_eq!(
+            build.skills.points_in_skill_at_level(skill, 1),
+            original.skills.points_in_skill_at_level(skill, fighter),
+        );
+    }
+    assert_eq!(
+        build.reorder_levels(&[Class::Bard; 20]),
+        Err(edit::EditError::NotAReordering),
+    );
+}
+
+#[test]
+fn skill_allocation_test() {
+    use allocate::{SkillPolicy, SkillPriority};
+
+    let file = std::fs::File::open(
+        "./test/brd15_ftr4_rog1_-_drw_-_trapper_-_WC_StD_-_THFs_none_BPlat_-_Wartrapper.build"
+    ).unwrap();
+    let build = parse(&mut std::io::BufReader::new(file)).unwrap();
+
+    // Rogue 8 or Bard 6 or Fighter 2, plus 3 for the Drow's 16 Intelligence.
+    assert_eq!(build.skill_points_at(1), 44);
+    assert_eq!(build.skill_points_at(2), 9);
+    assert_eq!(build.skill_points_at(3), 5);
+    assert_eq!(build.skill_points_at(21), 0);
+    for level in 1..=20 {
+        let spent: u8 = Skill::ALL
+            .iter()
+            .map(|&s| build.skills.points_in_skill_at_level(s, level - 1))
+            .sum();
+        assert_eq!(spent, build.skill_points_at(level as u8));
+    }
+
+    let first_level = [
+        Skill::Balance,
+        Skill::Concentration,
+        Skill::Jump,
+        Skill::OpenLock,
+        Skill::Tumble,
+    ];
+    let mut priorities: Vec<SkillPriority> = first_level
+        .iter()
+        .map(|&skill| SkillPriority {
+            skill,
+            policy: SkillPolicy::FirstLevel,
+        })
+        .collect();
+    for &skill in [
+        Skill::DisableDevice,
+        Skill::Search,
+        Skill::Spot,
+        Skill::Perform,
+    ]
+    .iter()
+    {
+        priorities.push(SkillPriority {
+            skill,
+            policy: SkillPolicy::Max,
+        });
+    }
+    priorities.push(SkillPriority {
+        skill:  Skill::UseMagicDevice,
+        policy: SkillPolicy::UpTo(10),
+    });
+    priorities.push(SkillPriority {
+        skill:  Skill::Heal,
+        policy: SkillPolicy::Max,
+    });
+
+    let mut allocated = build.clone();
+    allocated.skills = allocate::allocate_skills(&build, &priorities);
+    for &skill in first_level.iter() {
+        assert_eq!(allocated.skills.points_in_skill_at_level(skill, 0), 4);
+        assert_eq!(allocated.skills.points_in_skill_at_level(skill, 1), 0);
+    }
+    // The first two levels are spent as in the fixture, where Concentration
+    // and Perform are capped at 2 ranks as Rogue 1.
+    for level in 0..2 {
+        for &skill in Skill::ALL.iter() {
+            assert_eq!(
+                allocated.skills.points_in_skill_at_level(skill, level),
+                build.skills.points_in_skill_at_level(skill, level),
+                "{}",
+                skill,
+            );
+        }
+    }
+
+    let snapshot = allocated.at_level(20);
+    let ranks = |skill| {
+        snapshot
+            .skill_ranks
+            .iter()
+            .find(|(s, _)| *s == skill)
+            .map_or(0.0, |(_, r)| *r)
+    };
+    assert_eq!(ranks(Skill::DisableDevice), 23.0);
+    assert_eq!(ranks(Skill::Spot), 23.0);
+    assert_eq!(ranks(Skill::Perform), 23.0);
+    assert_eq!(ranks(Skill::UseMagicDevice), 10.0);
+    assert_eq!(ranks(Skill::Concentration), 2.0);
+    for level in 1..=20 {
+        let spent: u8 = Skill::ALL
+            .iter()
+            .map(|&s| allocated.skills.points_in_skill_at_level(s, level - 1))
+            .sum();
+        assert!(spent <= allocated.skill_points_at(level as u8));
+        for (skill, ranks) in allocated.at_level(level as u8).skill_ranks {
+            assert!(ranks <= f32::from(level as u8 + 3), "{}", skill);
+        }
+    }
+}
+
+#[test]
+fn point_buy_test() {
+    use point_buy::{AbilityGoal, Tradeoff};
+
+    let goals = [
+        AbilityGoal::Maximize(Ability::Cha),
+        AbilityGoal::AtLeast(Ability::Con, 14),
+        AbilityGoal::Dump(Ability::Wis),
+    ];
+    let points = |pb: &point_buy::PointBuy| {
+        Ability::ALL
+            .iter()
+            .map(|&a| pb.stats[a])
+            .collect::<Vec<u8>>()
+    };
+
+    // The Drow's -2 Constitution makes 14 cost 10 points, and the 2 left over
+    // go to the cheapest abilities without a goal.
+    let drow = point_buy::point_buy(Race::Drow, BuildType::Adventurer, &goals);
+    assert_eq!(points(&drow), vec![1, 1, 10, 0, 0, 16]);
+    assert!(drow.tradeoffs.is_empty());
+    let human = point_buy::point_buy(Race::Human, BuildType::Hero, &goals);
+    assert_eq!(points(&human), vec![4, 4, 6, 4, 0, 16]);
+
+    let two_maxed = point_buy::point_buy(
+        Race::Human,
+        BuildType::Adventurer,
+        &[
+            AbilityGoal::Maximize(Ability::Str),
+            AbilityGoal::Maximize(Ability::Dex),
+        ],
+    );
+    assert_eq!(points(&two_maxed), vec![16, 10, 1, 1, 0, 0]);
+    assert_eq!(
+        two_maxed.tradeoffs,
+        vec![Tradeoff::NextScore {
+            ability: Ability::Dex,
+            score:   17,
+            cost:    1,
+        }]
+    );
+    assert_eq!(
+        two_maxed.tradeoffs[0].to_string(),
+        "Dexterity 17 would take 1 more build points",
+    );
+
+    let too_high = point_buy::point_buy(
+        Race::Human,
+        BuildType::Legend,
+        &[AbilityGoal::AtLeast(Ability::Int, 20)],
+    );
+    assert_eq!(too_high.stats[Ability::Int], 16);
+    assert_eq!(
+        too_high.tradeoffs,
+        vec![Tradeoff::TargetMissed {
+            ability: Ability::Int,
+            target:  20,
+            reached: 18,
+        }]
+    );
+
+    let mut dumps: Vec<AbilityGoal> = Ability::ALL[1..]
+        .iter()
+        .map(|&a| AbilityGoal::Dump(a))
+        .collect();
+    dumps.insert(0, AbilityGoal::Maximize(Ability::Str));
+    let dumped =
+        point_buy::point_buy(Race::Human, BuildType::Adventurer, &dumps);
+    assert_eq!(dumped.tradeoffs, vec![Tradeoff::Unspent(12)]);
+
+    let file = std::fs::File::open(
+        "./test/brd15_ftr4_rog1_-_drw_-_trapper_-_WC_StD_-_THFs_none_BPlat_-_Wartrapper.build"
+    ).unwrap();
+    let mut build = parse(&mut std::io::BufReader::new(file)).unwrap();
+    build.race = Race::Human;
+    let point_buys = build.regenerate_stats(&goals);
+    let enabled: Vec<BuildType> = BuildType::ALL
+        .iter()
+        .copied()
+        .filter(|&bt| build.stats(bt).is_some())
+        .collect();
+    assert_eq!(
+        point_buys
+            .iter()
+            .map(|pb| pb.build_type)
+            .collect::<Vec<_>>(),
+        enabled,
+    );
+    assert_eq!(
+        build.stats(BuildType::Adventurer),
+        Some(&point_buys[0].stats)
+    );
+    assert_eq!(build.levelup_at(4), Some(Ability::Cha));
+    assert_eq!(build.at_level(4).ability_scores.unwrap()[Ability::Cha], 19);
+}
+
+#[test]
+fn forum_import_test() {
+    use forum::Unrecovered;
+
+    let build_file = std::fs::File::open(
+        "./test/brd15_ftr4_rog1_-_drw_-_trapper_-_WC_StD_-_THFs_none_BPlat_-_Wartrapper.build"
+    ).unwrap();
+    let build = parse(&mut std::io::BufReader::new(build_file)).unwrap();
+    let forum_file =
+        std::fs::File::open("./test/wartrapper_forum.txt").unwrap();
+    let (imported, unrecovered) =
+        forum::import(&mut std::io::BufReader::new(forum_file)).unwrap();
+
+    assert_eq!(unrecovered, vec![Unrecovered::Skills]);
+    assert_eq!(imported.name, build.name);
+    assert_eq!(imported.race, build.race);
+    assert_eq!(imported.alignment, build.alignment);
+    assert_eq!(imported.max_levels, build.max_levels);
+    assert_eq!(imported.levels, build.levels);
+    for &build_type in BuildType::ALL.iter() {
+        assert_eq!(imported.stats(build_type), build.stats(build_type));
+    }
+    assert_eq!(
+        imported.at_level(20).ability_scores,
+        build.at_level(20).ability_scores,
+    );
+    assert_eq!(imported.at_level(20).feats, build.at_level(20).feats);
+    assert_eq!(imported.spells, build.spells);
+    assert_eq!(imported.tier_five, build.tier_five);
+    for tree in imported.enhancements.trees.iter() {
+        assert_eq!(
+            Some(&tree.tiers),
+            build.enhancements.get(tree.name).map(|et| &et.tiers),
+        );
+    }
+
+    let export = "Trapper\r\nLevel 20 True Neutral Drow\r\n(15 Bard / 4 \
+                  Fighter / 1 Rogue)\r\n\r\nFeats\r\n 1: Power Attack\r\n2: \
+                  Cleave (Wizard bonus \
+                  feat)\r\n\r\nEnhancements\r\nWarchanter\r\n  Cores: Weapon \
+                  Training\r\nNowhere\r\n  Cores: Nothing\r\n";
+    let (trapper, unrecovered) =
+        forum::import(&mut export.as_bytes()).unwrap();
+    assert_eq!(trapper.levels.len(), 20);
+    assert_eq!(
+        trapper.levels.iter().filter(|&&c| c == Class::Bard).count(),
+        15
+    );
+    assert_eq!(trapper.feats.standard_feats.len(), 1);
+    assert_eq!(
+        unrecovered,
+        vec![
+            Unrecovered::LevelOrder,
+            Unrecovered::Abilities,
+            Unrecovered::Skills,
+            Unrecovered::FeatSlot(7, "Cleave".to_owned()),
+            Unrecovered::UnknownTree(12, "Nowhere".to_owned()),
+        ],
+    );
+    assert_eq!(
+        unrecovered[3].to_string(),
+        "line 7: No feat slot for Cleave",
+    );
+
+    assert!(matches!(
+        forum::import(&mut "Trapper\r\nLevel 20 Drow\r\n".as_bytes()),
+        Err(forum::ImportError::UnknownAlignment(_)),
+    ));
+}
+
+#[test]
+fn ddo_builder_test() {
+    use ddo_builder::{Unmapped, XmlError};
+
+    let build_file = std::fs::File::open(
+        "./test/brd15_ftr4_rog1_-_drw_-_trapper_-_WC_StD_-_THFs_none_BPlat_-_Wartrapper.build"
+    ).unwrap();
+    let build = parse(&mut std::io::BufReader::new(build_file)).unwrap();
+    let xml_file = std::fs::File::open("./test/wartrapper_ddob.xml").unwrap();
+    let (imported, unmapped) =
+        ddo_builder::import(&mut std::io::BufReader::new(xml_file)).unwrap();
+
+    assert_eq!(imported, build);
+    assert_eq!(
+        unmapped,
+        vec![
+            Unmapped::Element("Notes".to_owned()),
+            Unmapped::Element("GearSetups".to_owned()),
+        ],
+    );
+
+    // The fixture is an export with DDO Builder's notes and gear added
+    let mut exported = Vec::new();
+    assert_eq!(ddo_builder::export(&build, &mut exported).unwrap(), vec![]);
+    let fixture = std::fs::read_to_string("./test/wartrapper_ddob.xml")
+        .unwrap()
+        .replace("\r\n", "\n");
+    let notes = fixture.find("    <Notes>").unwrap();
+    let gear_end = fixture.find("    <AbilitySpend>").unwrap();
+    let exported = String::from_utf8(exported).unwrap();
+    assert_eq!(
+        exported,
+        format!("{}{}", &fixture[..notes], &fixture[gear_end..])
+    );
+    let (reimported, _) =
+        ddo_builder::import(&mut exported.as_bytes()).unwrap();
+    assert_eq!(reimported, build);
+
+    // Names with spaces are read too, and without <MaxLevels> or
+    // <BuildType>, they come from the feats and the first <AbilitySpend>
+    let older = exported
+        .replace("TrueNeutral", "True Neutral")
+        .replace("DisableDevice", "Disable Device")
+        .replace("StalwartDefender", "Stalwart Defender")
+        .replace("    <MaxLevels>20</MaxLevels>\n", "")
+        .replace("    <BuildType>Adventurer</BuildType>\n", "");
+    let (reimported, unmapped) =
+        ddo_builder::import(&mut older.as_bytes()).unwrap();
+    assert_eq!(reimported, build);
+    assert_eq!(unmapped, vec![]);
+
+    // Every stat set and the max level make it across
+    let file = std::fs::File::open("./test/test1.build").unwrap();
+    let mut epic = parse(&mut std::io::BufReader::new(file)).unwrap();
+    epic.max_levels = 34;
+    epic.hero_stats = epic.champion_stats.clone();
+    epic.hero_stats.as_mut().unwrap()[Ability::Str] += 2;
+    let mut exported = Vec::new();
+    assert_eq!(ddo_builder::export(&epic, &mut exported).unwrap(), vec![]);
+    let (reimported, _) = ddo_builder::import(&mut &exported[..]).unwrap();
+    assert_eq!(reimported.max_levels, 34);
+    assert_eq!(reimported.preferred_build_type, BuildType::Champion);
+    assert_eq!(reimported.adventurer_stats, None);
+    assert_eq!(reimported.champion_stats, epic.champion_stats);
+    assert_eq!(reimported.hero_stats, epic.hero_stats);
+    assert_eq!(reimported.legend_stats, None);
+    assert_eq!(reimported.levels, epic.levels);
+    assert_eq!(reimported.skills, epic.skills);
+    assert_eq!(reimported.at_level(30).feats, epic.at_level(30).feats);
+
+    // Empty spell slots are kept
+    let file = std::fs::File::open("./test/test3.build").unwrap();
+    let wizard = parse(&mut std::io::BufReader::new(file)).unwrap();
+    let mut exported = Vec::new();
+    ddo_builder::export(&wizard, &mut exported).unwrap();
+    let (reimported, _) = ddo_builder::import(&mut &exported[..]).unwrap();
+    assert!(wizard.spells.spellbooks.iter().flatten().any(|sb| sb
+        .spells_by_level
+        .iter()
+        .flatten()
+        .any(Option::is_none)));
+    assert_eq!(reimported.spells, wizard.spells);
+
+    assert!(matches!(
+        ddo_builder::import(&mut "<Character></Character>".as_bytes()),
+        Err(XmlError::NotACharacter),
+    ));
+}
+
+#[test]
+fn filename_test() {
+    use filename::{BuildFilename, FilenameError};
+
+    let wartrapper_filename =
+        "brd15_ftr4_rog1_-_drw_-_trapper_-_WC_StD_-_THFs_none_BPlat_-_Wartrapper.build";
+    let file = std::fs::File::open(format!("./test/{}", wartrapper_filename))
+        .unwrap();
+    let build = parse(&mut std::io::BufReader::new(file)).unwrap();
+
+    let mut name = BuildFilename::new(&build);
+    assert_eq!(
+        name.split,
+        vec![(Class::Bard, 15), (Class::Fighter, 4), (Class::Rogue, 1)],
+    );
+    assert_eq!(
+        name.trees,
+        vec![
+            EnhancementTreeName::Class(ClassEnhancementTree::Warchanter),
+            EnhancementTreeName::Class(ClassEnhancementTree::StalwartDefender),
+        ],
+    );
+    assert_eq!(
+        name.filename(),
+        "brd15_ftr4_rog1_-_drw_-_none_-_WC_StD_-_none_-_Wartrapper.build",
+    );
+
+    name.role = Some("trapper".to_owned());
+    name.tags = vec!["THFs".to_owned(), "none".to_owned(), "BPlat".to_owned()];
+    assert_eq!(name.filename(), wartrapper_filename);
+    assert_eq!(wartrapper_filename.parse(), Ok(name.clone()));
+
+    name.name = "Old Wartrapper".to_owned();
+    name.role = None;
+    name.tags = vec![];
+    let renamed = name.filename();
+    assert_eq!(
+        renamed,
+        "brd15_ftr4_rog1_-_drw_-_none_-_WC_StD_-_none_-_Old_Wartrapper.build",
+    );
+    assert_eq!(renamed.parse(), Ok(name));
+
+    assert_eq!(
+        "test1.build".parse::<BuildFilename>(),
+        Err(FilenameError::PartCount(1)),
+    );
+    assert_eq!(
+        "brd_-_drw_-_none_-_none_-_none_-_x".parse::<BuildFilename>(),
+        Err(FilenameError::MalformedClass("brd".to_owned())),
+    );
+    assert_eq!(
+        "xyz20_-_drw_-_none_-_none_-_none_-_x".parse::<BuildFilename>(),
+        Err(FilenameError::UnknownClass("xyz".to_owned())),
+    );
+    assert_eq!(
+        "brd20_-_drw_-_none_-_WC_Xyz_-_none_-_x".parse::<BuildFilename>(),
+        Err(FilenameError::UnknownTree("Xyz".to_owned())),
+    );
+}
+
+#[test]
+fn archive_test() {
+    use archive::{Archive, ArchiveEntry, Condition, Query, QueryError};
+
+    let archive = Archive::index("./test").unwrap();
+    assert!(archive.errors.is_empty());
+    assert_eq!(archive.entries.len(), 12);
+
+    let names = |query: &str| {
+        let query = query.parse::<Query>().unwrap();
+        archive
+            .query(&query)
+            .map(|e| e.name.clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        names("Drow and Warchanter tier 5 and feat power attack"),
+        vec!["Wartrapper"],
+    );
+    assert_eq!(names("4 Fighter and 4 Bard"), vec!["Wartrapper"]);
+    assert_eq!(names("16 Favored Soul"), vec!["Old Habits"]);
+    assert_eq!(
+        names("Bard and Stalwart Defender 20 AP"),
+        vec!["Wartrapper"]
+    );
+    assert_eq!(names("Stalwart Defender"), vec!["Wartrapper", "Old Habits"]);
+    assert_eq!(
+        names("Elf-Arcane Archer and spell haste"),
+        Vec::<String>::new()
+    );
+    assert_eq!(names("4 Rogue"), Vec::<String>::new());
+    assert_eq!(names("").len(), 12);
+
+    let wartrapper = &archive.entries[0];
+    assert_eq!(
+        wartrapper.summary(),
+        "Wartrapper: Drow, 15 Bard/4 Fighter/1 Rogue, Warchanter tier 5",
+    );
+    // Without a catalog, every enhancement costs 1 point per rank
+    assert_eq!(
+        wartrapper.trees[..2],
+        [
+            (
+                EnhancementTreeName::Class(ClassEnhancementTree::Warchanter),
+                41
+            ),
+            (
+                EnhancementTreeName::Class(
+                    ClassEnhancementTree::StalwartDefender
+                ),
+                26,
+            ),
+        ],
+    );
+    // Enchant Weapon, Armorer and Howl of the North cost 2 points per rank
+    let catalogued = ArchiveEntry::new_with(
+        wartrapper.path.clone(),
+        &parse(&mut std::io::BufReader::new(
+            std::fs::File::open(&wartrapper.path).unwrap(),
+        ))
+        .unwrap(),
+        &catalog(),
+    );
+    assert_eq!(catalogued.trees[0].1, 44);
+
+    let query = "Drow and 1 Rogue and Warchanter tier 5 and Swashbuckler 4 \
+                 AP and feat Power Attack and spell Haste";
+    assert_eq!(query.parse::<Query>().unwrap().to_string(), query);
+    assert_eq!(
+        "Warchanter".parse(),
+        Ok(Condition::ActionPoints {
+            tree:     EnhancementTreeName::Class(
+                ClassEnhancementTree::Warchanter
+            ),
+            at_least: 1,
+        }),
+    );
+    assert_eq!(
+        "Gunslinger tier 5".parse::<Condition>(),
+        Err(QueryError::UnknownName("Gunslinger".to_owned())),
+    );
+    assert_eq!(
+        "Drow and".parse::<Query>(),
+        Err(QueryError::UnknownName("Drow and".to_owned())),
+    );
+}
+
+#[test]
+fn statistics_test() {
+    use statistics::{statistics, TableFormat};
+
+    let builds = archive::build_paths("./test")
+        .unwrap()
+        .iter()
+        .map(|path| {
+            let file = std::fs::File::open(path).unwrap();
+            parse(&mut std::io::BufReader::new(file)).unwrap()
+        })
+        .collect::<Vec<_>>();
+    let stats = statistics(&builds);
+    assert_eq!(stats.build_count, 12);
+
+    let fighters = &stats.split_feats[0];
+    assert_eq!(fighters.split, vec![(Class::Fighter, 20)]);
+    assert_eq!(fighters.builds, 3);
+    assert!(fighters.feats.contains(&("Power Attack".to_owned(), 3)));
+
+    let power_attack = stats
+        .feat_timings
+        .iter()
+        .find(|ft| ft.feat == "Power Attack")
+        .unwrap();
+    assert_eq!(power_attack.levels, vec![(1, 1), (6, 4)]);
+    assert_eq!(power_attack.count(), 5);
+    assert_eq!(power_attack.median(), Some(6));
+    let quicken = stats
+        .feat_timings
+        .iter()
+        .find(|ft| ft.feat == "Quicken Spell")
+        .unwrap();
+    assert_eq!(quicken.median(), Some(12));
+
+    let aasimar = &stats.stat_spreads[0];
+    assert_eq!(
+        (aasimar.race, aasimar.build_type, aasimar.builds),
+        (Race::Aasimar, BuildType::Champion, 2),
+    );
+    assert_eq!(aasimar.average, [18.0, 8.0, 16.0, 14.0, 10.0, 8.0]);
+
+    let warchanter = stats
+        .tree_popularity
+        .iter()
+        .find(|tp| tp.tree == ClassEnhancementTree::Warchanter)
+        .unwrap();
+    assert_eq!((warchanter.builds, warchanter.tier_five), (1, 1));
+    assert_eq!(warchanter.average_ap, 41.0);
+    assert_eq!(stats.tier_five_choices.len(), 9);
+    assert!(stats
+        .skill_allocation
+        .iter()
+        .all(|sa| sa.skill != Skill::Bluff));
+
+    let markdown = stats.report(TableFormat::Markdown);
+    assert!(markdown.starts_with("# Statistics of 12 builds\n"));
+    assert!(markdown.contains("| Warchanter | 1 | 41.0 | 1 |\n"));
+    let csv = stats.report(TableFormat::Csv);
+    assert!(csv.contains(
+        "\nFeat timings\nFeat,Taken,Earliest,Median,Latest,Levels\n"
+    ));
+    assert!(csv.contains("\nPower Attack,5,1,6,6,\"1: 1, 6: 4\"\n"));
+}
+
+#[test]
+fn spreadsheet_test() {
+    use statistics::{write_tables, TableFormat};
+
+    let file = std::fs::File::open(
+        "./test/brd15_ftr4_rog1_-_drw_-_trapper_-_WC_StD_-_THFs_none_BPlat_-_Wartrapper.build",
+    )
+    .unwrap();
+    let build = parse(&mut std::io::BufReader::new(file)).unwrap();
+
+    let tables = spreadsheet::tables(&build);
+    let names = tables.iter().map(|t| t.name).collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        vec!["levels", "skills", "stats", "feats", "enhancements"]
+    );
+
+    let levels = &tables[0];
+    assert_eq!(levels.rows.len(), 20);
+    assert_eq!(levels.rows[14], vec!["15", "Fighter", "4"]);
+
+    // A column for every heroic level, whether or not points were spent
+    let skills = &tables[1];
+    assert_eq!(skills.header.len(), 22);
+    assert_eq!(skills.header[21], "Tome");
+    assert_eq!(skills.rows.len(), Skill::ALL.len());
+
+    let stats = &tables[2];
+    assert_eq!(
+        stats.header,
+        vec![
+            "Ability",
+            "Adventurer",
+            "Champion",
+            "Hero",
+            "Legend",
+            "Tome"
+        ],
+    );
+    assert_eq!(stats.rows[0], vec!["Strength", "10", "", "", "", "0"]);
+
+    let feats = &tables[3];
+    assert!(feats.rows.contains(&vec![
+        "Class".to_owned(),
+        "Fighter".to_owned(),
+        "2".to_owned(),
+        "11".to_owned(),
+        "Improved Critical: Slashing".to_owned(),
+    ]));
+
+    let enhancements = &tables[4];
+    let warchanter_ap: u16 = enhancements
+        .rows
+        .iter()
+        .filter(|r| r[0] == "Warchanter")
+        .map(|r| r[6].parse::<u16>().unwrap())
+        .sum();
+    assert_eq!(warchanter_ap, 41);
+
+    let csv = write_tables(&tables, TableFormat::Csv);
+    assert!(
+        csv.starts_with("Level plan\nLevel,Class,Class Level\n1,Rogue,1\n")
+    );
+    assert!(csv.contains(
+        "\nEnhancements\nTree,Tier \
+         5,Tier,Enhancement,Subenhancement,Rank,AP\nWarchanter,Yes,0,Skaldic,\
+         Constitution,,1\n"
+    ));
+}
+
+#[test]
+fn share_code_test() {
+    use share::{decode, encode, ShareCodeError};
+
+    for path in archive::build_paths("./test").unwrap() {
+        let file = std::fs::File::open(&path).unwrap();
+        let build = parse(&mut std::io::BufReader::new(file)).unwrap();
+
+        let code = encode(&build);
+        assert!(
+            code.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+            "{}",
+            code,
+        );
+        assert_eq!(decode(&code).unwrap(), build, "{}", path.display());
+
+        let mut written = Vec::new();
+        write(&build, &mut written).unwrap();
+        assert!(code.len() < written.len(), "{}", path.display());
+    }
+
+    // Compressed, the code of this 6 KB build is some 1,200 characters long
+    // instead of 2,139
+    let file = std::fs::File::open(
+        "./test/brd15_ftr4_rog1_-_drw_-_trapper_-_WC_StD_-_THFs_none_BPlat_-_Wartrapper.build",
+    )
+    .unwrap();
+    let code = encode(&parse(&mut std::io::BufReader::new(file)).unwrap());
+    assert!(code.len() < 1_250, "{}", code.len());
+
+    assert_eq!(decode("AQ=="), Err(ShareCodeError::InvalidCharacter('=')));
+    assert_eq!(decode(""), Err(ShareCodeError::Truncated));
+    // Version 2 doesn't exist yet
+    assert_eq!(decode("Ag"), Err(ShareCodeError::UnknownVersion(2)));
+    // A reserved block type
+    assert_eq!(decode("Af8"), Err(ShareCodeError::InvalidCompression));
+
+    let file = std::fs::File::open("./test/test3.build").unwrap();
+    let build = parse(&mut std::io::BufReader::new(file)).unwrap();
+    let code = encode(&build);
+    assert!(code.starts_with('A'));
+    assert_eq!(
+        decode(&code[..code.len() - 8]),
+        Err(ShareCodeError::Truncated),
+    );
+}
@@ -0,0 +1,553 @@
+//! Checking that a build follows the game's rules, as far as the game
+//! content in the registry describes them.
+//!
+//! A build that parses is not necessarily one that the game would let you
+//! make; CBL itself enforces few of the rules. `validate()` lists every rule
+//! that a build breaks, rather than stopping at the first one.
+//!
+//! The enhancements themselves can only be checked in trees that the
+//! registry has a catalog of. `unchecked_trees()` lists the others, so that
+//! a build without violations isn't mistaken for one that was fully checked.
+//! The bundled content has no catalogs, so with it, every tree that a build
+//! spends points in is listed; catalogs are loaded from content patches.
+
+use crate::{
+    allocate::max_half_ranks,
+    content::{self, Registry},
+    Alignment,
+    CharacterBuild,
+    Class,
+    EnhancementTree,
+    EnhancementTreeName,
+    Race,
+    Skill,
+    HEROIC_LEVEL_CAP,
+};
+use std::fmt;
+
+/// A rule of the game that a build breaks.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Violation {
+    /// The class can't be taken by a character of the alignment.
+    AlignmentForbidsClass(Class, Alignment),
+    /// An iconic race's first level isn't in its starting class.
+    WrongStartingClass {
+        race:     Race,
+        expected: Class,
+        found:    Class,
+    },
+    /// The skill has more ranks at a heroic level than the character level
+    /// plus 3, or half that if it isn't a class skill of any class taken so
+    /// far. Only the first such level of each skill is reported.
+    TooManySkillRanks {
+        skill: Skill,
+        level: u8,
+        ranks: f32,
+        max:   f32,
+    },
+    /// More skill points are spent at a heroic level than it grants (see
+    /// `CharacterBuild::skill_points_at_with()`).
+    TooManySkillPoints {
+        level:     u8,
+        spent:     u16,
+        available: u8,
+    },
+    /// Spells of the level are chosen in the class's spellbook, but the build
+    /// has too few levels of the class to learn them.
+    SpellLocked {
+        class:       Class,
+        spell_level: usize,
+        needed:      u8,
+        found:       u8,
+    },
+    /// The tree belongs to another race than the build's.
+    TreeOfOtherRace(EnhancementTreeName, Race),
+    /// The build has no levels in any class that can be spent in the tree.
+    NoLevelsForTree(EnhancementTreeName),
+    /// The enhancement's tier needs more levels than the build has in the
+    /// tree's class (or more character levels, for trees without a class).
+    TierLocked {
+        tree:        EnhancementTreeName,
+        tier:        usize,
+        enhancement: String,
+        needed:      u8,
+        found:       u8,
+    },
+    /// A tier 5 enhancement was taken in a tree other than `tier_five`.
+    TierFiveOutsideTree(EnhancementTreeName, String),
+    /// `tier_five` names a tree that has no tier 5 enhancements.
+    EmptyTierFive(EnhancementTreeName),
+    /// The tree's catalog has no such enhancement in that tier.
+    UnknownEnhancement {
+        tree:        EnhancementTreeName,
+        tier:        usize,
+        enhancement: String,
+    },
+    /// The sub-enhancement isn't one of the enhancement's choices, or is
+    /// missing (`None`) although there is a choice to make.
+    InvalidChoice {
+        tree:        EnhancementTreeName,
+        enhancement: String,
+        choice:      Option<String>,
+    },
+    TooManyRanks {
+        tree:        EnhancementTreeName,
+        enhancement: String,
+        ranks:       u8,
+        max:         u8,
+    },
+    /// The enhancement needs another one of its tree that wasn't taken.
+    MissingPrerequisite {
+        tree:        EnhancementTreeName,
+        enhancement: String,
+        requires:    String,
+    },
+}
+
+/// The levels in a tree's class that are needed to unlock each of its tiers,
+/// indexed by tier.
+pub const TIER_LEVELS: [u8; 6] = [1, 1, 2, 3, 4, 12];
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Self::AlignmentForbidsClass(c, a) =>
+                write!(f, "{} characters can't take levels in {}", a, c),
+            Self::WrongStartingClass {
+                race,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} must start as a {}, not as a {}",
+                race, expected, found,
+            ),
+            Self::TooManySkillRanks {
+                skill,
+                level,
+                ranks,
+                max,
+            } => write!(
+                f,
+                "{} has {} ranks at level {}, but at most {}",
+                skill, ranks, level, max,
+            ),
+            Self::TooManySkillPoints {
+                level,
+                spent,
+                available,
+            } => write!(
+                f,
+                "{} skill points are spent at level {}, which grants {}",
+                spent, level, available,
+            ),
+            Self::SpellLocked {
+                class,
+                spell_level,
+                needed,
+                found,
+            } => write!(
+                f,
+                "Level {} spells of {} need {} levels, but have {}",
+                spell_level, class, needed, found,
+            ),
+            Self::TreeOfOtherRace(t, r) =>
+                write!(f, "{} is only available to {}", t, r),
+            Self::NoLevelsForTree(t) =>
+                write!(f, "No levels can be spent in {}", t),
+            Self::TierLocked {
+                tree,
+                tier,
+                enhancement,
+                needed,
+                found,
+            } => write!(
+                f,
+                "{} (tier {} of {}) needs {} levels, but has {}",
+                enhancement, tier, tree, needed, found,
+            ),
+            Self::TierFiveOutsideTree(t, e) => write!(
+                f,
+                "{} (tier 5 of {}) is outside of the tier 5 tree",
+                e, t,
+            ),
+            Self::EmptyTierFive(t) => write!(
+                f,
+                "{} is the tier 5 tree, but has no tier 5 enhancements",
+                t,
+            ),
+            Self::UnknownEnhancement {
+                tree,
+                tier,
+                enhancement,
+            } => write!(
+                f,
+                "{} has no enhancement {} in tier {}",
+                tree, enhancement, tier,
+            ),
+            Self::InvalidChoice {
+                tree,
+                enhancement,
+                choice: Some(c),
+            } => write!(
+                f,
+                "{} is not a choice of {} ({})",
+                c, enhancement, tree,
+            ),
+            Self::InvalidChoice {
+                tree,
+                enhancement,
+                choice: None,
+            } => write!(f, "{} ({}) needs a choice", enhancement, tree),
+            Self::TooManyRanks {
+                tree,
+                enhancement,
+                ranks,
+                max,
+            } => write!(
+                f,
+                "{} ({}) has {} ranks, but at most {}",
+                enhancement, tree, ranks, max,
+            ),
+            Self::MissingPrerequisite {
+                tree,
+                enhancement,
+                requires,
+            } => write!(f, "{} ({}) requires {}", enhancement, tree, requires),
+        }
+    }
+}
+
+/// Validates a build against the game content of `content::registry()`.
+pub fn validate(build: &CharacterBuild) -> Vec<Violation> {
+    validate_with(build, &content::registry())
+}
+
+/// Lists every rule that `build` breaks, according to `content`, in the
+/// order that the build's sections are written.
+pub fn validate_with(
+    build: &CharacterBuild,
+    content: &Registry,
+) -> Vec<Violation> {
+    let mut violations = Vec::new();
+
+    check_starting_class(build, content, &mut violations);
+    check_alignment(build, content, &mut violations);
+    check_skills(build, content, &mut violations);
+    check_spells(build, content, &mut violations);
+    check_racial_trees(build, content, &mut violations);
+    check_tiers(build, content, &mut violations);
+    check_catalog(build, content, &mut violations);
+
+    violations
+}
+
+/// Lists the trees of `build` whose enhancements `validate()` couldn't
+/// check, using the game content of `content::registry()`.
+pub fn unchecked_trees(build: &CharacterBuild) -> Vec<EnhancementTreeName> {
+    unchecked_trees_with(build, &content::registry())
+}
+
+/// Lists the trees of `build` that have enhancements taken but no catalog
+/// in `content` (see `Registry::has_catalog()`), in the order that they were
+/// declared.
+pub fn unchecked_trees_with(
+    build: &CharacterBuild,
+    content: &Registry,
+) -> Vec<EnhancementTreeName> {
+    build
+        .enhancements
+        .trees
+        .iter()
+        .filter(|et| et.tiers.iter().any(|es| !es.is_empty()))
+        .map(|et| et.name)
+        .filter(|&t| !content.has_catalog(t))
+        .collect()
+}
+
+fn check_starting_class(
+    build: &CharacterBuild,
+    content: &Registry,
+    violations: &mut Vec<Violation>,
+) {
+    let expected = content.race(build.race).and_then(|ri| ri.starting_class);
+    if let (Some(expected), Some(&found)) = (expected, build.levels.first()) {
+        if found != expected {
+            violations.push(Violation::WrongStartingClass {
+                race: build.race,
+                expected,
+                found,
+            });
+        }
+    }
+}
+
+fn check_alignment(
+    build: &CharacterBuild,
+    content: &Registry,
+    violations: &mut Vec<Violation>,
+) {
+    let mut classes: Vec<Class> = Vec::with_capacity(3);
+    for &class in build.levels.iter() {
+        if !classes.contains(&class) {
+            classes.push(class);
+        }
+    }
+
+    for class in classes {
+        let allowed =
+            content.class(class).map_or(&[][..], |ci| &ci.alignments);
+        if !allowed.is_empty() && !allowed.contains(&build.alignment) {
+            violations.push(Violation::AlignmentForbidsClass(
+                class,
+                build.alignment,
+            ));
+        }
+    }
+}
+
+fn check_skills(
+    build: &CharacterBuild,
+    content: &Registry,
+    violations: &mut Vec<Violation>,
+) {
+    let heroic_levels = build.levels.len().min(HEROIC_LEVEL_CAP.into());
+    // The ranks of each skill so far, in half ranks.
+    let mut half_ranks = [0u16; Skill::ALL.len()];
+    let mut reported = [false; Skill::ALL.len()];
+
+    for (i, &class) in build.levels[..heroic_levels].iter().enumerate() {
+        let level = i as u8 + 1;
+        let class_skills = content
+            .class(class)
+            .map_or(&[][..], |ci| &ci.class_skills[..]);
+
+        let mut spent = 0;
+        for (j, &skill) in Skill::ALL.iter().enumerate() {
+            let points = build.skills.points_in_skill_at_level(skill, i);
+            spent += u16::from(points);
+            half_ranks[j] += if class_skills.contains(&skill) {
+                2 * u16::from(points)
+            } else {
+                u16::from(points)
+            };
+
+            let max = max_half_ranks(&build.levels[..=i], skill, content);
+            if half_ranks[j] > max && !reported[j] {
+                reported[j] = true;
+                violations.push(Violation::TooManySkillRanks {
+                    skill,
+                    level,
+                    ranks: f32::from(half_ranks[j]) / 2.0,
+                    max: f32::from(max) / 2.0,
+                });
+            }
+        }
+
+        let available = build.skill_points_at_with(level, content);
+        if spent > u16::from(available) {
+            violations.push(Violation::TooManySkillPoints {
+                level,
+                spent,
+                available,
+            });
+        }
+    }
+}
+
+fn check_spells(
+    build: &CharacterBuild,
+    content: &Registry,
+    violations: &mut Vec<Violation>,
+) {
+    for spellbook in build.spells.spellbooks.iter().flatten() {
+        let class = spellbook.class;
+        let unlocks = content
+            .class(class)
+            .map_or(&[][..], |ci| &ci.spell_unlocks[..]);
+        let found = build.levels.iter().filter(|&&c| c == class).count() as u8;
+
+        for (i, spells) in spellbook.spells_by_level.iter().enumerate() {
+            let needed = match unlocks.get(i) {
+                Some(&needed) => needed,
+                None => continue,
+            };
+            if found < needed && spells.iter().any(Option::is_some) {
+                violations.push(Violation::SpellLocked {
+                    class,
+                    spell_level: i + 1,
+                    needed,
+                    found,
+                });
+            }
+        }
+    }
+}
+
+fn check_racial_trees(
+    build: &CharacterBuild,
+    content: &Registry,
+    violations: &mut Vec<Violation>,
+) {
+    for enhancement_tree in build.enhancements.trees.iter() {
+        let tree = enhancement_tree.name;
+        let race = match tree {
+            EnhancementTreeName::Race(r) => Some(r),
+            EnhancementTreeName::RaceClass(_) =>
+                content.tree(tree).and_then(|ti| ti.race),
+            _ => None,
+        };
+        if let Some(race) = race.filter(|&r| r != build.race) {
+            violations.push(Violation::TreeOfOtherRace(tree, race));
+        }
+    }
+}
+
+fn check_tiers(
+    build: &CharacterBuild,
+    content: &Registry,
+    violations: &mut Vec<Violation>,
+) {
+    for enhancement_tree in build.enhancements.trees.iter() {
+        let tree = enhancement_tree.name;
+        let found =
+            match levels_for_tree(&build.levels, content, enhancement_tree) {
+                Some(found) => found,
+                None => {
+                    violations.push(Violation::NoLevelsForTree(tree));
+                    continue;
+                },
+            };
+
+        for (tier, enhancements) in enhancement_tree.tiers.iter().enumerate() {
+            for enhancement in enhancements.iter() {
+                if tier == 5 && build.tier_five != Some(tree) {
+                    violations.push(Violation::TierFiveOutsideTree(
+                        tree,
+                        enhancement.name.clone(),
+                    ));
+                }
+                if found < TIER_LEVELS[tier] {
+                    violations.push(Violation::TierLocked {
+                        tree,
+                        tier,
+                        enhancement: enhancement.name.clone(),
+                        needed: TIER_LEVELS[tier],
+                        found,
+                    });
+                }
+            }
+        }
+    }
+
+    if let Some(tier_five) = build.tier_five {
+        let has_tier_five = build
+            .enhancements
+            .get(tier_five)
+            .is_some_and(|et| !et.tiers[5].is_empty());
+        if !has_tier_five {
+            violations.push(Violation::EmptyTierFive(tier_five));
+        }
+    }
+}
+
+/// The number of levels that unlock the tiers of `tree`, given the class of
+/// each level so far: the levels of its source for class trees, or else the
+/// character levels. `None` if the tree is a class tree that none of the
+/// levels can be spent in.
+pub(crate) fn levels_for_tree(
+    levels: &[Class],
+    content: &Registry,
+    tree: &EnhancementTree,
+) -> Option<u8> {
+    let count = |class| levels.iter().filter(|&&c| c == class).count();
+    let sources = content.tree(tree.name).map_or(&[][..], |ti| &ti.sources);
+
+    let levels = match tree.source {
+        Some(source) => count(source),
+        None if sources.is_empty() => levels.len(),
+        None => sources.iter().map(|&s| count(s)).max().unwrap_or(0),
+    };
+
+    if levels == 0 {
+        None
+    } else {
+        Some(levels as u8)
+    }
+}
+
+fn check_catalog(
+    build: &CharacterBuild,
+    content: &Registry,
+    violations: &mut Vec<Violation>,
+) {
+    for enhancement_tree in build.enhancements.trees.iter() {
+        let tree = enhancement_tree.name;
+        // Listed by `unchecked_trees()` instead
+        if !content.has_catalog(tree) {
+            continue;
+        }
+
+        // The catalog entries of the enhancements that were taken, so that
+        // prerequisites can be checked by their current names.
+        let taken: Vec<_> = enhancement_tree
+            .tiers
+            .iter()
+            .enumerate()
+            .flat_map(|(tier, es)| es.iter().map(move |e| (tier, e)))
+            .map(|(tier, e)| {
+                (tier, e, content.enhancement(tree, tier, &e.name))
+            })
+            .collect();
+
+        for &(tier, enhancement, info) in taken.iter() {
+            let info = match info {
+                Some(info) => info,
+                None => {
+                    violations.push(Violation::UnknownEnhancement {
+                        tree,
+                        tier,
+                        enhancement: enhancement.name.clone(),
+                    });
+                    continue;
+                },
+            };
+
+            let choice = enhancement.subenhancement.as_ref();
+            let valid_choice = match choice {
+                Some(c) => info.choices.contains(c),
+                None => info.choices.is_empty(),
+            };
+            if !valid_choice {
+                violations.push(Violation::InvalidChoice {
+                    tree,
+                    enhancement: enhancement.name.clone(),
+                    choice: choice.cloned(),
+                });
+            }
+
+            let ranks = enhancement.rank.map_or(1, |r| r.get());
+            if ranks > info.ranks {
+                violations.push(Violation::TooManyRanks {
+                    tree,
+                    enhancement: enhancement.name.clone(),
+                    ranks,
+                    max: info.ranks,
+                });
+            }
+
+            for requires in info.requires.iter() {
+                let has_prerequisite = taken
+                    .iter()
+                    .any(|(_, _, i)| i.is_some_and(|i| &i.name == requires));
+                if !has_prerequisite {
+                    violations.push(Violation::MissingPrerequisite {
+                        tree,
+                        enhancement: enhancement.name.clone(),
+                        requires: requires.clone(),
+                    });
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,329 @@
+//! Writing builds back out in the `.build` format that CBL reads.
+
+use crate::{
+    content::{self, Registry},
+    Ability,
+    BuildType,
+    CharacterBuild,
+    Class,
+    EnhancementTreeName,
+    Feat,
+    Race,
+    SecondaryFeatType,
+    Skill,
+    Stats,
+    HEROIC_LEVEL_CAP,
+    LEVELUP_COUNT,
+    LEVELUP_INTERVAL,
+};
+use std::io::{self, prelude::*};
+
+/// CBL writes its files with Windows line endings.
+const NEWLINE: &str = "\r\n";
+
+/// Writes a build using the names of `content::registry()`.
+pub fn write<W: Write>(
+    build: &CharacterBuild,
+    output: &mut W,
+) -> io::Result<()> {
+    write_with(build, &content::registry(), output)
+}
+
+/// Writes a build in the format that `parse_with()` reads, using the names
+/// that `content` gives to races, classes and enhancement trees.
+pub fn write_with<W: Write>(
+    build: &CharacterBuild,
+    content: &Registry,
+    output: &mut W,
+) -> io::Result<()> {
+    let mut out = String::with_capacity(4_096);
+
+    write_overview(build, content, &mut out);
+    write_stats(build, &mut out);
+    write_skills(build, &mut out);
+    write_feats(build, content, &mut out);
+    write_spells(build, content, &mut out);
+    write_enhancements(build, content, &mut out);
+
+    output.write_all(out.replace('\n', NEWLINE).as_bytes())
+}
+
+fn write_overview(
+    build: &CharacterBuild,
+    content: &Registry,
+    out: &mut String,
+) {
+    out.push_str("[Overview]\n\n");
+    out.push_str(&format!("Name: {}\n", build.name));
+    out.push_str(&format!("Race: {}\n", race_name(content, build.race)));
+    out.push_str(&format!("Alignment: {}\n", build.alignment));
+    out.push_str(&format!("MaxLevels: {}\n\n", build.max_levels));
+
+    // CBL lists the classes from the most levels to the fewest.
+    let classes = build.class_split();
+    for (class, _) in classes.iter() {
+        out.push_str(&format!("Class: {}\n", class_name(content, *class)));
+    }
+    out.push('\n');
+
+    // Single-class builds don't list their levels.
+    if classes.len() > 1 {
+        for (i, &class) in build.levels.iter().enumerate() {
+            out.push_str(&format!(
+                "Level: {}\t{}\n",
+                i + 1,
+                class_name(content, class),
+            ));
+        }
+        out.push('\n');
+    }
+    out.push('\n');
+}
+
+fn write_stats(build: &CharacterBuild, out: &mut String) {
+    out.push_str("[Stats]\n\n");
+    out.push_str(&format!("Preferred: {}\n\n", build.preferred_build_type));
+
+    for &build_type in BuildType::ALL.iter() {
+        if build.stats(build_type).is_none() {
+            out.push_str(&format!("{}: No\n", build_type));
+        }
+    }
+    // CBL still fills in the columns of disabled build types; the model
+    // doesn't keep them, so they are filled in from the preferred one.
+    let preferred = build.preferred_stats();
+    let columns: Vec<Option<&Stats>> = BuildType::ALL
+        .iter()
+        .map(|&bt| build.stats(bt).or(preferred))
+        .collect();
+    out.push_str(";    Advn  Chmp  Hero  Lgnd  Tome\n");
+    out.push_str(";    ----  ----  ----  ----  ----\n");
+    for &ability in Ability::ALL.iter() {
+        out.push_str(ability.abbreviation());
+        out.push(':');
+        for stats in columns.iter() {
+            out.push_str("  ");
+            out.push_str(&blank_if_zero(stats.map_or(0, |s| s[ability]), 2));
+            out.push_str("  ");
+        }
+        out.push_str("   ");
+        out.push_str(&blank_if_zero(build.stat_tomes[ability], 1));
+        out.push('\n');
+    }
+    out.push_str(";    ----  ----  ----  ----\n");
+    out.push_str(";   ");
+    for stats in columns.iter() {
+        out.push_str("  ");
+        out.push_str(&stats.map_or_else(
+            || "  ".to_owned(),
+            |s| format!("{:>2}", total_points(s)),
+        ));
+        out.push_str("  ");
+    }
+    out.push_str(" \n\n");
+
+    // CBL stores a "level 0" increase, which the model doesn't keep. The
+    // increases past `max_levels` are kept, as CBL does.
+    out.push_str("Levelup: 0\t\n");
+    for level in (1..=LEVELUP_COUNT as u8).map(|i| i * LEVELUP_INTERVAL) {
+        out.push_str(&format!("Levelup: {}\t", level));
+        if let Some(ability) = build.levelup_at(level) {
+            out.push_str(&ability.to_string());
+        }
+        out.push('\n');
+    }
+    out.push_str("\n\n");
+}
+
+fn write_skills(build: &CharacterBuild, out: &mut String) {
+    out.push_str("[Skills]\n\n");
+    out.push(';');
+    out.push_str(&" ".repeat(6));
+    for level in 1..=HEROIC_LEVEL_CAP {
+        out.push_str(&format!("{:>4}", level));
+    }
+    out.push_str("  Tome\n");
+    out.push(';');
+    out.push_str(&" ".repeat(8));
+    out.push_str(&"-".repeat(4 * (HEROIC_LEVEL_CAP as usize + 1)));
+    out.push('\n');
+
+    for &skill in Skill::ALL.iter() {
+        out.push_str(skill.to_7_chars());
+        out.push(':');
+        for level in 0..HEROIC_LEVEL_CAP as usize {
+            let points = build.skills.points_in_skill_at_level(skill, level);
+            out.push_str(&format!(" {} ", blank_if_zero(points, 2)));
+        }
+        out.push_str(&format!(
+            " {} ",
+            blank_if_zero(build.skills.tome(skill), 2)
+        ));
+        out.push('\n');
+    }
+    out.push_str("\n\n");
+}
+
+fn write_feats(build: &CharacterBuild, content: &Registry, out: &mut String) {
+    out.push_str("[Feats]\n\n");
+
+    let groups = [
+        ("Standard", &build.feats.standard_feats),
+        ("Legend", &build.feats.legend_feats),
+        ("Race", &build.feats.race_feats),
+        ("Class", &build.feats.class_feats),
+    ];
+    for (base_type, feats) in groups.iter().filter(|(_, fs)| !fs.is_empty()) {
+        for feat in feats.iter() {
+            out.push_str(&format!(
+                "{}: {} {}\t{}\n",
+                base_type,
+                secondary_type_name(content, feat),
+                feat.level,
+                feat.name,
+            ));
+        }
+        out.push('\n');
+    }
+    out.push('\n');
+}
+
+fn write_spells(build: &CharacterBuild, content: &Registry, out: &mut String) {
+    out.push_str("[Spells]\n\n");
+
+    for spellbook in build.spells.spellbooks.iter().flatten() {
+        let class = class_name(content, spellbook.class);
+        for (i, spells) in spellbook.spells_by_level.iter().enumerate() {
+            if spells.is_empty() {
+                continue;
+            }
+
+            for spell in spells.iter() {
+                out.push_str(&format!(
+                    "Spell: {} {}\t{}\n",
+                    class,
+                    i + 1,
+                    spell.as_deref().unwrap_or(""),
+                ));
+            }
+            out.push('\n');
+        }
+    }
+    out.push('\n');
+}
+
+fn write_enhancements(
+    build: &CharacterBuild,
+    content: &Registry,
+    out: &mut String,
+) {
+    out.push_str("[Enhancements]\n\n");
+
+    if let Some(tier_five) = build.tier_five {
+        let source =
+            build.enhancements.get(tier_five).and_then(|et| et.source);
+        out.push_str(&format!(
+            "Tier5: {}\n\n",
+            content
+                .tree_name_for_source(tier_five, source)
+                .map_or_else(|| tree_name(content, tier_five), str::to_owned),
+        ));
+    }
+
+    for enhancement_tree in build.enhancements.trees.iter() {
+        let tree = enhancement_tree.name;
+        // Builds that were put together by hand may lack the tree's source
+        // and class levels, so they are worked out from the levels.
+        let source = enhancement_tree.source.or_else(|| {
+            content.tree(tree).and_then(|ti| {
+                ti.sources
+                    .iter()
+                    .copied()
+                    .find(|s| build.levels.contains(s))
+            })
+        });
+
+        out.push_str(&format!(
+            "Tree: {}\n",
+            content
+                .tree_name_for_source(tree, source)
+                .map_or_else(|| tree_name(content, tree), str::to_owned),
+        ));
+        out.push_str(&format!("Type: {:?}\n", tree.tree_type()));
+        if let Some(class) = source {
+            out.push_str(&format!("Source: {}\n", class_name(content, class)));
+        }
+        let class_levels =
+            enhancement_tree
+                .class_levels
+                .unwrap_or_else(|| match source {
+                    Some(class) =>
+                        build.levels.iter().filter(|&&c| c == class).count()
+                            as u8,
+                    None => build.max_levels,
+                });
+        out.push_str(&format!("ClassLevels: {}\n", class_levels));
+
+        for (tier, enhancements) in enhancement_tree.tiers.iter().enumerate() {
+            for enhancement in enhancements.iter() {
+                out.push_str(&format!(
+                    "Ability: Tier {}: {}",
+                    tier, enhancement.name,
+                ));
+                if let Some(sub) = &enhancement.subenhancement {
+                    out.push_str(&format!(": {}", sub));
+                }
+                if let Some(rank) = enhancement.rank {
+                    out.push_str(&format!(" (Rank {})", rank));
+                }
+                out.push('\n');
+            }
+        }
+        out.push('\n');
+    }
+}
+
+/// Right-aligns `n` in `width` columns, writing `0` as blanks like CBL does.
+fn blank_if_zero(n: u8, width: usize) -> String {
+    if n == 0 {
+        " ".repeat(width)
+    } else {
+        format!("{:>width$}", n, width = width)
+    }
+}
+
+fn total_points(stats: &Stats) -> u16 {
+    Ability::ALL.iter().map(|&a| u16::from(stats[a])).sum()
+}
+
+pub(crate) fn race_name(content: &Registry, race: Race) -> String {
+    content
+        .race(race)
+        .map_or_else(|| format!("{:?}", race), |ri| ri.name.clone())
+}
+
+pub(crate) fn class_name(content: &Registry, class: Class) -> String {
+    content
+        .class(class)
+        .map_or_else(|| format!("{:?}", class), |ci| ci.name.clone())
+}
+
+pub(crate) fn tree_name(
+    content: &Registry,
+    tree: EnhancementTreeName,
+) -> String {
+    content
+        .tree(tree)
+        .map_or_else(|| format!("{:?}", tree), |ti| ti.name.clone())
+}
+
+fn secondary_type_name(content: &Registry, feat: &Feat) -> String {
+    match feat.secondary_type {
+        SecondaryFeatType::Heroic => "Heroic".to_owned(),
+        SecondaryFeatType::Epic => "Epic".to_owned(),
+        SecondaryFeatType::Destiny => "Destiny".to_owned(),
+        SecondaryFeatType::Legend => "Legend".to_owned(),
+        SecondaryFeatType::Class(c) => class_name(content, c),
+        SecondaryFeatType::Race(r) => race_name(content, r),
+    }
+}
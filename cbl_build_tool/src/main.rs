@@ -0,0 +1,542 @@
+// Character Builder Lite utilities
+extern crate cbl_build_parser;
+
+use cbl_build_parser::{
+    archive::{self, Archive, Query, QueryError},
+    content,
+    ddo_builder,
+    filename::BuildFilename,
+    forum,
+    guide::{self, GuideFormat},
+    migrate,
+    order::{self, Milestone},
+    share,
+    spreadsheet,
+    statistics::{self, TableFormat},
+    validate,
+};
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, prelude::*, BufReader},
+    path::Path,
+    process,
+};
+
+const USAGE: &str = "\
+Usage: cbl_build_tool [--content FILE] COMMAND [ARGS...]
+
+Commands:
+    check FILE...       List the game rules that each build breaks. Exits
+                        with 1 if any build breaks one. Trees whose
+                        enhancements aren't catalogued, and so couldn't be
+                        checked, are listed on stderr.
+    export-csv IN [DIR] Write a build as CSV tables for spreadsheets: its
+                        level plan, skill points, build points, feats and
+                        enhancements. Writes one file per table into DIR
+                        (e.g. DIR/skills.csv), or every table to stdout.
+    find DIR [QUERY...] List the builds under DIR that match every query,
+                        e.g. \"Drow and Warchanter tier 5 and feat Power
+                        Attack\", with a summary of each. Files that
+                        aren't valid builds are listed on stderr.
+    from-code CODE [OUT]
+                        Turn a share code back into a build. Writes to OUT,
+                        or to stdout.
+    from-ddob IN [OUT]  Turn a DDO Builder character file into a build, and
+                        list what couldn't be carried over. Writes to OUT,
+                        or to stdout.
+    guide [--text] IN [OUT]
+                        Write a level-by-level leveling guide, in Markdown
+                        or, with --text, in plain text. Writes to OUT, or
+                        to stdout.
+    import IN [OUT]     Turn a build exported as a forum post back into a
+                        build, and list what couldn't be carried over.
+                        Writes to OUT, or to stdout.
+    name [--role ROLE] [--tags TAGS] [--rename] FILE...
+                        Print the canonical filename of each build, e.g.
+                        \"brd15_ftr4_rog1_-_drw_-_trapper_-_WC_StD_-_
+                        THFs_none_BPlat_-_Wartrapper.build\". The role and
+                        tags (separated by _) are kept from a canonical
+                        filename unless they are given. With --rename,
+                        rename each file instead.
+    order [--count N] SPLIT [MILESTONE...]
+                        List the best N (default 1) orders to take the
+                        levels of a class split in, e.g. \"15 Bard/4
+                        Fighter/1 Rogue\", so that each milestone is
+                        reached in time.
+    parse-name FILENAME...
+                        List what a canonical filename says about its
+                        build.
+    reorder [--out OUT] IN [MILESTONE...]
+                        Rewrite a build with its levels in the best order
+                        for the milestones. Writes to OUT, or to stdout.
+    stats [--csv] DIR [OUT]
+                        Write statistics over the builds under DIR: feats
+                        by class split, feat timings, starting ability
+                        scores, tree popularity and skill points, as
+                        Markdown tables or, with --csv, CSV tables. Writes
+                        to OUT, or to stdout.
+    to-code IN          Print a build as a share code: a short string that
+                        can be pasted into chat or a URL.
+    to-ddob IN [OUT]    Write a build as a DDO Builder character file, and
+                        list what couldn't be carried over. Writes to OUT,
+                        or to stdout.
+    upgrade IN [OUT]    Rewrite a build using current names, and list what
+                        was renamed. Writes to OUT, or to stdout.
+
+Milestones:
+    \"Fighter 4 by 12\"     A class level by a character level
+    \"Bard spells 5 by 18\" A class's spell level by a character level
+    \"Bard tier 5 by 12\"   Tier 5 of a class's tree by a character level
+    \"Rogue first\"         The class of level 1
+
+Queries:
+    \"Drow\"                A race
+    \"4 Rogue\", \"Rogue\"    At least 4 (or 1) levels of a class
+    \"Warchanter tier 5\"   The tier 5 tree
+    \"Stalwart Defender 20 AP\"
+                        At least 20 (or, without \"20 AP\", 1) action
+                        points spent in a tree
+    \"feat Power Attack\"   A feat
+    \"spell Haste\"         A spell
+
+Options:
+    --content FILE      Patch the bundled game content with FILE.
+";
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut content_filename = None;
+    let mut command = None;
+    let mut command_args = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--content" => content_filename = args.next(),
+            _ if command.is_none() => command = Some(arg),
+            _ => command_args.push(arg),
+        }
+    }
+
+    if let Some(content_filename) = content_filename {
+        let mut registry = content::Registry::bundled();
+        if let Err(e) = registry.patch_file(&content_filename) {
+            fail(&format!("{}: {}", content_filename, e));
+        }
+        content::set_registry(registry);
+    }
+
+    match command.as_deref() {
+        Some("check") => check(&command_args),
+        Some("export-csv") => export_csv(&command_args),
+        Some("find") => find(&command_args),
+        Some("from-code") => from_code(&command_args),
+        Some("from-ddob") => from_ddob(&command_args),
+        Some("guide") => guide(&command_args),
+        Some("import") => import(&command_args),
+        Some("name") => name(&command_args),
+        Some("order") => order(&command_args),
+        Some("parse-name") => parse_name(&command_args),
+        Some("reorder") => reorder(&command_args),
+        Some("stats") => stats(&command_args),
+        Some("to-code") => to_code(&command_args),
+        Some("to-ddob") => to_ddob(&command_args),
+        Some("upgrade") => upgrade(&command_args),
+        _ => {
+            eprint!("{}", USAGE);
+            process::exit(2);
+        },
+    }
+}
+
+fn check(args: &[String]) {
+    if args.is_empty() {
+        fail(USAGE);
+    }
+
+    let mut valid = true;
+    for filename in args.iter() {
+        let build = read_build(filename);
+
+        for violation in validate::validate(&build) {
+            valid = false;
+            println!("{}: {}", filename, violation);
+        }
+        for tree in validate::unchecked_trees(&build) {
+            eprintln!(
+                "{}: the enhancements of {} weren't checked, as they aren't \
+                 catalogued",
+                filename, tree,
+            );
+        }
+    }
+
+    if !valid {
+        process::exit(1);
+    }
+}
+
+fn export_csv(args: &[String]) {
+    let in_filename = args.first().unwrap_or_else(|| fail(USAGE));
+    let tables = spreadsheet::tables(&read_build(in_filename));
+
+    let dir = match args.get(1) {
+        Some(dir) => Path::new(dir),
+        None => {
+            let csv = statistics::write_tables(&tables, TableFormat::Csv);
+            write_output(None, csv.as_bytes());
+            return;
+        },
+    };
+    if let Err(e) = fs::create_dir_all(dir) {
+        fail(&format!("{}: {}", dir.display(), e));
+    }
+    for table in tables.iter() {
+        let path = dir.join(format!("{}.csv", table.name));
+        if let Err(e) = fs::write(&path, table.write(TableFormat::Csv)) {
+            fail(&format!("{}: {}", path.display(), e));
+        }
+    }
+}
+
+fn find(args: &[String]) {
+    let dir = args.first().unwrap_or_else(|| fail(USAGE));
+    let mut query = Query::default();
+    for q in args[1..].iter() {
+        let parsed: Query = q
+            .parse()
+            .unwrap_or_else(|e: QueryError| fail(&e.to_string()));
+        query.conditions.extend(parsed.conditions);
+    }
+
+    let archive = Archive::index(dir)
+        .unwrap_or_else(|e| fail(&format!("{}: {}", dir, e)));
+    for (path, e) in archive.errors.iter() {
+        eprintln!("{}: {}", path.display(), e);
+    }
+    for entry in archive.query(&query) {
+        println!("{}: {}", entry.path.display(), entry.summary());
+    }
+}
+
+fn from_code(args: &[String]) {
+    let code = args.first().unwrap_or_else(|| fail(USAGE));
+    let build = share::decode(code).unwrap_or_else(|e| fail(&e.to_string()));
+
+    let mut out = Vec::new();
+    cbl_build_parser::write(&build, &mut out)
+        .unwrap_or_else(|e| fail(&e.to_string()));
+    write_output(args.get(1), &out);
+}
+
+fn from_ddob(args: &[String]) {
+    let in_filename = args.first().unwrap_or_else(|| fail(USAGE));
+    let in_file = File::open(in_filename)
+        .unwrap_or_else(|e| fail(&format!("{}: {}", in_filename, e)));
+
+    let (build, unmapped) = ddo_builder::import(&mut BufReader::new(in_file))
+        .unwrap_or_else(|e| fail(&format!("{}: {}", in_filename, e)));
+
+    let mut out = Vec::new();
+    cbl_build_parser::write(&build, &mut out)
+        .unwrap_or_else(|e| fail(&e.to_string()));
+    write_output(args.get(1), &out);
+
+    for u in unmapped.iter() {
+        eprintln!("{}: {}", in_filename, u);
+    }
+}
+
+fn guide(args: &[String]) {
+    let format = if args.iter().any(|a| a == "--text") {
+        GuideFormat::PlainText
+    } else {
+        GuideFormat::Markdown
+    };
+    let mut filenames = args.iter().filter(|a| *a != "--text");
+    let in_filename = filenames.next().unwrap_or_else(|| fail(USAGE));
+    let build = read_build(in_filename);
+
+    write_output(
+        filenames.next(),
+        guide::leveling_guide(&build, format).as_bytes(),
+    );
+}
+
+fn import(args: &[String]) {
+    let in_filename = args.first().unwrap_or_else(|| fail(USAGE));
+    let in_file = File::open(in_filename)
+        .unwrap_or_else(|e| fail(&format!("{}: {}", in_filename, e)));
+
+    let (build, unrecovered) = forum::import(&mut BufReader::new(in_file))
+        .unwrap_or_else(|e| fail(&format!("{}: {}", in_filename, e)));
+
+    let mut out = Vec::new();
+    cbl_build_parser::write(&build, &mut out)
+        .unwrap_or_else(|e| fail(&e.to_string()));
+    write_output(args.get(1), &out);
+
+    for u in unrecovered.iter() {
+        eprintln!("{}: {}", in_filename, u);
+    }
+}
+
+fn name(args: &[String]) {
+    let mut role = None;
+    let mut tags = None;
+    let mut rename = false;
+    let mut filenames = Vec::with_capacity(args.len());
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--role" =>
+                role = Some(args.next().unwrap_or_else(|| fail(USAGE))),
+            "--tags" =>
+                tags = Some(args.next().unwrap_or_else(|| fail(USAGE))),
+            "--rename" => rename = true,
+            _ => filenames.push(arg),
+        }
+    }
+    if filenames.is_empty() {
+        fail(USAGE);
+    }
+
+    for filename in filenames {
+        let path = Path::new(filename);
+        let current = path
+            .file_name()
+            .and_then(|f| f.to_str())
+            .and_then(|f| f.parse::<BuildFilename>().ok());
+        let mut canonical = BuildFilename::new(&read_build(filename));
+        match role {
+            Some(role) => canonical.role = Some(role.clone()),
+            None =>
+                canonical.role = current.as_ref().and_then(|c| c.role.clone()),
+        }
+        match tags {
+            Some(tags) =>
+                canonical.tags = tags.split('_').map(str::to_owned).collect(),
+            None =>
+                canonical.tags = current.map(|c| c.tags).unwrap_or_default(),
+        }
+
+        if rename {
+            let new_path = path.with_file_name(canonical.filename());
+            if new_path != path {
+                if new_path.exists() {
+                    fail(&format!("{}: already exists", new_path.display()));
+                }
+                fs::rename(path, &new_path)
+                    .unwrap_or_else(|e| fail(&format!("{}: {}", filename, e)));
+                println!("{} -> {}", filename, new_path.display());
+            }
+        } else {
+            println!("{}", canonical);
+        }
+    }
+}
+
+fn order(args: &[String]) {
+    let mut count = 1;
+    let mut positional = Vec::with_capacity(args.len());
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--count" => {
+                count = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or_else(|| fail(USAGE));
+            },
+            _ => positional.push(arg),
+        }
+    }
+    let split = positional.first().unwrap_or_else(|| fail(USAGE));
+    let split =
+        order::parse_split(split).unwrap_or_else(|e| fail(&e.to_string()));
+    let milestones = parse_milestones(&positional[1..]);
+
+    let orderings = order::orderings(&split, &milestones)
+        .unwrap_or_else(|e| fail(&e.to_string()));
+    let mut found = false;
+    for (i, ranked) in orderings.take(count).enumerate() {
+        found = true;
+        if i > 0 {
+            println!();
+        }
+        for (milestone, level) in
+            milestones.iter().zip(ranked.milestone_levels.iter())
+        {
+            println!("; {}: reached at level {}", milestone, level);
+        }
+        for (level, class) in ranked.levels.iter().enumerate() {
+            println!("Level: {}\t{}", level + 1, class);
+        }
+    }
+
+    if !found {
+        fail(&order::OrderError::Unsatisfiable.to_string());
+    }
+}
+
+fn parse_name(args: &[String]) {
+    if args.is_empty() {
+        fail(USAGE);
+    }
+
+    for (i, filename) in args.iter().enumerate() {
+        let file_name = Path::new(filename)
+            .file_name()
+            .and_then(|f| f.to_str())
+            .unwrap_or(filename);
+        let parsed: BuildFilename = file_name
+            .parse()
+            .unwrap_or_else(|e| fail(&format!("{}: {}", filename, e)));
+
+        if i > 0 {
+            println!();
+        }
+        println!("File: {}", filename);
+        println!("Name: {}", parsed.name);
+        println!("Race: {}", parsed.race);
+        println!("Split: {}", order::format_split(&parsed.split));
+        if let Some(role) = parsed.role {
+            println!("Role: {}", role);
+        }
+        for tree in parsed.trees.iter() {
+            println!("Tree: {}", tree);
+        }
+        for tag in parsed.tags.iter() {
+            println!("Tag: {}", tag);
+        }
+    }
+}
+
+fn reorder(args: &[String]) {
+    let mut out_filename = None;
+    let mut positional = Vec::with_capacity(args.len());
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => out_filename = args.next(),
+            _ => positional.push(arg),
+        }
+    }
+    let in_filename = positional.first().unwrap_or_else(|| fail(USAGE));
+    let mut build = read_build(in_filename);
+    let milestones = parse_milestones(&positional[1..]);
+
+    let best = order::best_order(&build.class_split(), &milestones)
+        .unwrap_or_else(|e| fail(&format!("{}: {}", in_filename, e)));
+    build
+        .reorder_levels(&best.levels)
+        .unwrap_or_else(|e| fail(&format!("{}: {}", in_filename, e)));
+
+    let mut out = Vec::new();
+    cbl_build_parser::write(&build, &mut out)
+        .unwrap_or_else(|e| fail(&e.to_string()));
+    write_output(out_filename, &out);
+}
+
+fn parse_milestones(args: &[&String]) -> Vec<Milestone> {
+    args.iter()
+        .map(|m| {
+            m.parse()
+                .unwrap_or_else(|e: order::OrderError| fail(&e.to_string()))
+        })
+        .collect()
+}
+
+fn stats(args: &[String]) {
+    let format = if args.iter().any(|a| a == "--csv") {
+        TableFormat::Csv
+    } else {
+        TableFormat::Markdown
+    };
+    let mut positional = args.iter().filter(|a| *a != "--csv");
+    let dir = positional.next().unwrap_or_else(|| fail(USAGE));
+
+    let paths = archive::build_paths(dir)
+        .unwrap_or_else(|e| fail(&format!("{}: {}", dir, e)));
+    let mut builds = Vec::with_capacity(paths.len());
+    for path in paths.iter() {
+        let parsed = File::open(path)
+            .map_err(cbl_build_parser::ParseError::from)
+            .and_then(|f| cbl_build_parser::parse(&mut BufReader::new(f)));
+        match parsed {
+            Ok(build) => builds.push(build),
+            Err(e) => eprintln!("{}: {}", path.display(), e),
+        }
+    }
+
+    let report = statistics::statistics(&builds).report(format);
+    write_output(positional.next(), report.as_bytes());
+}
+
+fn to_code(args: &[String]) {
+    let in_filename = args.first().unwrap_or_else(|| fail(USAGE));
+
+    println!("{}", share::encode(&read_build(in_filename)));
+}
+
+fn to_ddob(args: &[String]) {
+    let in_filename = args.first().unwrap_or_else(|| fail(USAGE));
+    let build = read_build(in_filename);
+
+    let mut out = Vec::new();
+    let unmapped = ddo_builder::export(&build, &mut out)
+        .unwrap_or_else(|e| fail(&e.to_string()));
+    write_output(args.get(1), &out);
+
+    for u in unmapped.iter() {
+        eprintln!("{}: {}", in_filename, u);
+    }
+}
+
+fn upgrade(args: &[String]) {
+    let in_filename = args.first().unwrap_or_else(|| fail(USAGE));
+    let in_file = File::open(in_filename)
+        .unwrap_or_else(|e| fail(&format!("{}: {}", in_filename, e)));
+
+    let (build, migrations) = migrate::parse_and_migrate(
+        &mut BufReader::new(in_file),
+        &content::registry(),
+    )
+    .unwrap_or_else(|e| fail(&format!("{}: {}", in_filename, e)));
+
+    let mut out = Vec::new();
+    cbl_build_parser::write(&build, &mut out)
+        .unwrap_or_else(|e| fail(&e.to_string()));
+    write_output(args.get(1), &out);
+
+    if migrations.is_empty() {
+        eprintln!("{}: already up to date", in_filename);
+    }
+    for migration in migrations.iter() {
+        eprintln!("{}: {}", in_filename, migration);
+    }
+}
+
+fn read_build(filename: &str) -> cbl_build_parser::CharacterBuild {
+    let file = File::open(filename)
+        .unwrap_or_else(|e| fail(&format!("{}: {}", filename, e)));
+
+    cbl_build_parser::parse(&mut BufReader::new(file))
+        .unwrap_or_else(|e| fail(&format!("{}: {}", filename, e)))
+}
+
+/// Writes `output` to the file `filename`, or to stdout.
+fn write_output(filename: Option<&String>, output: &[u8]) {
+    let written = match filename {
+        Some(filename) =>
+            File::create(filename).and_then(|mut f| f.write_all(output)),
+        None => io::stdout().lock().write_all(output),
+    };
+    if let Err(e) = written {
+        fail(&e.to_string());
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message.trim_end());
+    process::exit(1);
+}